@@ -1,3 +1,6 @@
 fn main() {
-    embuild::espidf::sysenv::output();
+    // the host tools (e.g. apkg2deck) are built without ESP-IDF
+    if std::env::var_os("CARGO_FEATURE_ESP").is_some() {
+        embuild::espidf::sysenv::output();
+    }
 }
@@ -0,0 +1,421 @@
+//! Converts Anki packages (`.apkg`/`.colpkg`) into cardworder decks.
+//!
+//! `apkg2deck [--history] [--overwrite] <package> <output dir>` writes `decks.jsn` and a
+//! `deckNNNN.dck` file per Anki deck into the output directory, copy them to the root of the SD card.
+//! With `--history` the review log of every card is replayed through FSRS so cards keep their
+//! intervals. Decks already in the output directory are only replaced with `--overwrite`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use cardworder::logic::{
+    deck::{deck_file, deck_index::DeckIndex, deck_preset::DeckPreset, model::Deck},
+    import::anki::{card_for_ord, replay_reviews, AnkiNoteType, AnkiReview},
+};
+use chrono::{DateTime, TimeZone, Utc};
+use rusqlite::{Connection, OpenFlags};
+
+/// Collection files in order of preference, newer Anki versions put a stub into the older ones.
+const COLLECTION_FILES: [&str; 3] = [
+    "collection.anki21b",
+    "collection.anki21",
+    "collection.anki2",
+];
+
+/// Newer collections separate deck levels with the unit separator instead of `::`.
+const DECK_LEVEL_SEPARATOR: char = '\u{1f}';
+
+/// `cards.queue` of a suspended Anki card.
+const QUEUE_SUSPENDED: i64 = -1;
+
+struct Options {
+    package: PathBuf,
+    output_dir: PathBuf,
+    with_history: bool,
+    is_overwriting: bool,
+}
+
+struct AnkiNote {
+    note_type_id: i64,
+    fields: String,
+    /// Space separated, with a space at both ends.
+    tags: String,
+    created: DateTime<Utc>,
+}
+
+struct AnkiCard {
+    id: i64,
+    note_id: i64,
+    deck_id: i64,
+    /// Template of a standard note, deletion number - 1 of a cloze note.
+    ord: i64,
+    queue: i64,
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: apkg2deck [--history] [--overwrite] <package.apkg> <output dir>");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = convert(&options) {
+        eprintln!("error: {:?}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_args() -> Result<Options> {
+    let mut with_history = false;
+    let mut is_overwriting = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--history" => with_history = true,
+            "--overwrite" => is_overwriting = true,
+            _ if arg.starts_with("--") => return Err(anyhow!("unknown option {}", arg)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    match <[PathBuf; 2]>::try_from(paths) {
+        Ok([package, output_dir]) => Ok(Options {
+            package,
+            output_dir,
+            with_history,
+            is_overwriting,
+        }),
+        Err(_) => Err(anyhow!("expected a package and an output directory")),
+    }
+}
+
+fn convert(options: &Options) -> Result<()> {
+    let collection_path = extract_collection(&options.package)?;
+    let result = convert_collection(&collection_path, options);
+    let _ = fs::remove_file(&collection_path);
+    result
+}
+
+/// Unpacks the SQLite collection into a temporary file, rusqlite can't read it from memory.
+fn extract_collection(package: &Path) -> Result<PathBuf> {
+    let file = fs::File::open(package)
+        .map_err(|e| anyhow!("Failed to open {}: {}", package.display(), e))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| anyhow!("{} is not an Anki package: {}", package.display(), e))?;
+
+    let name = COLLECTION_FILES
+        .iter()
+        .find(|name| archive.index_for_name(name).is_some())
+        .ok_or_else(|| anyhow!("no collection in {}", package.display()))?;
+
+    let mut contents = Vec::new();
+    archive.by_name(name)?.read_to_end(&mut contents)?;
+    if name.ends_with("anki21b") {
+        contents = decompress_zstd(&contents)?;
+    }
+
+    let path = std::env::temp_dir().join(format!("apkg2deck-{}.anki2", std::process::id()));
+    fs::File::create(&path)?.write_all(&contents)?;
+    Ok(path)
+}
+
+#[cfg(feature = "anki-zstd")]
+fn decompress_zstd(contents: &[u8]) -> Result<Vec<u8>> {
+    Ok(zstd::decode_all(contents)?)
+}
+
+#[cfg(not(feature = "anki-zstd"))]
+fn decompress_zstd(_contents: &[u8]) -> Result<Vec<u8>> {
+    Err(anyhow!(
+        "the package is exported by a newer Anki, rebuild with the anki-zstd feature \
+         or export with \"Support older Anki versions\""
+    ))
+}
+
+fn convert_collection(collection_path: &Path, options: &Options) -> Result<()> {
+    let conn = Connection::open_with_flags(collection_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let note_types = load_note_types(&conn)?;
+    let deck_names = load_deck_names(&conn)?;
+    let notes = load_notes(&conn)?;
+    let cards = load_cards(&conn)?;
+    let reviews = if options.with_history {
+        load_reviews(&conn)?
+    } else {
+        HashMap::new()
+    };
+
+    let index_path = options.output_dir.join(DeckIndex::FILE_NAME);
+    let mut index: DeckIndex = match fs::read_to_string(&index_path) {
+        Ok(index_str) => serde_json::from_str(&index_str)?,
+        Err(_) => DeckIndex::default(),
+    };
+
+    let scheduler = DeckPreset::new(DeckPreset::DEFAULT_ID, "Default").scheduler();
+    let mut decks: BTreeMap<String, Deck> = BTreeMap::new();
+    let mut existing_decks = BTreeSet::new();
+    let mut skipped = 0;
+    let mut skipped_cards = 0;
+    for (anki_note_id, note_cards) in cards {
+        let Some(note) = notes.get(&anki_note_id) else {
+            continue;
+        };
+        let Some(note_type) = note_types.get(&note.note_type_id) else {
+            skipped += 1;
+            continue;
+        };
+        let fields = note_type.note_fields(&note.fields);
+        if fields[0].is_empty() {
+            skipped += 1;
+            continue;
+        }
+
+        // cards of a note can be in different Anki decks, the note goes to the deck of its first card
+        let deck_name = deck_names
+            .get(&note_cards[0].deck_id)
+            .cloned()
+            .unwrap_or_else(|| "Default".to_string());
+        let deck = decks.entry(deck_name.clone()).or_insert_with(|| {
+            let info = match index.find_by_name(&deck_name) {
+                Some(info) => {
+                    existing_decks.insert(deck_name.clone());
+                    info.clone()
+                }
+                None => index.add_deck(&deck_name),
+            };
+            Deck::new(info)
+        });
+
+        let note_id = deck.add_note(fields, note.created);
+        if let Some(deck_note) = deck.note_mut(note_id) {
+            for tag in note.tags.split_whitespace() {
+                deck_note.add_tag(tag);
+            }
+        }
+
+        let mut imported_cards = Vec::new();
+        for anki_card in &note_cards {
+            let Some(card) = card_for_ord(deck, note_id, anki_card.ord, note.created)
+                .and_then(|id| deck.card_mut(id))
+            else {
+                skipped_cards += 1;
+                continue;
+            };
+            card.suspended = anki_card.queue == QUEUE_SUSPENDED;
+            if let Some(history) = reviews.get(&anki_card.id) {
+                replay_reviews(card, history, &scheduler);
+            }
+            imported_cards.push(card.clone());
+        }
+        // the note keeps only the cards it had in Anki, not every card of the deck templates
+        if let Some(deck_note) = deck.note(note_id).cloned() {
+            deck.restore_note(deck_note, &imported_cards);
+        }
+    }
+
+    if !existing_decks.is_empty() && !options.is_overwriting {
+        let names: Vec<&str> = existing_decks.iter().map(|name| name.as_str()).collect();
+        return Err(anyhow!(
+            "{} already has {}, pass --overwrite to replace them",
+            options.output_dir.display(),
+            names.join(", ")
+        ));
+    }
+
+    fs::create_dir_all(&options.output_dir)?;
+    for deck in decks.values() {
+        let path = options.output_dir.join(deck.info.file_name());
+        fs::write(&path, deck_file::write_deck(deck)?)
+            .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+        println!(
+            "{} -> {}: {} notes",
+            deck.info.name,
+            deck.info.file_name(),
+            deck.notes().len()
+        );
+    }
+    fs::write(&index_path, serde_json::to_string(&index)?)
+        .map_err(|e| anyhow!("Failed to write {}: {}", index_path.display(), e))?;
+
+    if skipped > 0 {
+        println!("skipped {} notes without a front field", skipped);
+    }
+    if skipped_cards > 0 {
+        println!(
+            "skipped {} cards of templates other than front and back",
+            skipped_cards
+        );
+    }
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn load_note_types(conn: &Connection) -> Result<HashMap<i64, AnkiNoteType>> {
+    let mut fields: BTreeMap<i64, (String, Vec<String>)> = BTreeMap::new();
+
+    if table_exists(conn, "notetypes")? {
+        let mut stmt = conn.prepare("SELECT id, name FROM notetypes")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (id, name) = row?;
+            fields.insert(id, (name, Vec::new()));
+        }
+
+        let mut stmt = conn.prepare("SELECT ntid, name FROM fields ORDER BY ntid, ord")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (id, name) = row?;
+            if let Some((_, names)) = fields.get_mut(&id) {
+                names.push(name);
+            }
+        }
+    } else {
+        let models: String = conn.query_row("SELECT models FROM col", [], |row| row.get(0))?;
+        let models: HashMap<String, serde_json::Value> = serde_json::from_str(&models)?;
+        for (id, model) in models {
+            let name = model["name"].as_str().unwrap_or_default().to_string();
+            let mut flds: Vec<(i64, String)> = model["flds"]
+                .as_array()
+                .map(|flds| {
+                    flds.iter()
+                        .map(|f| {
+                            let ord = f["ord"].as_i64().unwrap_or_default();
+                            (ord, f["name"].as_str().unwrap_or_default().to_string())
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            flds.sort();
+            let id = id
+                .parse()
+                .map_err(|e| anyhow!("bad note type id {}: {}", id, e))?;
+            fields.insert(id, (name, flds.into_iter().map(|(_, name)| name).collect()));
+        }
+    }
+
+    Ok(fields
+        .into_iter()
+        .map(|(id, (name, names))| (id, AnkiNoteType::new(&name, &names)))
+        .collect())
+}
+
+fn load_deck_names(conn: &Connection) -> Result<HashMap<i64, String>> {
+    let mut names = HashMap::new();
+
+    if table_exists(conn, "decks")? {
+        let mut stmt = conn.prepare("SELECT id, name FROM decks")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (id, name) = row?;
+            names.insert(id, name.replace(DECK_LEVEL_SEPARATOR, "::"));
+        }
+    } else {
+        let decks: String = conn.query_row("SELECT decks FROM col", [], |row| row.get(0))?;
+        let decks: HashMap<String, serde_json::Value> = serde_json::from_str(&decks)?;
+        for (id, deck) in decks {
+            let id = id
+                .parse()
+                .map_err(|e| anyhow!("bad deck id {}: {}", id, e))?;
+            names.insert(id, deck["name"].as_str().unwrap_or_default().to_string());
+        }
+    }
+
+    Ok(names)
+}
+
+fn load_notes(conn: &Connection) -> Result<HashMap<i64, AnkiNote>> {
+    let mut stmt = conn.prepare("SELECT id, mid, flds, tags FROM notes")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+        ))
+    })?;
+
+    let mut notes = HashMap::new();
+    for row in rows {
+        let (id, note_type_id, fields, tags) = row?;
+        // note ids are creation times in milliseconds
+        let created = Utc.timestamp_millis_opt(id).single().unwrap_or_default();
+        notes.insert(
+            id,
+            AnkiNote {
+                note_type_id,
+                fields,
+                tags,
+                created,
+            },
+        );
+    }
+    Ok(notes)
+}
+
+/// Cards by note id, in template order.
+fn load_cards(conn: &Connection) -> Result<BTreeMap<i64, Vec<AnkiCard>>> {
+    let mut stmt = conn.prepare("SELECT id, nid, did, ord, queue FROM cards ORDER BY nid, ord")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(AnkiCard {
+            id: row.get(0)?,
+            note_id: row.get(1)?,
+            deck_id: row.get(2)?,
+            ord: row.get(3)?,
+            queue: row.get(4)?,
+        })
+    })?;
+
+    let mut cards: BTreeMap<i64, Vec<AnkiCard>> = BTreeMap::new();
+    for row in rows {
+        let card = row?;
+        cards.entry(card.note_id).or_default().push(card);
+    }
+    Ok(cards)
+}
+
+fn load_reviews(conn: &Connection) -> Result<HashMap<i64, Vec<AnkiReview>>> {
+    let mut stmt = conn.prepare("SELECT id, cid, ease, type FROM revlog ORDER BY id")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, u8>(2)?,
+            row.get::<_, u8>(3)?,
+        ))
+    })?;
+
+    let mut reviews: HashMap<i64, Vec<AnkiReview>> = HashMap::new();
+    for row in rows {
+        let (id, card_id, ease, kind) = row?;
+        // review ids are review times in milliseconds
+        let Some(time) = Utc.timestamp_millis_opt(id).single() else {
+            continue;
+        };
+        reviews
+            .entry(card_id)
+            .or_default()
+            .push(AnkiReview { time, ease, kind });
+    }
+    Ok(reviews)
+}
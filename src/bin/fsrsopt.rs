@@ -0,0 +1,161 @@
+//! Fits FSRS weights of a deck preset to the review log copied from the SD card.
+//!
+//! `fsrsopt <sd dir> [--preset <id>] [--epochs <n>] [--dry-run]` reads `reviews.log`, `decks.jsn`,
+//! the deck files and `presets.jsn` from the directory, prints log-loss/RMSE before and after
+//! fitting and stores the new weights in `presets.jsn` if they predict the log better.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use cardworder::logic::{
+    deck::{
+        deck_file::DeckRecord,
+        deck_index::DeckIndex,
+        deck_preset::{DeckPreset, DeckPresets, PresetId},
+    },
+    optimizer::trainer::{optimize, Metrics, TrainerConfig, TrainingSet},
+    review::review_log::{self, ReviewLogLoader},
+};
+
+struct Options {
+    sd_dir: PathBuf,
+    preset_id: PresetId,
+    epochs: Option<usize>,
+    dry_run: bool,
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: fsrsopt <sd dir> [--preset <id>] [--epochs <n>] [--dry-run]");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("error: {:?}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_args() -> Result<Options> {
+    let mut sd_dir = None;
+    let mut preset_id = DeckPreset::DEFAULT_ID;
+    let mut epochs = None;
+    let mut dry_run = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--preset" => {
+                let value = args.next().ok_or_else(|| anyhow!("--preset needs an id"))?;
+                preset_id = value.parse()?;
+            }
+            "--epochs" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--epochs needs a number"))?;
+                epochs = Some(value.parse()?);
+            }
+            "--dry-run" => dry_run = true,
+            _ if arg.starts_with("--") => return Err(anyhow!("unknown option {}", arg)),
+            _ if sd_dir.is_none() => sd_dir = Some(PathBuf::from(arg)),
+            _ => return Err(anyhow!("unexpected argument {}", arg)),
+        }
+    }
+
+    Ok(Options {
+        sd_dir: sd_dir.ok_or_else(|| anyhow!("expected the SD card directory"))?,
+        preset_id,
+        epochs,
+        dry_run,
+    })
+}
+
+fn run(options: &Options) -> Result<()> {
+    let read = |name: &str| {
+        let path = options.sd_dir.join(name);
+        fs::read_to_string(&path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))
+    };
+
+    let index: DeckIndex = serde_json::from_str(&read(DeckIndex::FILE_NAME)?)?;
+    let mut presets: DeckPresets = match read(DeckPresets::FILE_NAME) {
+        Ok(presets_str) => serde_json::from_str(&presets_str)?,
+        Err(_) => DeckPresets::default(),
+    };
+    let preset = presets.preset_or_default(options.preset_id);
+
+    // only the header line of every deck is needed to know its preset
+    let mut deck_ids = Vec::new();
+    for info in &index.decks {
+        let deck_str = read(&info.file_name())?;
+        let header = match DeckRecord::parse_line(deck_str.lines().next().unwrap_or_default())? {
+            DeckRecord::Deck(header) => header,
+            _ => return Err(anyhow!("{} has no deck record", info.file_name())),
+        };
+        if header.options.preset_id == preset.id {
+            deck_ids.push(info.id);
+        }
+    }
+
+    let mut log_loader = ReviewLogLoader::default();
+    for line in read(review_log::FILE_NAME)?.lines() {
+        log_loader.push_line(line);
+    }
+    if log_loader.skipped_lines() > 0 {
+        println!("skipped {} damaged log lines", log_loader.skipped_lines());
+    }
+    let entries = log_loader.finish();
+    let set = TrainingSet::from_log(entries.iter().filter(|e| deck_ids.contains(&e.deck_id)));
+
+    let mut config = TrainerConfig::default();
+    if let Some(epochs) = options.epochs {
+        config.epochs = epochs;
+    }
+    println!(
+        "preset {} \"{}\": {} decks, {} cards",
+        preset.id,
+        preset.name,
+        deck_ids.len(),
+        set.card_count()
+    );
+    let report = optimize(&set, preset.weights, config, |epoch| {
+        println!("epoch {}/{}", epoch, config.epochs)
+    });
+    print_metrics("before", &report.before);
+    print_metrics("after", &report.after);
+    println!("weights: {:?}", report.weights);
+
+    if !report.is_improved() {
+        println!(
+            "fitted weights are not better, {} is left as is",
+            DeckPresets::FILE_NAME
+        );
+        return Ok(());
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+
+    match presets.find_mut(preset.id) {
+        Some(stored) => stored.weights = report.weights,
+        None => presets.presets.push(DeckPreset {
+            weights: report.weights,
+            ..preset
+        }),
+    }
+    let path = options.sd_dir.join(DeckPresets::FILE_NAME);
+    fs::write(&path, serde_json::to_string(&presets)?)
+        .map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+    println!("saved to {}", path.display());
+    Ok(())
+}
+
+fn print_metrics(label: &str, metrics: &Metrics) {
+    println!(
+        "{:>6}: log-loss {:.4}, RMSE {:.4} over {} reviews",
+        label, metrics.log_loss, metrics.rmse, metrics.reviews
+    );
+}
@@ -0,0 +1,363 @@
+//! Runs the app on a PC with the real views, to work on the UI without flashing the Cardputer.
+//!
+//! `simulator [--png <frames dir>] <sd dir>` uses the directory as the SD card and draws the
+//! screen in the terminal with half blocks, the terminal needs true color and 240x68 cells.
+//! With `--png` the frames are written to the directory as `frameNNNNN.png` instead, each one
+//! when the screen changes.
+//!
+//! PC keys are pressed on the Cardputer keyboard: letters and symbols go to the key typing them
+//! in the En or Ru layout, arrows, Home, End, Esc and Delete go through Fn like on the device.
+//! Ctrl+Space switches the layout. F10 quits.
+
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    fs,
+    io::{self, BufWriter, Stdout, Write},
+    path::PathBuf,
+    rc::Rc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use cardworder::{
+    cardputer_hal::{
+        cardputer_hal::CardputerHal,
+        desktop::{DirStorage, SystemClock},
+        input::{
+            keyboard::key_for_char,
+            keyboard_io::{KeyEvent, Scancode},
+        },
+        memory::OfflineNetwork,
+        platform::{Display, KeySource, SCREEN_HEIGHT, SCREEN_WIDTH},
+    },
+    logic::{view_manager::ViewManager, views::main_menu::MainMenuView},
+    ui::cardworder_ui::CardworderUi,
+};
+use crossterm::{
+    cursor, event,
+    event::{Event, KeyCode, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Color, Colors, Print, ResetColor, SetColors},
+    terminal,
+};
+use embedded_graphics::pixelcolor::{Rgb565, Rgb888, RgbColor};
+
+/// Roughly what the device does with a full screen redraw every loop.
+const LOOP_PAUSE: Duration = Duration::from_millis(16);
+
+struct Options {
+    sd_dir: PathBuf,
+    png_dir: Option<PathBuf>,
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: simulator [--png <frames dir>] <sd dir>");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("error: {:?}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_args() -> Result<Options> {
+    let mut sd_dir = None;
+    let mut png_dir = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--png" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--png needs a directory"))?;
+                png_dir = Some(PathBuf::from(value));
+            }
+            _ if arg.starts_with("--") => return Err(anyhow!("unknown option {}", arg)),
+            _ if sd_dir.is_none() => sd_dir = Some(PathBuf::from(arg)),
+            _ => return Err(anyhow!("unexpected argument {}", arg)),
+        }
+    }
+
+    Ok(Options {
+        sd_dir: sd_dir.ok_or_else(|| anyhow!("expected the SD card directory"))?,
+        png_dir,
+    })
+}
+
+fn run(options: &Options) -> Result<()> {
+    if !options.sd_dir.is_dir() {
+        return Err(anyhow!("{} is not a directory", options.sd_dir.display()));
+    }
+    let display: Box<dyn Display> = match &options.png_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)
+                .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+            Box::new(PngFrames::new(dir.clone()))
+        }
+        None => Box::new(TerminalDisplay::new()),
+    };
+
+    let _terminal = RawTerminal::enter(options.png_dir.is_none())?;
+    let is_quitting = Rc::new(Cell::new(false));
+    let keys = TerminalKeys::new(is_quitting.clone());
+
+    let mut hal = CardputerHal::build(
+        display,
+        Box::new(DirStorage::new(&options.sd_dir)),
+        Box::new(keys),
+        Box::new(OfflineNetwork),
+        Box::new(SystemClock),
+    );
+    let ui = CardworderUi::build(hal.take_screen());
+    let mut view_manager = ViewManager::new(hal, ui, Box::new(MainMenuView::default()));
+
+    while !is_quitting.get() {
+        view_manager.loop_logic();
+        thread::sleep(LOOP_PAUSE);
+    }
+    Ok(())
+}
+
+/// Raw mode for reading keys one by one, the alternate screen when drawing to the terminal.
+/// Restored on drop, panics included.
+struct RawTerminal {
+    is_drawing: bool,
+}
+
+impl RawTerminal {
+    fn enter(is_drawing: bool) -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        if is_drawing {
+            crossterm::execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        }
+        Ok(Self { is_drawing })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        if self.is_drawing {
+            let _ = crossterm::execute!(
+                io::stdout(),
+                ResetColor,
+                cursor::Show,
+                terminal::LeaveAlternateScreen
+            );
+        }
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Draws two pixel rows per terminal line: the upper half block takes the foreground color
+/// of the upper pixel and the background color of the lower one.
+struct TerminalDisplay {
+    out: BufWriter<Stdout>,
+    last_frame: Vec<Rgb565>,
+}
+
+impl TerminalDisplay {
+    fn new() -> Self {
+        Self {
+            out: BufWriter::new(io::stdout()),
+            last_frame: Vec::new(),
+        }
+    }
+}
+
+fn terminal_color(color: Rgb565) -> Color {
+    let color = Rgb888::from(color);
+    Color::Rgb {
+        r: color.r(),
+        g: color.g(),
+        b: color.b(),
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn show_frame(&mut self, pixels: &[Rgb565]) -> Result<()> {
+        if pixels == self.last_frame.as_slice() {
+            return Ok(());
+        }
+        let width = SCREEN_WIDTH as usize;
+        for (line, rows) in pixels.chunks(width * 2).enumerate() {
+            let (upper, lower) = rows.split_at(width.min(rows.len()));
+            queue!(self.out, cursor::MoveTo(0, line as u16))?;
+            let mut colors = None;
+            for (x, top) in upper.iter().enumerate() {
+                let bottom = lower.get(x).copied().unwrap_or(Rgb565::BLACK);
+                // most of the screen is one color, only changes are sent
+                if colors != Some((*top, bottom)) {
+                    colors = Some((*top, bottom));
+                    let colors = Colors::new(terminal_color(*top), terminal_color(bottom));
+                    queue!(self.out, SetColors(colors))?;
+                }
+                queue!(self.out, Print('▀'))?;
+            }
+        }
+        queue!(self.out, ResetColor)?;
+        self.out.flush()?;
+        self.last_frame = pixels.to_vec();
+        Ok(())
+    }
+
+    fn set_backlight(&mut self, _is_on: bool) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes every new frame to a numbered PNG file.
+struct PngFrames {
+    dir: PathBuf,
+    frames_written: usize,
+    last_frame: Vec<Rgb565>,
+}
+
+impl PngFrames {
+    fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            frames_written: 0,
+            last_frame: Vec::new(),
+        }
+    }
+}
+
+impl Display for PngFrames {
+    fn show_frame(&mut self, pixels: &[Rgb565]) -> Result<()> {
+        if pixels == self.last_frame.as_slice() {
+            return Ok(());
+        }
+        let path = self
+            .dir
+            .join(format!("frame{:05}.png", self.frames_written));
+        let file = fs::File::create(&path)
+            .map_err(|e| anyhow!("Failed to create {}: {}", path.display(), e))?;
+        let mut encoder = png::Encoder::new(io::BufWriter::new(file), SCREEN_WIDTH, SCREEN_HEIGHT);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data: Vec<u8> = pixels
+            .iter()
+            .flat_map(|pixel| {
+                let color = Rgb888::from(*pixel);
+                [color.r(), color.g(), color.b()]
+            })
+            .collect();
+        encoder.write_header()?.write_image_data(&data)?;
+
+        self.frames_written += 1;
+        self.last_frame = pixels.to_vec();
+        Ok(())
+    }
+
+    fn set_backlight(&mut self, _is_on: bool) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Turns PC key presses into presses and releases of Cardputer keys, one event per read
+/// like the real keyboard.
+struct TerminalKeys {
+    events: VecDeque<(KeyEvent, Scancode)>,
+    is_quitting: Rc<Cell<bool>>,
+}
+
+impl TerminalKeys {
+    fn new(is_quitting: Rc<Cell<bool>>) -> Self {
+        Self {
+            events: VecDeque::new(),
+            is_quitting,
+        }
+    }
+
+    fn read_terminal(&mut self) -> io::Result<()> {
+        while self.events.is_empty() && event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            if key.code == KeyCode::F(10) {
+                self.is_quitting.set(true);
+                continue;
+            }
+            let Some(keys) = cardputer_keys(key.code, key.modifiers) else {
+                continue;
+            };
+            // modifiers go down first and up last, like when typed on the device
+            self.events
+                .extend(keys.iter().map(|key| (KeyEvent::Pressed, *key)));
+            self.events
+                .extend(keys.iter().rev().map(|key| (KeyEvent::Released, *key)));
+        }
+        Ok(())
+    }
+}
+
+impl KeySource for TerminalKeys {
+    fn read_event(&mut self) -> Option<(KeyEvent, Scancode)> {
+        if self.events.is_empty() {
+            if let Err(e) = self.read_terminal() {
+                log::error!("error read terminal: {:?}", e);
+            }
+        }
+        self.events.pop_front()
+    }
+}
+
+/// Cardputer keys to hold for a PC key, modifiers first. `None` for keys the device doesn't have.
+fn cardputer_keys(code: KeyCode, modifiers: KeyModifiers) -> Option<Vec<Scancode>> {
+    let (key, is_shifted, is_fn) = match code {
+        // the character already tells if Shift was down
+        KeyCode::Char(c) => {
+            let (key, is_shifted) = key_for_char(c)?;
+            (key, is_shifted, false)
+        }
+        KeyCode::BackTab => (Scancode::Tab, true, false),
+        code => {
+            let (key, is_fn) = special_key(code)?;
+            (key, modifiers.contains(KeyModifiers::SHIFT), is_fn)
+        }
+    };
+    let held = [
+        (Scancode::Ctrl, modifiers.contains(KeyModifiers::CONTROL)),
+        (Scancode::Alt, modifiers.contains(KeyModifiers::ALT)),
+        (Scancode::Shift, is_shifted),
+        (Scancode::Fn, is_fn),
+    ];
+    let keys = held
+        .iter()
+        .filter(|(_, is_held)| *is_held)
+        .map(|(modifier, _)| *modifier)
+        .chain([key])
+        .collect();
+    Some(keys)
+}
+
+/// The key for a PC key that doesn't type a character and whether it goes with Fn.
+fn special_key(code: KeyCode) -> Option<(Scancode, bool)> {
+    let key = match code {
+        KeyCode::Enter => (Scancode::Enter, false),
+        KeyCode::Tab => (Scancode::Tab, false),
+        KeyCode::Backspace => (Scancode::Backspace, false),
+        KeyCode::Delete => (Scancode::Backspace, true),
+        KeyCode::Esc => (Scancode::Tilde, true),
+        KeyCode::Up => (Scancode::Semicolon, true),
+        KeyCode::Down => (Scancode::Period, true),
+        KeyCode::Left => (Scancode::Comma, true),
+        KeyCode::Right => (Scancode::Slash, true),
+        KeyCode::Home => (Scancode::LeftSquareBracket, true),
+        KeyCode::End => (Scancode::RightSquareBracket, true),
+        _ => return None,
+    };
+    Some(key)
+}
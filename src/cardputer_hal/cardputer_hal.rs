@@ -1,20 +1,28 @@
-use embedded_graphics::{pixelcolor::Rgb565, prelude::WebColors};
-use esp_idf_hal::{delay::Delay, prelude::Peripherals};
+use chrono::{DateTime, NaiveDateTime, Utc};
+#[cfg(feature = "esp")]
+use esp_idf_hal::prelude::Peripherals;
+#[cfg(feature = "esp")]
 use esp_idf_svc::eventloop::EspSystemEventLoop;
+#[cfg(feature = "esp")]
 use esp_idf_hal::gpio::{self, IOPin, Output, OutputPin, PinDriver};
+#[cfg(feature = "esp")]
 use esp_idf_svc::wifi::EspWifi;
 
-use crate::cardputer_hal::{
-    input::{keyboard::{InputLanguage, InputState, PressedSymbol}, keyboard_io::{CardputerKeyboard, Scancode, KeyEvent}},
-    screen::cardputer_screen::CardputerScreen,
-    sd::cardputer_sd::CardputerSd,
-    wifi::wifi::{CardWorderWifi, WifiConfig}};
+#[cfg(feature = "esp")]
+use crate::cardputer_hal::{esp_clock::EspClock, input::keyboard_io::CardputerKeyboard, screen::cardputer_screen::CardputerScreen, sd::cardputer_sd::CardputerSd, wifi::wifi::CardWorderWifi};
+use crate::{cardputer_hal::{
+    input::{keyboard::{InputLanguage, InputState, PressedSymbol}, keyboard_io::{Scancode, KeyEvent}},
+    platform::{Clock, Display, FileStorage, KeySource, Network, SdDirEntry, WifiConfig}},
+    logic::{deck::{deck_file::{self, DeckHeader, DeckLoader, DeckRecord}, deck_index::DeckIndex, deck_preset::DeckPresets, filtered_deck::FilteredDecks, model::{Deck, DeckId, DeckInfo}}, review::{review_log::{self, ReviewLogEntry, ReviewLogLoader}, undo::UndoStep}, settings::Settings}};
 
+/// The device as the views see it. On the Cardputer it is built by `new`, elsewhere
+/// `build` takes any implementations, like the in-memory ones of `memory`.
 pub struct CardputerHal<'a> {
-    screen: Option<CardputerScreen<'a>>,
-    sd: CardputerSd<'a, Delay>,
-    keyboard: CardputerKeyboard<'a>,
-    wifi: CardWorderWifi<'a>,
+    screen: Option<Box<dyn Display + 'a>>,
+    storage: Box<dyn FileStorage + 'a>,
+    keyboard: Box<dyn KeySource + 'a>,
+    network: Box<dyn Network + 'a>,
+    clock: Box<dyn Clock + 'a>,
 
     pub keyboard_state: KeyboardState,
 }
@@ -25,11 +33,18 @@ pub struct KeyboardState {
     pub pressed: Option<(KeyEvent,PressedSymbol)>,
 }
 
+impl KeyboardState {
+    /// Ctrl with a letter, matched by scancode so it works in the Ru layout too.
+    pub fn is_ctrl_with(&self, key: Scancode) -> bool {
+        self.input_state.ctrl_pressed && self.key == Some((KeyEvent::Pressed, key))
+    }
+}
+
+#[cfg(feature = "esp")]
 impl <'a>CardputerHal<'a> {
     pub fn new(peripherals: Peripherals, sysloop: EspSystemEventLoop) -> Self {
 
         let screen = CardputerScreen::build(
-            Rgb565::CSS_BLACK,
             peripherals.spi2,
             peripherals.pins.gpio36,
             peripherals.pins.gpio35,
@@ -71,6 +86,18 @@ impl <'a>CardputerHal<'a> {
 
         let wifi = CardWorderWifi::new(esp_wifi);
 
+        Self::build(Box::new(screen), Box::new(sd), Box::new(keyboard), Box::new(wifi), Box::new(EspClock))
+    }
+}
+
+impl <'a>CardputerHal<'a> {
+    pub fn build(
+        screen: Box<dyn Display + 'a>,
+        storage: Box<dyn FileStorage + 'a>,
+        keyboard: Box<dyn KeySource + 'a>,
+        network: Box<dyn Network + 'a>,
+        clock: Box<dyn Clock + 'a>,
+    ) -> Self {
         let input_state = InputState {
             ctrl_pressed: false,
             shift_pressed: false,
@@ -86,7 +113,7 @@ impl <'a>CardputerHal<'a> {
             pressed: None,
         };
 
-        Self { screen:Some(screen), sd, keyboard, wifi, keyboard_state }
+        Self { screen: Some(screen), storage, keyboard, network, clock, keyboard_state }
     }
 
     pub fn create_wifi_file_if_non_exists(
@@ -94,11 +121,11 @@ impl <'a>CardputerHal<'a> {
         ssid: heapless::String<32>,
         password: heapless::String<64>,
     ) -> anyhow::Result<()> {
-        let is_file_exists = { self.sd.is_file_exists("wifi_cfg.jsn").unwrap() };
+        let is_file_exists = { self.storage.is_file_exists("wifi_cfg.jsn").unwrap() };
         if !is_file_exists {
             let config = WifiConfig { ssid, password };
             let config_str = serde_json::to_string(&config).unwrap();
-            self.sd
+            self.storage
                 .write_file("wifi_cfg.jsn", &config_str)
                 .unwrap();
         }
@@ -107,29 +134,311 @@ impl <'a>CardputerHal<'a> {
 
     pub fn load_wifi_config(&mut self) -> anyhow::Result<WifiConfig> {
             let config_str = self
-            .sd
+            .storage
             .read_file("wifi_cfg.jsn")
-            .map_err(|e| anyhow::anyhow!("Failed to read wifi_cfg.jsn"))?;
+            .map_err(|e| anyhow::anyhow!("Failed to read wifi_cfg.jsn: {:?}", e))?;
 
         let config: WifiConfig = serde_json::from_str(&config_str)?;
 
         Ok(config)
     }
     
+    pub fn load_deck_index(&mut self) -> anyhow::Result<DeckIndex> {
+        let is_file_exists = self
+            .storage
+            .is_file_exists(DeckIndex::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to open SD card: {:?}", e))?;
+        if !is_file_exists {
+            return Ok(DeckIndex::default());
+        }
+
+        let index_str = self
+            .storage
+            .read_file(DeckIndex::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", DeckIndex::FILE_NAME, e))?;
+
+        let index: DeckIndex = serde_json::from_str(&index_str)?;
+
+        Ok(index)
+    }
+
+    pub fn save_deck_index(&mut self, index: &DeckIndex) -> anyhow::Result<()> {
+        let index_str = serde_json::to_string(index)?;
+        self.storage
+            .write_file(DeckIndex::FILE_NAME, &index_str)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {:?}", DeckIndex::FILE_NAME, e))
+    }
+
+    pub fn load_settings(&mut self) -> anyhow::Result<Settings> {
+        let is_file_exists = self
+            .storage
+            .is_file_exists(Settings::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to open SD card: {:?}", e))?;
+        if !is_file_exists {
+            return Ok(Settings::default());
+        }
+
+        let settings_str = self
+            .storage
+            .read_file(Settings::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", Settings::FILE_NAME, e))?;
+
+        let settings: Settings = serde_json::from_str(&settings_str)?;
+
+        Ok(settings)
+    }
+
+    pub fn load_presets(&mut self) -> anyhow::Result<DeckPresets> {
+        let is_file_exists = self
+            .storage
+            .is_file_exists(DeckPresets::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to open SD card: {:?}", e))?;
+        if !is_file_exists {
+            return Ok(DeckPresets::default());
+        }
+
+        let presets_str = self
+            .storage
+            .read_file(DeckPresets::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", DeckPresets::FILE_NAME, e))?;
+
+        let presets: DeckPresets = serde_json::from_str(&presets_str)?;
+
+        Ok(presets)
+    }
+
+    pub fn save_presets(&mut self, presets: &DeckPresets) -> anyhow::Result<()> {
+        let presets_str = serde_json::to_string(presets)?;
+        self.storage
+            .write_file(DeckPresets::FILE_NAME, &presets_str)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {:?}", DeckPresets::FILE_NAME, e))
+    }
+
+    pub fn load_filtered_decks(&mut self) -> anyhow::Result<FilteredDecks> {
+        let is_file_exists = self
+            .storage
+            .is_file_exists(FilteredDecks::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to open SD card: {:?}", e))?;
+        if !is_file_exists {
+            return Ok(FilteredDecks::default());
+        }
+
+        let filtered_str = self
+            .storage
+            .read_file(FilteredDecks::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", FilteredDecks::FILE_NAME, e))?;
+
+        let filtered: FilteredDecks = serde_json::from_str(&filtered_str)?;
+
+        Ok(filtered)
+    }
+
+    pub fn save_filtered_decks(&mut self, filtered: &FilteredDecks) -> anyhow::Result<()> {
+        let filtered_str = serde_json::to_string(filtered)?;
+        self.storage
+            .write_file(FilteredDecks::FILE_NAME, &filtered_str)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {:?}", FilteredDecks::FILE_NAME, e))
+    }
+
+    /// Only the first line of the deck file: its name and options.
+    pub fn load_deck_header(&mut self, info: &DeckInfo) -> anyhow::Result<DeckHeader> {
+        let mut header = None;
+        self.storage
+            .read_lines(&info.file_name(), &mut |line| {
+                if header.is_none() {
+                    header = Some(DeckRecord::parse_line(line));
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", info.file_name(), e))?;
+
+        match header {
+            Some(Ok(DeckRecord::Deck(header))) => Ok(header),
+            Some(Err(e)) => Err(e),
+            _ => Err(anyhow::anyhow!("{} has no deck record", info.file_name())),
+        }
+    }
+
+    /// Reads the deck line by line. If some lines are damaged the scheduling is rebuilt
+    /// from the review log, cards are brought in line with the templates and the repaired
+    /// deck is written back.
+    pub fn load_deck(&mut self, info: &DeckInfo) -> anyhow::Result<Deck> {
+        let mut loader = DeckLoader::default();
+        let mut load_error = None;
+        self.storage
+            .read_lines(&info.file_name(), &mut |line| {
+                if load_error.is_none() {
+                    load_error = loader.push_line(line).err();
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", info.file_name(), e))?;
+        if let Some(e) = load_error {
+            return Err(e);
+        }
+
+        let skipped_lines = loader.skipped_lines();
+        let mut deck = loader.finish()?;
+        if skipped_lines > 0 {
+            log::warn!("{} damaged lines in {}, rebuilding from {}", skipped_lines, info.file_name(), review_log::FILE_NAME);
+            let entries = self.load_review_log(&[info.id])?;
+            let scheduler = self.load_presets()?.preset_or_default(deck.options.preset_id).scheduler();
+            let rebuilt = review_log::rebuild_deck(&mut deck, &entries, &scheduler);
+            log::info!("rebuilt {} cards", rebuilt);
+        }
+
+        // templates may have been switched on or off in the deck header
+        let (added, removed) = deck.sync_cards(self.now());
+        if added > 0 || removed > 0 {
+            log::info!("{}: added {} cards, removed {} cards", info.file_name(), added, removed);
+        }
+        if skipped_lines > 0 || added > 0 || removed > 0 {
+            self.save_deck(&deck)?;
+        }
+        Ok(deck)
+    }
+
+    /// Total size of the files of the decks, a deck without a file counts as empty.
+    pub fn deck_files_size(&mut self, decks: &[DeckInfo]) -> anyhow::Result<u32> {
+        let entries = self.list_dir("/")?;
+        Ok(decks
+            .iter()
+            .filter_map(|info| entries.iter().find(|e| !e.is_dir && e.name.eq_ignore_ascii_case(&info.file_name())))
+            .map(|e| e.size)
+            .sum())
+    }
+
+    pub fn save_deck(&mut self, deck: &Deck) -> anyhow::Result<()> {
+        self.storage
+            .write_lines(&deck.info.file_name(), &mut deck_file::deck_lines(deck))
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {:?}", deck.info.file_name(), e))
+    }
+
+    pub fn append_review_log(&mut self, entry: &ReviewLogEntry) -> anyhow::Result<()> {
+        let line = entry.to_line()?;
+        self.storage
+            .append_line(review_log::FILE_NAME, line.trim_end())
+            .map_err(|e| anyhow::anyhow!("Failed to append {}: {:?}", review_log::FILE_NAME, e))
+    }
+
+    /// Takes an undone grade out of the journal. Its line is blanked instead of removed,
+    /// so only the latest entry still in the journal can go.
+    pub fn remove_review_log_entry(&mut self, entry: &ReviewLogEntry) -> anyhow::Result<()> {
+        let line = entry.to_line()?;
+        let is_removed = self
+            .storage
+            .blank_last_line(review_log::FILE_NAME, line.trim_end())
+            .map_err(|e| anyhow::anyhow!("Failed to update {}: {:?}", review_log::FILE_NAME, e))?;
+        if !is_removed {
+            return Err(anyhow::anyhow!("{} doesn't end with the undone grade", review_log::FILE_NAME));
+        }
+        Ok(())
+    }
+
+    /// Undoes a change of a deck that is not in memory: the deck is read, restored and written back.
+    pub fn undo_in_deck(&mut self, step: &UndoStep) -> anyhow::Result<()> {
+        let index = self.load_deck_index()?;
+        let info = index.decks.iter().find(|d| d.id == step.deck_id).ok_or_else(|| anyhow::anyhow!("no deck {}", step.deck_id))?;
+        let mut deck = self.load_deck(info)?;
+        step.restore(&mut deck);
+        // the journal goes first, like when grading
+        if let Some(entry) = &step.log {
+            if let Err(e) = self.remove_review_log_entry(entry) {
+                log::warn!("undone grade left in the journal: {:?}", e);
+            }
+        }
+        self.save_deck(&deck)
+    }
+
+    /// Journal entries of the given decks, a missing journal is an empty one.
+    pub fn load_review_log(&mut self, deck_ids: &[DeckId]) -> anyhow::Result<Vec<ReviewLogEntry>> {
+        let is_file_exists = self
+            .storage
+            .is_file_exists(review_log::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to open SD card: {:?}", e))?;
+        if !is_file_exists {
+            return Ok(Vec::new());
+        }
+
+        let mut loader = ReviewLogLoader::for_decks(deck_ids);
+        self.storage
+            .read_lines(review_log::FILE_NAME, &mut |line| loader.push_line(line))
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", review_log::FILE_NAME, e))?;
+        if loader.skipped_lines() > 0 {
+            log::warn!("skipped {} damaged lines of {}", loader.skipped_lines(), review_log::FILE_NAME);
+        }
+        Ok(loader.finish())
+    }
+
+    /// Feeds every journal entry to `on_entry` without keeping them, a missing journal is an empty one.
+    pub fn read_review_log(&mut self, mut on_entry: impl FnMut(&ReviewLogEntry)) -> anyhow::Result<()> {
+        let is_file_exists = self
+            .storage
+            .is_file_exists(review_log::FILE_NAME)
+            .map_err(|e| anyhow::anyhow!("Failed to open SD card: {:?}", e))?;
+        if !is_file_exists {
+            return Ok(());
+        }
+
+        let mut skipped_lines = 0;
+        self.storage
+            .read_lines(review_log::FILE_NAME, &mut |line| {
+                if line.trim().is_empty() {
+                    return;
+                }
+                match ReviewLogEntry::parse_line(line) {
+                    Ok(entry) => on_entry(&entry),
+                    Err(_) => skipped_lines += 1,
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", review_log::FILE_NAME, e))?;
+        if skipped_lines > 0 {
+            log::warn!("skipped {} damaged lines of {}", skipped_lines, review_log::FILE_NAME);
+        }
+        Ok(())
+    }
+
+    pub fn list_dir(&mut self, path: &str) -> anyhow::Result<Vec<SdDirEntry>> {
+        self.storage
+            .list_dir(path)
+            .map_err(|e| anyhow::anyhow!("Failed to list {}: {:?}", path, e))
+    }
+
+    pub fn read_lines(&mut self, path: &str, mut on_line: impl FnMut(&str)) -> anyhow::Result<()> {
+        self.storage
+            .read_lines(path, &mut on_line)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {:?}", path, e))
+    }
+
     pub fn connect_wifi(&mut self, wifi_config: WifiConfig) -> anyhow::Result<()> {
-        self.wifi.connect(wifi_config).map_err(|e| anyhow::anyhow!("Failed to connect to wifi"))
+        self.network.connect(wifi_config).map_err(|e| anyhow::anyhow!("Failed to connect to wifi: {:?}", e))
     }
 
     pub fn stop_wifi(&mut self) -> anyhow::Result<()> {
-        self.wifi.stop().map_err(|e| anyhow::anyhow!("Failed to stop wifi"))
+        self.network.stop().map_err(|e| anyhow::anyhow!("Failed to stop wifi: {:?}", e))
+    }
+
+    /// Sets the clock over the network, wifi has to be connected.
+    pub fn sync_time(&mut self) -> anyhow::Result<()> {
+        self.network.sync_time().map_err(|e| anyhow::anyhow!("Failed to sync time: {:?}", e))
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    pub fn local_now(&self) -> NaiveDateTime {
+        self.clock.local_now()
+    }
+
+    pub fn set_time_zone(&mut self, tz: &str) {
+        self.clock.set_time_zone(tz);
     }
 
-    pub fn take_screen(&mut self) -> CardputerScreen<'a> {
-        core::mem::replace(&mut self.screen, None).unwrap()
+    pub fn take_screen(&mut self) -> Box<dyn Display + 'a> {
+        self.screen.take().unwrap()
     }
 
     pub fn update_keyboard_state(&mut self) {
-        let key = self.keyboard.read_events();
+        let key = self.keyboard.read_event();
 
         let pressed = match key {
             Some((event, key)) => self.keyboard_state.input_state.eat_keys(event, key).map(|f| (event, f)),
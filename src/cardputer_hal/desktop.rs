@@ -0,0 +1,177 @@
+//! The PC side of the simulator: a directory stands for the SD card and the system clock is used.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+
+use crate::cardputer_hal::{
+    memory::blank_last_line_in,
+    platform::{Clock, FileStorage, SdDirEntry},
+};
+
+/// Files of a directory, like a copy of the SD card.
+pub struct DirStorage {
+    root: PathBuf,
+}
+
+impl DirStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// FAT doesn't care about case, so a file copied from the card as `DECKS.JSN` is still found.
+    fn resolve(&self, path: &str) -> PathBuf {
+        let mut resolved = self.root.clone();
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            let exact = resolved.join(name);
+            resolved = if exact.exists() {
+                exact
+            } else {
+                find_ignoring_case(&resolved, name).unwrap_or(exact)
+            };
+        }
+        resolved
+    }
+}
+
+fn find_ignoring_case(dir: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(name)
+        })
+        .map(|entry| entry.path())
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> anyhow::Error {
+    anyhow!("{}: {}", path.display(), e)
+}
+
+impl FileStorage for DirStorage {
+    fn read_file(&mut self, path: &str) -> Result<String> {
+        let path = self.resolve(path);
+        fs::read_to_string(&path).map_err(|e| io_error(&path, e))
+    }
+
+    fn read_lines(&mut self, path: &str, on_line: &mut dyn FnMut(&str)) -> Result<()> {
+        let path = self.resolve(path);
+        let file = fs::File::open(&path).map_err(|e| io_error(&path, e))?;
+        let mut is_first_line = true;
+        for line in BufReader::new(file).split(b'\n') {
+            let mut line = line.map_err(|e| io_error(&path, e))?;
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            let text = String::from_utf8_lossy(&line);
+            let text = if is_first_line {
+                text.trim_start_matches('\u{feff}')
+            } else {
+                &text
+            };
+            on_line(text);
+            is_first_line = false;
+        }
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> Result<()> {
+        let path = self.resolve(path);
+        fs::write(&path, contents).map_err(|e| io_error(&path, e))
+    }
+
+    fn write_lines(
+        &mut self,
+        path: &str,
+        lines: &mut dyn Iterator<Item = Result<String>>,
+    ) -> Result<()> {
+        let path = self.resolve(path);
+        let file = fs::File::create(&path).map_err(|e| io_error(&path, e))?;
+        let mut file = BufWriter::new(file);
+        for line in lines {
+            file.write_all(line?.as_bytes())
+                .map_err(|e| io_error(&path, e))?;
+        }
+        file.flush().map_err(|e| io_error(&path, e))
+    }
+
+    fn append_line(&mut self, path: &str, line: &str) -> Result<()> {
+        let path = self.resolve(path);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|e| io_error(&path, e))?;
+        let mut contents = format!("{}\n", line);
+        if file.metadata().map_err(|e| io_error(&path, e))?.len() > 0 {
+            let mut last_byte = [0u8; 1];
+            file.seek(SeekFrom::End(-1))
+                .and_then(|_| file.read_exact(&mut last_byte))
+                .map_err(|e| io_error(&path, e))?;
+            if last_byte[0] != b'\n' {
+                contents.insert(0, '\n');
+            }
+        }
+        file.write_all(contents.as_bytes())
+            .map_err(|e| io_error(&path, e))
+    }
+
+    fn blank_last_line(&mut self, path: &str, line: &str) -> Result<bool> {
+        let path = self.resolve(path);
+        let mut contents = fs::read_to_string(&path).map_err(|e| io_error(&path, e))?;
+        if !blank_last_line_in(&mut contents, line) {
+            return Ok(false);
+        }
+        fs::write(&path, contents).map_err(|e| io_error(&path, e))?;
+        Ok(true)
+    }
+
+    fn is_file_exists(&mut self, path: &str) -> Result<bool> {
+        Ok(self.resolve(path).is_file())
+    }
+
+    fn list_dir(&mut self, path: &str) -> Result<Vec<SdDirEntry>> {
+        let path = self.resolve(path);
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&path).map_err(|e| io_error(&path, e))? {
+            let entry = entry.map_err(|e| io_error(&path, e))?;
+            let metadata = entry.metadata().map_err(|e| io_error(&entry.path(), e))?;
+            entries.push(SdDirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                long_name: None,
+                size: metadata.len() as u32,
+                is_dir: metadata.is_dir(),
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+}
+
+/// The clock of the PC, already right, so there is nothing to sync.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn local_now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+
+    fn set_time_zone(&mut self, tz: &str) {
+        env::set_var("TZ", tz);
+    }
+}
@@ -0,0 +1,49 @@
+use std::ffi::CString;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use esp_idf_sys::{localtime_r, setenv, time, time_t, tm, tzset};
+
+use crate::cardputer_hal::platform::Clock;
+
+/// The system clock, set over NTP on start.
+#[derive(Debug, Default)]
+pub struct EspClock;
+
+impl Clock for EspClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn local_now(&self) -> NaiveDateTime {
+        let mut tm = tm {
+            tm_sec: 0,
+            tm_min: 0,
+            tm_hour: 0,
+            tm_mday: 0,
+            tm_mon: 0,
+            tm_year: 0,
+            tm_wday: 0,
+            tm_yday: 0,
+            tm_isdst: 0,
+        };
+        let mut now_time: time_t = 0;
+        unsafe {
+            time(&mut now_time);
+            localtime_r(&now_time, &mut tm);
+        }
+        NaiveDate::from_ymd_opt(tm.tm_year + 1900, tm.tm_mon as u32 + 1, tm.tm_mday as u32)
+            .and_then(|date| {
+                date.and_hms_opt(tm.tm_hour as u32, tm.tm_min as u32, tm.tm_sec as u32)
+            })
+            .unwrap_or_default()
+    }
+
+    fn set_time_zone(&mut self, tz: &str) {
+        let tz = CString::new(tz).unwrap_or_default();
+        unsafe {
+            let env_tz = b"TZ\0";
+            setenv(env_tz.as_ptr() as *const i8, tz.as_ptr(), 1);
+            tzset();
+        }
+    }
+}
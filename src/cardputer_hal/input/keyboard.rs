@@ -18,6 +18,8 @@ pub enum PressedSymbol {
     ArrowDown,
     ArrowLeft,
     ArrowRight,
+    Home,
+    End,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -76,11 +78,22 @@ impl InputState {
             (_, Scancode::Slash, _, true) => Some(PressedSymbol::ArrowRight),
             (_, Scancode::Comma, _, true) => Some(PressedSymbol::ArrowLeft),
             (_, Scancode::Period, _, true) => Some(PressedSymbol::ArrowDown),
+            (_, Scancode::LeftSquareBracket, _, true) => Some(PressedSymbol::Home),
+            (_, Scancode::RightSquareBracket, _, true) => Some(PressedSymbol::End),
             _ => self.key_to_pressed_symbol(key),
         };
     }
 }
 
+/// The key typing `c` in either layout and whether it needs Shift, for keys typed on a PC.
+pub fn key_for_char(c: char) -> Option<(Scancode, bool)> {
+    let maps = [(SYMBOL_MAP_EN, false), (SYMBOL_MAP_EN_SHIFTED, true), (SYMBOL_MAP_RU, false), (SYMBOL_MAP_RU_SHIFTED, true)];
+    maps.iter().find_map(|(map, shift)| {
+        let index = map.iter().position(|symbol| *symbol == Some(PressedSymbol::Char(c)))?;
+        Some((Scancode::ALL[index], *shift))
+    })
+}
+
 const SYMBOL_MAP_EN: [Option<PressedSymbol>; 56] = [
     None,
     Some(PressedSymbol::Char('z')),
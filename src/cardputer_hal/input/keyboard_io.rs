@@ -1,12 +1,19 @@
+#[cfg(feature = "esp")]
 use esp_idf_hal::gpio::{AnyIOPin, AnyOutputPin, PinDriver};
 
+#[cfg(feature = "esp")]
+use crate::cardputer_hal::platform::KeySource;
+
+#[cfg(feature = "esp")]
 type KeyboardState = [u8; 8];
+#[cfg(feature = "esp")]
 pub struct CardputerKeyboard<'a> {
     mux: [PinDriver<'a, AnyOutputPin, esp_idf_hal::gpio::Output>; 3],
     columns: [PinDriver<'a, AnyIOPin, esp_idf_hal::gpio::Input>; 7],
     state: KeyboardState,
 }
 
+#[cfg(feature = "esp")]
 impl<'a> CardputerKeyboard<'a> {
     pub fn new(
         mux: [PinDriver<'a, AnyOutputPin, esp_idf_hal::gpio::Output>; 3],
@@ -103,6 +110,13 @@ impl<'a> CardputerKeyboard<'a> {
     }
 }
 
+#[cfg(feature = "esp")]
+impl KeySource for CardputerKeyboard<'_> {
+    fn read_event(&mut self) -> Option<(KeyEvent, Scancode)> {
+        self.read_events()
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Scancode {
     Space = 6, // register 0 msb
@@ -170,12 +184,75 @@ pub enum Scancode {
     Tilde = 49,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl Scancode {
+    /// All keys, a key is at the index of its value.
+    pub const ALL: [Scancode; 56] = [
+        Scancode::Opt,
+        Scancode::Z,
+        Scancode::C,
+        Scancode::B,
+        Scancode::M,
+        Scancode::Period,
+        Scancode::Space,
+        Scancode::Shift,
+        Scancode::S,
+        Scancode::F,
+        Scancode::H,
+        Scancode::K,
+        Scancode::Semicolon,
+        Scancode::Enter,
+        Scancode::Q,
+        Scancode::E,
+        Scancode::T,
+        Scancode::U,
+        Scancode::O,
+        Scancode::LeftSquareBracket,
+        Scancode::BackSlash,
+        Scancode::_1,
+        Scancode::_3,
+        Scancode::_5,
+        Scancode::_7,
+        Scancode::_9,
+        Scancode::Underscore,
+        Scancode::Backspace,
+        Scancode::Ctrl,
+        Scancode::Alt,
+        Scancode::X,
+        Scancode::V,
+        Scancode::N,
+        Scancode::Comma,
+        Scancode::Slash,
+        Scancode::Fn,
+        Scancode::A,
+        Scancode::D,
+        Scancode::G,
+        Scancode::J,
+        Scancode::L,
+        Scancode::Quote,
+        Scancode::Tab,
+        Scancode::W,
+        Scancode::R,
+        Scancode::Y,
+        Scancode::I,
+        Scancode::P,
+        Scancode::RightSquareBracket,
+        Scancode::Tilde,
+        Scancode::_2,
+        Scancode::_4,
+        Scancode::_6,
+        Scancode::_8,
+        Scancode::_0,
+        Scancode::Equal,
+    ];
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum KeyEvent {
     Pressed,
     Released,
 }
 
+#[cfg(feature = "esp")]
 const KEY_MAP: [Scancode; 56] = [
     Scancode::Opt,
     Scancode::Z,
@@ -0,0 +1,361 @@
+//! In-memory stand-ins for the device, for running the app on a PC and for tests.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use embedded_graphics::pixelcolor::Rgb565;
+
+use crate::cardputer_hal::{
+    input::keyboard_io::{KeyEvent, Scancode},
+    platform::{Clock, Display, FileStorage, KeySource, Network, SdDirEntry, WifiConfig},
+};
+
+/// Keeps the last shown frame.
+#[derive(Debug, Default)]
+pub struct MemoryDisplay {
+    pub frame: Vec<Rgb565>,
+    pub frames_shown: usize,
+    pub is_backlight_on: bool,
+}
+
+impl Display for MemoryDisplay {
+    fn show_frame(&mut self, pixels: &[Rgb565]) -> Result<()> {
+        self.frame = pixels.to_vec();
+        self.frames_shown += 1;
+        Ok(())
+    }
+
+    fn set_backlight(&mut self, is_on: bool) -> Result<()> {
+        self.is_backlight_on = is_on;
+        Ok(())
+    }
+}
+
+/// Plays back queued key events, one per read.
+#[derive(Debug, Default)]
+pub struct ScriptedKeys {
+    events: VecDeque<(KeyEvent, Scancode)>,
+}
+
+impl ScriptedKeys {
+    pub fn push(&mut self, event: KeyEvent, key: Scancode) {
+        self.events.push_back((event, key));
+    }
+
+    /// Presses and releases the key.
+    pub fn tap(&mut self, key: Scancode) {
+        self.push(KeyEvent::Pressed, key);
+        self.push(KeyEvent::Released, key);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl KeySource for ScriptedKeys {
+    fn read_event(&mut self) -> Option<(KeyEvent, Scancode)> {
+        self.events.pop_front()
+    }
+}
+
+/// Files by path, directories exist as long as there are files in them.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    files: BTreeMap<String, String>,
+}
+
+impl MemoryStorage {
+    pub fn with_file(mut self, path: &str, contents: &str) -> Self {
+        self.files.insert(normalize(path), contents.to_string());
+        self
+    }
+
+    pub fn file(&self, path: &str) -> Option<&str> {
+        self.files.get(&normalize(path)).map(|f| f.as_str())
+    }
+
+    fn existing(&self, path: &str) -> Result<&String> {
+        self.files
+            .get(&normalize(path))
+            .ok_or_else(|| anyhow!("no file {}", path))
+    }
+}
+
+fn normalize(path: &str) -> String {
+    path.trim_start_matches('/').to_string()
+}
+
+/// `FileStorage::blank_last_line` over the whole file contents.
+pub(crate) fn blank_last_line_in(file: &mut String, line: &str) -> bool {
+    let end = file.trim_end().len();
+    let Some(start) = end.checked_sub(line.len()) else {
+        return false;
+    };
+    let is_line_start = file
+        .get(..start)
+        .is_some_and(|s| s.is_empty() || s.ends_with('\n'));
+    if !is_line_start || file.get(start..end) != Some(line) {
+        return false;
+    }
+    file.replace_range(start..end, &" ".repeat(line.len()));
+    true
+}
+
+impl FileStorage for MemoryStorage {
+    fn read_file(&mut self, path: &str) -> Result<String> {
+        self.existing(path).cloned()
+    }
+
+    fn read_lines(&mut self, path: &str, on_line: &mut dyn FnMut(&str)) -> Result<()> {
+        let contents = self.existing(path)?;
+        let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+        for line in contents.lines() {
+            on_line(line);
+        }
+        Ok(())
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> Result<()> {
+        self.files.insert(normalize(path), contents.to_string());
+        Ok(())
+    }
+
+    fn write_lines(
+        &mut self,
+        path: &str,
+        lines: &mut dyn Iterator<Item = Result<String>>,
+    ) -> Result<()> {
+        let file = self.files.entry(normalize(path)).or_default();
+        file.clear();
+        for line in lines {
+            file.push_str(&line?);
+        }
+        Ok(())
+    }
+
+    fn append_line(&mut self, path: &str, line: &str) -> Result<()> {
+        let file = self.files.entry(normalize(path)).or_default();
+        if !file.is_empty() && !file.ends_with('\n') {
+            file.push('\n');
+        }
+        file.push_str(line);
+        file.push('\n');
+        Ok(())
+    }
+
+    fn blank_last_line(&mut self, path: &str, line: &str) -> Result<bool> {
+        let file = self
+            .files
+            .get_mut(&normalize(path))
+            .ok_or_else(|| anyhow!("no file {}", path))?;
+        Ok(blank_last_line_in(file, line))
+    }
+
+    fn is_file_exists(&mut self, path: &str) -> Result<bool> {
+        Ok(self.files.contains_key(&normalize(path)))
+    }
+
+    fn list_dir(&mut self, path: &str) -> Result<Vec<SdDirEntry>> {
+        let dir = normalize(path);
+        let prefix = if dir.is_empty() || dir.ends_with('/') {
+            dir
+        } else {
+            format!("{}/", dir)
+        };
+        let mut entries: Vec<SdDirEntry> = Vec::new();
+        for (file_path, contents) in self.files.range(prefix.clone()..) {
+            let Some(rest) = file_path.strip_prefix(&prefix) else {
+                break;
+            };
+            let entry = match rest.split_once('/') {
+                Some((dir_name, _)) => SdDirEntry {
+                    name: dir_name.to_string(),
+                    long_name: None,
+                    size: 0,
+                    is_dir: true,
+                },
+                None => SdDirEntry {
+                    name: rest.to_string(),
+                    long_name: None,
+                    size: contents.len() as u32,
+                    is_dir: false,
+                },
+            };
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Storage shared with the code that set it up, which can look at the files afterwards.
+impl<T: FileStorage> FileStorage for Rc<RefCell<T>> {
+    fn read_file(&mut self, path: &str) -> Result<String> {
+        self.borrow_mut().read_file(path)
+    }
+
+    fn read_lines(&mut self, path: &str, on_line: &mut dyn FnMut(&str)) -> Result<()> {
+        self.borrow_mut().read_lines(path, on_line)
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> Result<()> {
+        self.borrow_mut().write_file(path, contents)
+    }
+
+    fn write_lines(
+        &mut self,
+        path: &str,
+        lines: &mut dyn Iterator<Item = Result<String>>,
+    ) -> Result<()> {
+        self.borrow_mut().write_lines(path, lines)
+    }
+
+    fn append_line(&mut self, path: &str, line: &str) -> Result<()> {
+        self.borrow_mut().append_line(path, line)
+    }
+
+    fn blank_last_line(&mut self, path: &str, line: &str) -> Result<bool> {
+        self.borrow_mut().blank_last_line(path, line)
+    }
+
+    fn is_file_exists(&mut self, path: &str) -> Result<bool> {
+        self.borrow_mut().is_file_exists(path)
+    }
+
+    fn list_dir(&mut self, path: &str) -> Result<Vec<SdDirEntry>> {
+        self.borrow_mut().list_dir(path)
+    }
+}
+
+/// Always tells the same time, in UTC.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock {
+    pub now: DateTime<Utc>,
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now
+    }
+
+    fn local_now(&self) -> NaiveDateTime {
+        self.now.naive_utc()
+    }
+
+    fn set_time_zone(&mut self, _tz: &str) {}
+}
+
+/// Connects to nothing, the clock is taken as already set.
+#[derive(Debug, Default)]
+pub struct OfflineNetwork;
+
+impl Network for OfflineNetwork {
+    fn connect(&mut self, config: WifiConfig) -> Result<()> {
+        log::info!("offline, not connecting to {}", config.ssid);
+        Ok(())
+    }
+
+    fn sync_time(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(storage: &mut MemoryStorage, path: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        storage
+            .read_lines(path, &mut |line| lines.push(line.to_string()))
+            .unwrap();
+        lines
+    }
+
+    #[test]
+    fn append_line_mends_torn_tail() {
+        let mut storage = MemoryStorage::default().with_file("reviews.log", "one\ntw");
+        storage.append_line("reviews.log", "three").unwrap();
+        assert_eq!(storage.file("reviews.log"), Some("one\ntw\nthree\n"));
+
+        storage.append_line("/new.log", "first").unwrap();
+        storage.append_line("new.log", "second").unwrap();
+        assert_eq!(storage.file("new.log"), Some("first\nsecond\n"));
+        assert_eq!(lines(&mut storage, "new.log"), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn blank_last_line_only_takes_the_last_line() {
+        let mut storage = MemoryStorage::default();
+        storage.append_line("log", "one").unwrap();
+        storage.append_line("log", "two").unwrap();
+        assert!(!storage.blank_last_line("log", "one").unwrap());
+        // the end of a longer line is not a line of its own
+        assert!(!storage.blank_last_line("log", "wo").unwrap());
+        assert!(storage.blank_last_line("log", "two").unwrap());
+        assert_eq!(storage.file("log"), Some("one\n   \n"));
+
+        // blanked lines are skipped from the end
+        assert!(storage.blank_last_line("log", "one").unwrap());
+        assert_eq!(storage.file("log"), Some("   \n   \n"));
+        assert!(!storage.blank_last_line("log", "one").unwrap());
+        assert!(storage.blank_last_line("missing", "one").is_err());
+    }
+
+    #[test]
+    fn blank_torn_last_line() {
+        let mut file = "one\ntwo".to_string();
+        assert!(blank_last_line_in(&mut file, "two"));
+        assert_eq!(file, "one\n   ");
+        let mut file = "two".to_string();
+        assert!(blank_last_line_in(&mut file, "two"));
+        assert_eq!(file, "   ");
+    }
+
+    #[test]
+    fn write_lines_replaces_contents() {
+        let mut storage = MemoryStorage::default().with_file("deck0001.dck", "old\n");
+        let mut records = ["a\n", "b\n"].iter().map(|line| Ok(line.to_string()));
+        storage.write_lines("deck0001.dck", &mut records).unwrap();
+        assert_eq!(storage.file("deck0001.dck"), Some("a\nb\n"));
+
+        let mut failing = [Ok("a\n".to_string()), Err(anyhow!("bad record"))].into_iter();
+        assert!(storage.write_lines("deck0002.dck", &mut failing).is_err());
+    }
+
+    #[test]
+    fn list_dir_shows_nested_files_as_dirs() {
+        let mut storage = MemoryStorage::default()
+            .with_file("/Russian/verbs.csv", "a;b")
+            .with_file("Russian/Nouns/food.csv", "c;d")
+            .with_file("decks.jsn", "{}");
+        let names = |entries: Vec<SdDirEntry>| -> Vec<(String, bool)> {
+            entries.into_iter().map(|e| (e.name, e.is_dir)).collect()
+        };
+        assert_eq!(
+            names(storage.list_dir("/").unwrap()),
+            vec![
+                ("Russian".to_string(), true),
+                ("decks.jsn".to_string(), false)
+            ]
+        );
+        assert_eq!(
+            names(storage.list_dir("Russian").unwrap()),
+            vec![
+                ("Nouns".to_string(), true),
+                ("verbs.csv".to_string(), false)
+            ]
+        );
+    }
+}
@@ -1,5 +1,14 @@
 pub mod input;
+pub mod memory;
+pub mod platform;
+#[cfg(feature = "host")]
+pub mod desktop;
+#[cfg(feature = "esp")]
+pub mod esp_clock;
+#[cfg(feature = "esp")]
 pub mod screen;
+#[cfg(feature = "esp")]
 pub mod sd;
+#[cfg(feature = "esp")]
 pub mod wifi;
 pub mod cardputer_hal;
\ No newline at end of file
@@ -0,0 +1,90 @@
+//! What the app needs from the device, so logic and UI run against the Cardputer as well as
+//! against the fakes of `memory` on a PC. The ESP implementations are built with the `esp` feature.
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use embedded_graphics::pixelcolor::Rgb565;
+use serde::{Deserialize, Serialize};
+
+use crate::cardputer_hal::input::keyboard_io::{KeyEvent, Scancode};
+
+pub const SCREEN_WIDTH: u32 = 240;
+pub const SCREEN_HEIGHT: u32 = 135;
+
+/// The screen, frames are drawn in RAM and shown whole.
+pub trait Display {
+    /// Shows `SCREEN_WIDTH` x `SCREEN_HEIGHT` pixels given row by row.
+    fn show_frame(&mut self, pixels: &[Rgb565]) -> Result<()>;
+    fn set_backlight(&mut self, is_on: bool) -> Result<()>;
+}
+
+/// The keyboard.
+pub trait KeySource {
+    /// The key pressed or released since the last call, one key at a time.
+    fn read_event(&mut self) -> Option<(KeyEvent, Scancode)>;
+}
+
+/// A file or a directory found by `FileStorage::list_dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdDirEntry {
+    /// 8.3 name, the one to open the file with.
+    pub name: String,
+    /// Long file name if the file has one.
+    pub long_name: Option<String>,
+    pub size: u32,
+    pub is_dir: bool,
+}
+
+impl SdDirEntry {
+    pub fn display_name(&self) -> &str {
+        self.long_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// The SD card. Paths are relative to its root, directories separated by `/`.
+pub trait FileStorage {
+    fn read_file(&mut self, path: &str) -> Result<String>;
+    /// Reads a text file line by line without loading it whole, line breaks are not passed to `on_line`.
+    fn read_lines(&mut self, path: &str, on_line: &mut dyn FnMut(&str)) -> Result<()>;
+    /// Creates the file or replaces its contents.
+    fn write_file(&mut self, path: &str, contents: &str) -> Result<()>;
+    /// Creates the file or replaces its contents with `lines`, each one ending with its line
+    /// break. Lines are written as they come, so the whole file is never in memory.
+    fn write_lines(
+        &mut self,
+        path: &str,
+        lines: &mut dyn Iterator<Item = Result<String>>,
+    ) -> Result<()>;
+    /// Appends `line` and a line break to the end of the file, creating it if needed.
+    /// A torn last line gets its line break first, so the new line stays readable.
+    fn append_line(&mut self, path: &str, line: &str) -> Result<()>;
+    /// Overwrites the last line that is not blank with spaces if it is `line`, readers skip
+    /// blank lines. Returns false if the file ends with another line.
+    fn blank_last_line(&mut self, path: &str, line: &str) -> Result<bool>;
+    fn is_file_exists(&mut self, path: &str) -> Result<bool>;
+    /// Lists a directory, `""` or `"/"` is the root one.
+    fn list_dir(&mut self, path: &str) -> Result<Vec<SdDirEntry>>;
+}
+
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+    /// Wall clock time in the time zone set last, for the top line.
+    fn local_now(&self) -> NaiveDateTime;
+    /// POSIX TZ string like `MSK-3`, from the settings.
+    fn set_time_zone(&mut self, tz: &str);
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WifiConfig {
+    pub ssid: heapless::String<32>,
+    pub password: heapless::String<64>,
+}
+
+/// Wifi, only used to set the clock.
+pub trait Network {
+    /// Blocks until connected.
+    fn connect(&mut self, config: WifiConfig) -> Result<()>;
+    /// Sets the system clock over NTP, blocks until it is set.
+    fn sync_time(&mut self) -> Result<()>;
+    fn stop(&mut self) -> Result<()>;
+}
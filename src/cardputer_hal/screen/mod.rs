@@ -1,4 +1,3 @@
 pub mod cardputer_screen;
 pub mod display;
-mod framebuffer;
-mod st7789v2;
+mod st7789v2;
\ No newline at end of file
@@ -1,6 +1,6 @@
 use embedded_hal::delay::DelayNs;
 use embedded_sdmmc::{
-    BlockDevice, Directory, Error, Mode, SdCard, TimeSource, VolumeIdx, VolumeManager,
+    BlockDevice, Directory, Error, LfnBuffer, Mode, SdCard, TimeSource, VolumeIdx, VolumeManager,
 };
 use esp_idf_hal::{
     delay::Delay,
@@ -15,6 +15,10 @@ use esp_idf_hal::{
 
 use embedded_sdmmc::SdCardError;
 
+use crate::cardputer_hal::platform::{FileStorage, SdDirEntry};
+
+const BLOCK_SIZE: usize = 512;
+
 pub struct CardputerSd<'a, DELAYER>
 where
     DELAYER: DelayNs + 'a,
@@ -79,9 +83,13 @@ impl CardputerSd<'_, Delay> {
 
     pub fn read_file(&mut self, path: &str) -> Result<String, Error<SdCardError>> {
         let volume0 = self.volume_manager.open_volume(VolumeIdx(0))?;
-        let root_dir = volume0.open_root_dir()?;
+        let mut dir = volume0.open_root_dir()?;
+        let (dirs, file_name) = split_path(path);
+        for dir_name in dirs {
+            dir.change_dir(dir_name)?;
+        }
 
-        let file = root_dir.open_file_in_dir(path, Mode::ReadOnly)?;
+        let file = dir.open_file_in_dir(file_name, Mode::ReadOnly)?;
         let mut contents = Vec::new();
 
         let mut buffer = [0u8; 512];
@@ -98,23 +106,269 @@ impl CardputerSd<'_, Delay> {
         })
     }
 
+    /// Reads a text file line by line without loading it whole, line breaks are not passed to `on_line`.
+    pub fn read_lines(
+        &mut self,
+        path: &str,
+        mut on_line: impl FnMut(&str),
+    ) -> Result<(), Error<SdCardError>> {
+        let volume0 = self.volume_manager.open_volume(VolumeIdx(0))?;
+        let mut dir = volume0.open_root_dir()?;
+        let (dirs, file_name) = split_path(path);
+        for dir_name in dirs {
+            dir.change_dir(dir_name)?;
+        }
+
+        let file = dir.open_file_in_dir(file_name, Mode::ReadOnly)?;
+        let mut line = Vec::new();
+        let mut is_first_line = true;
+        let mut emit_line = |line: &mut Vec<u8>| {
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            {
+                let text = String::from_utf8_lossy(line);
+                // skip UTF-8 BOM written by spreadsheet editors
+                let text = if is_first_line { text.trim_start_matches('\u{feff}') } else { &text };
+                on_line(text);
+            }
+            is_first_line = false;
+            line.clear();
+        };
+
+        let mut buffer = [0u8; 512];
+        loop {
+            let bytes_read = file.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            for byte in &buffer[..bytes_read] {
+                if *byte == b'\n' {
+                    emit_line(&mut line);
+                } else {
+                    line.push(*byte);
+                }
+            }
+        }
+        if !line.is_empty() {
+            emit_line(&mut line);
+        }
+        Ok(())
+    }
+
     pub fn write_file(&mut self, path: &str, contents: &str) -> Result<(), Error<SdCardError>> {
         let volume0 = self.volume_manager.open_volume(VolumeIdx(0))?;
-        let root_dir = volume0.open_root_dir()?;
+        let mut dir = volume0.open_root_dir()?;
+        let (dirs, file_name) = split_path(path);
+        for dir_name in dirs {
+            dir.change_dir(dir_name)?;
+        }
 
-        let file = root_dir.open_file_in_dir(path, Mode::ReadWriteCreate)?;
+        let file = dir.open_file_in_dir(file_name, Mode::ReadWriteCreateOrTruncate)?;
         file.write(contents.as_bytes())?;
         file.flush()?;
         file.close()?;
         Ok(())
     }
 
+    /// Creates the file or replaces its contents with `lines`. They are gathered into blocks
+    /// of the card's size, so only one line and one block are in memory.
+    pub fn write_lines(
+        &mut self,
+        path: &str,
+        lines: &mut dyn Iterator<Item = anyhow::Result<String>>,
+    ) -> anyhow::Result<()> {
+        let volume0 = self.volume_manager.open_volume(VolumeIdx(0)).map_err(sd_error)?;
+        let mut dir = volume0.open_root_dir().map_err(sd_error)?;
+        let (dirs, file_name) = split_path(path);
+        for dir_name in dirs {
+            dir.change_dir(dir_name).map_err(sd_error)?;
+        }
+
+        let file = dir
+            .open_file_in_dir(file_name, Mode::ReadWriteCreateOrTruncate)
+            .map_err(sd_error)?;
+        let mut block = Vec::with_capacity(BLOCK_SIZE);
+        for line in lines {
+            block.extend_from_slice(line?.as_bytes());
+            if block.len() >= BLOCK_SIZE {
+                file.write(&block).map_err(sd_error)?;
+                block.clear();
+            }
+        }
+        file.write(&block).map_err(sd_error)?;
+        file.flush().map_err(sd_error)?;
+        file.close().map_err(sd_error)?;
+        Ok(())
+    }
+
+    /// Appends `line` and a line break to the end of the file, creating it if needed.
+    /// If the file ends with a torn line (power loss during the previous write), the line break
+    /// is restored first, so the new line stays readable.
+    pub fn append_line(&mut self, path: &str, line: &str) -> Result<(), Error<SdCardError>> {
+        let volume0 = self.volume_manager.open_volume(VolumeIdx(0))?;
+        let mut dir = volume0.open_root_dir()?;
+        let (dirs, file_name) = split_path(path);
+        for dir_name in dirs {
+            dir.change_dir(dir_name)?;
+        }
+
+        let file = dir.open_file_in_dir(file_name, Mode::ReadWriteCreateOrAppend)?;
+        if file.length() > 0 {
+            let mut last_byte = [0u8; 1];
+            file.seek_from_end(1)?;
+            file.read(&mut last_byte)?;
+            if last_byte[0] != b'\n' {
+                file.write(b"\n")?;
+            }
+        }
+        file.write(line.as_bytes())?;
+        file.write(b"\n")?;
+        file.flush()?;
+        file.close()?;
+        Ok(())
+    }
+
+    /// Overwrites the last line that is not blank with spaces if it is `line`, readers skip
+    /// blank lines. The file keeps its size, FAT can't be shortened in place.
+    /// Returns false if the file ends with another line.
+    pub fn blank_last_line(&mut self, path: &str, line: &str) -> Result<bool, Error<SdCardError>> {
+        let volume0 = self.volume_manager.open_volume(VolumeIdx(0))?;
+        let mut dir = volume0.open_root_dir()?;
+        let (dirs, file_name) = split_path(path);
+        for dir_name in dirs {
+            dir.change_dir(dir_name)?;
+        }
+
+        let file = dir.open_file_in_dir(file_name, Mode::ReadWriteAppend)?;
+        // lines blanked before are skipped from the end
+        let mut end = file.length();
+        let mut buffer = [0u8; 512];
+        'search: while end > 0 {
+            let start = end.saturating_sub(buffer.len() as u32);
+            let chunk = &mut buffer[..(end - start) as usize];
+            file.seek_from_start(start)?;
+            file.read(chunk)?;
+            for (i, byte) in chunk.iter().enumerate().rev() {
+                if !byte.is_ascii_whitespace() {
+                    end = start + i as u32 + 1;
+                    break 'search;
+                }
+            }
+            end = start;
+        }
+        let Some(line_start) = end.checked_sub(line.len() as u32) else {
+            return Ok(false);
+        };
+
+        file.seek_from_start(line_start.saturating_sub(1))?;
+        let mut contents = vec![0u8; (end - line_start.saturating_sub(1)) as usize];
+        file.read(&mut contents)?;
+        let (before, found) = contents.split_at(contents.len() - line.len());
+        let is_line_start = line_start == 0 || before == b"\n";
+        if !is_line_start || found != line.as_bytes() {
+            return Ok(false);
+        }
+
+        file.seek_from_start(line_start)?;
+        file.write(&vec![b' '; line.len()])?;
+        file.flush()?;
+        file.close()?;
+        Ok(true)
+    }
+
     pub fn is_file_exists(&mut self, path: &str) -> Result<bool, Error<SdCardError>> {
         let volume0 = self.volume_manager.open_volume(VolumeIdx(0))?;
-        let root_dir = volume0.open_root_dir()?;
-        let file = root_dir.open_file_in_dir(path, Mode::ReadOnly);
+        let mut dir = volume0.open_root_dir()?;
+        let (dirs, file_name) = split_path(path);
+        for dir_name in dirs {
+            if dir.change_dir(dir_name).is_err() {
+                return Ok(false);
+            }
+        }
+        let file = dir.open_file_in_dir(file_name, Mode::ReadOnly);
         Ok(file.is_ok())
     }
+
+    /// Lists a directory, `""` or `"/"` is the root one.
+    pub fn list_dir(&mut self, path: &str) -> Result<Vec<SdDirEntry>, Error<SdCardError>> {
+        let volume0 = self.volume_manager.open_volume(VolumeIdx(0))?;
+        let mut dir = volume0.open_root_dir()?;
+        for dir_name in path.split('/').filter(|d| !d.is_empty()) {
+            dir.change_dir(dir_name)?;
+        }
+
+        let mut entries = Vec::new();
+        let mut lfn_storage = [0u8; 128];
+        let mut lfn_buffer = LfnBuffer::new(&mut lfn_storage);
+        dir.iterate_dir_lfn(&mut lfn_buffer, |entry, long_name| {
+            if entry.name == embedded_sdmmc::ShortFileName::parent_dir()
+                || entry.name == embedded_sdmmc::ShortFileName::this_dir()
+            {
+                return;
+            }
+            entries.push(SdDirEntry {
+                name: format!("{}", entry.name),
+                long_name: long_name.map(|n| n.to_string()),
+                size: entry.size,
+                is_dir: entry.attributes.is_directory(),
+            });
+        })?;
+        Ok(entries)
+    }
+}
+
+impl FileStorage for CardputerSd<'_, Delay> {
+    fn read_file(&mut self, path: &str) -> anyhow::Result<String> {
+        CardputerSd::read_file(self, path).map_err(sd_error)
+    }
+
+    fn read_lines(&mut self, path: &str, on_line: &mut dyn FnMut(&str)) -> anyhow::Result<()> {
+        CardputerSd::read_lines(self, path, on_line).map_err(sd_error)
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> anyhow::Result<()> {
+        CardputerSd::write_file(self, path, contents).map_err(sd_error)
+    }
+
+    fn write_lines(
+        &mut self,
+        path: &str,
+        lines: &mut dyn Iterator<Item = anyhow::Result<String>>,
+    ) -> anyhow::Result<()> {
+        CardputerSd::write_lines(self, path, lines)
+    }
+
+    fn append_line(&mut self, path: &str, line: &str) -> anyhow::Result<()> {
+        CardputerSd::append_line(self, path, line).map_err(sd_error)
+    }
+
+    fn blank_last_line(&mut self, path: &str, line: &str) -> anyhow::Result<bool> {
+        CardputerSd::blank_last_line(self, path, line).map_err(sd_error)
+    }
+
+    fn is_file_exists(&mut self, path: &str) -> anyhow::Result<bool> {
+        CardputerSd::is_file_exists(self, path).map_err(sd_error)
+    }
+
+    fn list_dir(&mut self, path: &str) -> anyhow::Result<Vec<SdDirEntry>> {
+        CardputerSd::list_dir(self, path).map_err(sd_error)
+    }
+}
+
+/// embedded-sdmmc errors are only `Debug`.
+fn sd_error(e: Error<SdCardError>) -> anyhow::Error {
+    anyhow::anyhow!("{:?}", e)
+}
+
+/// Splits `dir/sub/file.txt` into the directories to walk and the file name.
+fn split_path(path: &str) -> (impl Iterator<Item = &str>, &str) {
+    let path = path.trim_start_matches('/');
+    let (dirs, file_name) = match path.rfind('/') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("", path),
+    };
+    (dirs.split('/').filter(|d| !d.is_empty()), file_name)
 }
 
 fn list_dir<
@@ -1,15 +1,10 @@
 use anyhow::Result;
+use esp_idf_svc::sntp::{EspSntp, SyncStatus};
 use esp_idf_svc::wifi::{ClientConfiguration, Configuration, EspWifi};
 use esp_idf_sys::usleep;
-use heapless::String;
-use serde::{Deserialize, Serialize};
 
+use crate::cardputer_hal::platform::{Network, WifiConfig};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WifiConfig {
-    pub ssid: String<32>,
-    pub password: String<64>,
-}
 pub struct CardWorderWifi<'a> {
     driver: EspWifi<'a>
 }
@@ -51,3 +46,19 @@ impl<'a> CardWorderWifi<'a> {
         Ok(())
     }
 }
+
+impl Network for CardWorderWifi<'_> {
+    fn connect(&mut self, config: WifiConfig) -> Result<()> {
+        CardWorderWifi::connect(self, config)
+    }
+
+    fn sync_time(&mut self) -> Result<()> {
+        let ntp = EspSntp::new_default()?;
+        while ntp.get_sync_status() != SyncStatus::Completed {}
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        CardWorderWifi::stop(self)
+    }
+}
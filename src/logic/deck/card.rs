@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use rs_fsrs::State;
+use serde::{Deserialize, Serialize};
+
+use crate::logic::{
+    deck::{
+        card_template::TemplateId, cloze::ClozeNumber, filtered_deck::FilteredDeckId, note::NoteId,
+    },
+    review::scheduler::Scheduled,
+    study_day::DayNumber,
+};
+
+pub type CardId = u64;
+
+/// A reviewable side of a note together with its FSRS scheduling state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Card {
+    pub id: CardId,
+    pub note_id: NoteId,
+    /// Which side of the note is asked, see `CardTemplate`.
+    pub template: TemplateId,
+    /// Deletion asked by a card of a cloze template.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloze: Option<ClozeNumber>,
+    pub fsrs: rs_fsrs::Card,
+    /// (Re)learning step the card is on, see `Scheduler`.
+    #[serde(default)]
+    pub step: u16,
+    /// Suspended cards are kept out of every queue until unsuspended.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub suspended: bool,
+    /// Study day the card was buried on, it is back in the queue the day after.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buried_on: Option<DayNumber>,
+    /// Filtered deck the card is lent to, it is studied there instead of in its home deck.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filtered: Option<FilteredDeckId>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl Card {
+    pub fn new(id: CardId, note_id: NoteId, template: TemplateId, now: DateTime<Utc>) -> Self {
+        // rs_fsrs::Card::new() reads the clock itself, build it from `now` to stay deterministic
+        let fsrs = rs_fsrs::Card {
+            due: now,
+            last_review: now,
+            ..Default::default()
+        };
+        Self {
+            id,
+            note_id,
+            template,
+            cloze: None,
+            fsrs,
+            step: 0,
+            suspended: false,
+            buried_on: None,
+            filtered: None,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.fsrs.state
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.fsrs.state == State::New
+    }
+
+    pub fn is_buried(&self, today: DayNumber) -> bool {
+        self.buried_on == Some(today)
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.fsrs.due <= now
+    }
+
+    /// Forgets the scheduling, the card starts over as a new one.
+    pub fn reset(&mut self, now: DateTime<Utc>) {
+        let fresh = Card::new(self.id, self.note_id, self.template, now);
+        self.fsrs = fresh.fsrs;
+        self.step = fresh.step;
+    }
+
+    /// Stores the outcome of a review chosen from `Scheduler::preview`.
+    pub fn apply(&mut self, scheduled: &Scheduled) {
+        self.fsrs = scheduled.info.card.clone();
+        self.step = scheduled.step;
+    }
+}
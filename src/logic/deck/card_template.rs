@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::logic::deck::{
+    cloze::{self, ClozeNumber, Span},
+    note::Note,
+};
+
+pub type TemplateId = u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateKind {
+    /// One card asking the question field, for notes without cloze deletions.
+    #[default]
+    Basic,
+    /// One card per deletion number in the question field, the answer field is shown as extra.
+    Cloze,
+}
+
+/// Which note fields one card of the note asks and answers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardTemplate {
+    /// Stored in `Card::template`, never reused for another template of the deck.
+    pub id: TemplateId,
+    pub name: String,
+    pub question_field: usize,
+    pub answer_field: usize,
+    #[serde(default)]
+    pub kind: TemplateKind,
+    /// Disabling a template removes its cards, enabling it generates them again.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Both sides of a card ready to be drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardSides {
+    pub question: Vec<Span>,
+    pub answer: Vec<Span>,
+    /// What a typed answer is checked against.
+    pub expected: String,
+    /// Drawn under the answer, empty for basic cards.
+    pub extra: String,
+}
+
+impl CardTemplate {
+    pub const FORWARD_ID: TemplateId = 0;
+    pub const REVERSE_ID: TemplateId = 1;
+    pub const CLOZE_ID: TemplateId = 2;
+
+    pub fn forward() -> Self {
+        Self {
+            id: Self::FORWARD_ID,
+            name: "front→back".to_string(),
+            question_field: 0,
+            answer_field: 1,
+            kind: TemplateKind::Basic,
+            enabled: true,
+        }
+    }
+
+    pub fn reverse() -> Self {
+        Self {
+            id: Self::REVERSE_ID,
+            name: "back→front".to_string(),
+            question_field: 1,
+            answer_field: 0,
+            kind: TemplateKind::Basic,
+            enabled: true,
+        }
+    }
+
+    /// Sentence with deletions in the first field, e.g. a translation in the second.
+    pub fn cloze() -> Self {
+        Self {
+            id: Self::CLOZE_ID,
+            name: "cloze".to_string(),
+            question_field: 0,
+            answer_field: 1,
+            kind: TemplateKind::Cloze,
+            enabled: true,
+        }
+    }
+
+    pub fn question<'a>(&self, note: &'a Note) -> &'a str {
+        note.field(self.question_field)
+    }
+
+    pub fn answer<'a>(&self, note: &'a Note) -> &'a str {
+        note.field(self.answer_field)
+    }
+
+    /// Cards the note gets from this template, by cloze number (`None` for a basic card).
+    /// A card with nothing to ask is not generated.
+    pub fn cards_for(&self, note: &Note) -> Vec<Option<ClozeNumber>> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        match self.kind {
+            TemplateKind::Basic if note.is_cloze() || self.question(note).trim().is_empty() => {
+                Vec::new()
+            }
+            TemplateKind::Basic => vec![None],
+            TemplateKind::Cloze => cloze::numbers(self.question(note))
+                .into_iter()
+                .map(Some)
+                .collect(),
+        }
+    }
+
+    /// The sides of the card of this template asking deletion `cloze`, or the plain fields of a basic card.
+    pub fn sides(&self, note: &Note, cloze: Option<ClozeNumber>) -> CardSides {
+        match cloze {
+            Some(number) => {
+                let text = self.question(note);
+                CardSides {
+                    question: cloze::question(text, number),
+                    answer: cloze::answer(text, number),
+                    expected: cloze::deleted_text(text, number),
+                    extra: self.answer(note).to_string(),
+                }
+            }
+            None => CardSides {
+                question: vec![Span::plain(self.question(note))],
+                answer: vec![Span::plain(self.answer(note))],
+                expected: self.answer(note).to_string(),
+                extra: String::new(),
+            },
+        }
+    }
+}
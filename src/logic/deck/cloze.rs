@@ -0,0 +1,229 @@
+//! Cloze deletions in a note field: `{{c1::word}}` or `{{c1::word::hint}}`.
+//!
+//! Every deletion number gives its own card. The question hides the deletions of the card's
+//! number behind `[...]` (or `[hint]`), the other deletions are shown as plain text.
+
+pub type ClozeNumber = u8;
+
+const OPEN: &str = "{{c";
+const SEPARATOR: &str = "::";
+const CLOSE: &str = "}}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanStyle {
+    Plain,
+    /// Placeholder of a deletion on the question side.
+    Hidden,
+    /// Deleted text on the answer side.
+    Revealed,
+}
+
+/// A run of text drawn in one style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+impl Span {
+    pub fn plain(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            style: SpanStyle::Plain,
+        }
+    }
+}
+
+/// The text of spans without styles.
+pub fn plain_text(spans: &[Span]) -> String {
+    spans.iter().map(|s| s.text.as_str()).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part<'a> {
+    Text(&'a str),
+    Deletion {
+        number: ClozeNumber,
+        text: &'a str,
+        hint: Option<&'a str>,
+    },
+}
+
+/// Deletion numbers used in the text, sorted and without repeats.
+pub fn numbers(text: &str) -> Vec<ClozeNumber> {
+    let mut numbers: Vec<ClozeNumber> = parse(text)
+        .into_iter()
+        .filter_map(|part| match part {
+            Part::Deletion { number, .. } => Some(number),
+            Part::Text(_) => None,
+        })
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    numbers
+}
+
+pub fn has_deletions(text: &str) -> bool {
+    !numbers(text).is_empty()
+}
+
+/// The text with the deletions of `number` hidden.
+pub fn question(text: &str, number: ClozeNumber) -> Vec<Span> {
+    render(text, number, |_, hint| Span {
+        text: format!("[{}]", hint.unwrap_or("...")),
+        style: SpanStyle::Hidden,
+    })
+}
+
+/// The text with the deletions of `number` revealed.
+pub fn answer(text: &str, number: ClozeNumber) -> Vec<Span> {
+    render(text, number, |deleted, _| Span {
+        text: deleted.to_string(),
+        style: SpanStyle::Revealed,
+    })
+}
+
+/// What the deletions of `number` hide, several deletions are joined with ", ".
+pub fn deleted_text(text: &str, number: ClozeNumber) -> String {
+    parse(text)
+        .into_iter()
+        .filter_map(|part| match part {
+            Part::Deletion {
+                number: n, text, ..
+            } if n == number => Some(text),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn render(
+    text: &str,
+    number: ClozeNumber,
+    deletion: impl Fn(&str, Option<&str>) -> Span,
+) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for part in parse(text) {
+        let span = match part {
+            Part::Deletion {
+                number: n,
+                text,
+                hint,
+            } if n == number => deletion(text, hint),
+            Part::Deletion { text, .. } | Part::Text(text) => Span::plain(text),
+        };
+        match spans.last_mut() {
+            Some(last) if last.style == span.style => last.text.push_str(&span.text),
+            _ => spans.push(span),
+        }
+    }
+    spans
+}
+
+/// Splits the text into plain runs and deletions, malformed markup stays plain text.
+fn parse(text: &str) -> Vec<Part<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    let mut plain_start = 0;
+    let mut offset = 0;
+    while let Some(found) = rest.find(OPEN) {
+        let start = offset + found;
+        match parse_deletion(&text[start..]) {
+            Some((deletion, len)) => {
+                if plain_start < start {
+                    parts.push(Part::Text(&text[plain_start..start]));
+                }
+                parts.push(deletion);
+                offset = start + len;
+                plain_start = offset;
+            }
+            None => offset = start + OPEN.len(),
+        }
+        rest = &text[offset..];
+    }
+    if plain_start < text.len() {
+        parts.push(Part::Text(&text[plain_start..]));
+    }
+    parts
+}
+
+/// Reads a deletion at the start of `text`, returns it with the length of its markup.
+fn parse_deletion(text: &str) -> Option<(Part<'_>, usize)> {
+    let after_open = text.strip_prefix(OPEN)?;
+    let digits = after_open
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(after_open.len());
+    let number: ClozeNumber = after_open[..digits].parse().ok().filter(|n| *n > 0)?;
+    let body_and_rest = after_open[digits..].strip_prefix(SEPARATOR)?;
+    let body_len = body_and_rest.find(CLOSE)?;
+    let body = &body_and_rest[..body_len];
+    let (deleted, hint) = match body.split_once(SEPARATOR) {
+        Some((deleted, hint)) => (deleted, Some(hint)),
+        None => (body, None),
+    };
+    let len = OPEN.len() + digits + SEPARATOR.len() + body_len + CLOSE.len();
+    Some((
+        Part::Deletion {
+            number,
+            text: deleted,
+            hint,
+        },
+        len,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_markup_stays_plain() {
+        assert_eq!(parse("{{c0::x}}"), vec![Part::Text("{{c0::x}}")]);
+        assert_eq!(parse("{{c::x}}"), vec![Part::Text("{{c::x}}")]);
+        assert_eq!(parse("a {{c1::x"), vec![Part::Text("a {{c1::x")]);
+        assert_eq!(parse("{{c1:x}}"), vec![Part::Text("{{c1:x}}")]);
+        assert_eq!(parse("{{c300::x}}"), vec![Part::Text("{{c300::x}}")]);
+        assert!(!has_deletions("{{c0::x}} and {{c1::y"));
+    }
+
+    #[test]
+    fn malformed_markup_before_a_deletion() {
+        assert_eq!(
+            parse("{{c0::x}} {{c2::y}}!"),
+            vec![
+                Part::Text("{{c0::x}} "),
+                Part::Deletion {
+                    number: 2,
+                    text: "y",
+                    hint: None
+                },
+                Part::Text("!"),
+            ]
+        );
+    }
+
+    #[test]
+    fn hint_keeps_its_separators() {
+        assert_eq!(
+            parse("{{c1::Москва::city::capital}}"),
+            vec![Part::Deletion {
+                number: 1,
+                text: "Москва",
+                hint: Some("city::capital")
+            }]
+        );
+        let question = question("{{c1::Москва::a::b}} is big", 1);
+        assert_eq!(plain_text(&question), "[a::b] is big");
+        assert_eq!(question[0].style, SpanStyle::Hidden);
+    }
+
+    #[test]
+    fn numbers_are_sorted_without_repeats() {
+        let text = "{{c2::b}} {{c1::a}} {{c2::c}} {{c10::d}}";
+        assert_eq!(numbers(text), vec![1, 2, 10]);
+        assert_eq!(deleted_text(text, 2), "b, c");
+        assert_eq!(plain_text(&answer(text, 2)), "b a c d");
+        assert_eq!(plain_text(&question(text, 2)), "[...] a [...] d");
+        assert!(numbers("no deletions").is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+//! Deck file format: one JSON record per line.
+//!
+//! The first line describes the deck, then every note is followed by its cards:
+//! ```text
+//! {"deck":{"id":1,"name":"Russian","options":{...}}}
+//! {"note":{"id":1,"fields":["кошка","cat"],...}}
+//! {"card":{"id":1,"note_id":1,"template":0,"fsrs":{...}}}
+//! ```
+//! Keeping records on separate lines lets the file be read line by line from SD.
+
+use std::iter;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::logic::deck::{
+    card::Card,
+    deck_options::DeckOptions,
+    model::{DayCounts, Deck, DeckInfo},
+    note::Note,
+};
+
+/// First line of a deck file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeckHeader {
+    #[serde(flatten)]
+    pub info: DeckInfo,
+    #[serde(default)]
+    pub options: DeckOptions,
+    #[serde(default)]
+    pub today: DayCounts,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeckRecord {
+    Deck(DeckHeader),
+    Note(Note),
+    Card(Card),
+}
+
+impl DeckRecord {
+    pub fn parse_line(line: &str) -> Result<Self> {
+        Ok(serde_json::from_str(line)?)
+    }
+
+    pub fn to_line(&self) -> Result<String> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        Ok(line)
+    }
+}
+
+pub fn write_deck(deck: &Deck) -> Result<String> {
+    deck_lines(deck).collect()
+}
+
+/// Lines of the deck file made one record at a time, to be written without building the file.
+pub fn deck_lines(deck: &Deck) -> impl Iterator<Item = Result<String>> + '_ {
+    let header = DeckHeader {
+        info: deck.info.clone(),
+        options: deck.options.clone(),
+        today: deck.today,
+    };
+    let mut by_note = deck.cards_by_note();
+    iter::once(DeckRecord::Deck(header))
+        .chain(deck.notes().iter().flat_map(move |note| {
+            let cards = by_note.remove(&note.id).unwrap_or_default();
+            iter::once(DeckRecord::Note(note.clone()))
+                .chain(cards.into_iter().map(|card| DeckRecord::Card(card.clone())))
+        }))
+        .map(|record| record.to_line())
+}
+
+pub fn read_deck(contents: &str) -> Result<Deck> {
+    let mut loader = DeckLoader::default();
+    for line in contents.lines() {
+        loader.push_line(line)?;
+    }
+    loader.finish()
+}
+
+/// Builds a deck from lines fed one by one, so the whole file never has to be in memory.
+/// Damaged note and card lines (e.g. a tail torn by a power loss) are counted and skipped.
+#[derive(Default)]
+pub struct DeckLoader {
+    deck: Option<Deck>,
+    line_number: usize,
+    skipped_lines: usize,
+}
+
+impl DeckLoader {
+    pub fn push_line(&mut self, line: &str) -> Result<()> {
+        self.line_number += 1;
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+
+        let record = match DeckRecord::parse_line(line) {
+            Ok(record) => record,
+            Err(e) if self.deck.is_some() => {
+                log::warn!("skip deck line {}: {:?}", self.line_number, e);
+                self.skipped_lines += 1;
+                return Ok(());
+            }
+            Err(e) => return Err(anyhow!("line {}: {}", self.line_number, e)),
+        };
+
+        match (&mut self.deck, record) {
+            (None, DeckRecord::Deck(header)) => {
+                let mut deck = Deck::new(header.info);
+                deck.options = header.options;
+                deck.today = header.today;
+                self.deck = Some(deck);
+            }
+            (None, _) => return Err(anyhow!("deck file must start with a deck record")),
+            (Some(_), DeckRecord::Deck(_)) => {
+                return Err(anyhow!("line {}: second deck record", self.line_number))
+            }
+            (Some(deck), DeckRecord::Note(note)) => deck.insert_note(note),
+            (Some(deck), DeckRecord::Card(card)) => deck.insert_card(card),
+        }
+        Ok(())
+    }
+
+    pub fn skipped_lines(&self) -> usize {
+        self.skipped_lines
+    }
+
+    pub fn finish(self) -> Result<Deck> {
+        self.deck.ok_or_else(|| anyhow!("deck file is empty"))
+    }
+}
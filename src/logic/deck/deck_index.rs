@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::logic::deck::model::{DeckId, DeckInfo};
+
+/// List of decks stored on the SD card, each deck itself lives in `DeckInfo::file_name()`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeckIndex {
+    pub decks: Vec<DeckInfo>,
+    /// CSV/TSV files the import screen already went through.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imported_files: Vec<ImportedFile>,
+}
+
+/// A file is imported again only once its size changes, e.g. after rows were added to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportedFile {
+    pub path: String,
+    pub size: u32,
+}
+
+impl DeckIndex {
+    pub const FILE_NAME: &'static str = "decks.jsn";
+
+    pub fn find(&self, id: DeckId) -> Option<&DeckInfo> {
+        self.decks.iter().find(|d| d.id == id)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&DeckInfo> {
+        self.decks.iter().find(|d| d.name == name)
+    }
+
+    pub fn next_id(&self) -> DeckId {
+        self.decks.iter().map(|d| d.id).max().unwrap_or(0) + 1
+    }
+
+    /// Registers a new deck with a free id and returns its info.
+    pub fn add_deck(&mut self, name: &str) -> DeckInfo {
+        let info = DeckInfo {
+            id: self.next_id(),
+            name: name.to_string(),
+        };
+        self.decks.push(info.clone());
+        info
+    }
+
+    pub fn is_imported(&self, path: &str, size: u32) -> bool {
+        self.imported_files
+            .iter()
+            .any(|f| f.path == path && f.size == size)
+    }
+
+    pub fn mark_imported(&mut self, path: &str, size: u32) {
+        self.imported_files.retain(|f| f.path != path);
+        self.imported_files.push(ImportedFile {
+            path: path.to_string(),
+            size,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_is_imported_again_once_its_size_changes() {
+        let mut index = DeckIndex::default();
+        assert!(!index.is_imported("/words/verbs.csv", 120));
+        index.mark_imported("/words/verbs.csv", 120);
+        index.mark_imported("/words/nouns.csv", 80);
+        assert!(index.is_imported("/words/verbs.csv", 120));
+        assert!(!index.is_imported("/words/verbs.csv", 150));
+        assert!(!index.is_imported("/verbs.csv", 120));
+
+        index.mark_imported("/words/verbs.csv", 150);
+        assert!(index.is_imported("/words/verbs.csv", 150));
+        assert!(!index.is_imported("/words/verbs.csv", 120));
+        assert_eq!(index.imported_files.len(), 2);
+
+        let json = serde_json::to_string(&index).unwrap();
+        assert_eq!(serde_json::from_str::<DeckIndex>(&json).unwrap(), index);
+        // older indexes have no list
+        let old: DeckIndex = serde_json::from_str(r#"{"decks":[{"id":1,"name":"A"}]}"#).unwrap();
+        assert!(old.imported_files.is_empty());
+        assert!(!serde_json::to_string(&old)
+            .unwrap()
+            .contains("imported_files"));
+    }
+}
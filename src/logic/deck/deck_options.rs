@@ -0,0 +1,101 @@
+use rs_fsrs::State;
+use serde::{Deserialize, Serialize};
+
+use crate::logic::{
+    deck::{
+        card_template::CardTemplate,
+        deck_preset::{DeckPreset, PresetId},
+    },
+    review::answer_check::AnswerCheckOptions,
+};
+
+/// Order new cards are introduced in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NewCardOrder {
+    /// The order notes were added or imported in.
+    #[default]
+    FileOrder,
+    /// Shuffled once per study day.
+    Random,
+    /// Most frequent words first, notes without a rank go last.
+    FrequencyRank,
+}
+
+/// What happens to a card that became a leech, it is tagged in any case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeechAction {
+    #[default]
+    Suspend,
+    TagOnly,
+}
+
+/// Per deck settings, stored in the header line of the deck file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeckOptions {
+    pub answer_check: AnswerCheckOptions,
+    /// Every note gets the cards of the enabled templates, see `CardTemplate::cards_for`.
+    pub templates: Vec<CardTemplate>,
+    /// Scheduling settings and FSRS weights, see `DeckPresets`.
+    pub preset_id: PresetId,
+    pub new_per_day: usize,
+    /// Learning cards are not limited, only cards in the review state.
+    pub reviews_per_day: usize,
+    pub new_order: NewCardOrder,
+    /// Lapses that make a card a leech, 0 turns detection off.
+    pub leech_threshold: u32,
+    pub leech_action: LeechAction,
+    /// Which siblings (other cards of the same note) are buried until the next day
+    /// once one card of the note is studied.
+    pub bury_new: bool,
+    pub bury_reviews: bool,
+    pub bury_learning: bool,
+}
+
+impl Default for DeckOptions {
+    fn default() -> Self {
+        Self {
+            answer_check: AnswerCheckOptions::default(),
+            templates: vec![
+                CardTemplate::forward(),
+                CardTemplate {
+                    enabled: false,
+                    ..CardTemplate::reverse()
+                },
+                CardTemplate::cloze(),
+            ],
+            preset_id: DeckPreset::DEFAULT_ID,
+            new_per_day: 20,
+            reviews_per_day: 200,
+            new_order: NewCardOrder::default(),
+            leech_threshold: 8,
+            leech_action: LeechAction::default(),
+            bury_new: true,
+            bury_reviews: true,
+            bury_learning: false,
+        }
+    }
+}
+
+impl DeckOptions {
+    /// Whether a sibling in this state is buried.
+    pub fn is_buried_sibling(&self, state: State) -> bool {
+        match state {
+            State::New => self.bury_new,
+            State::Review => self.bury_reviews,
+            State::Learning | State::Relearning => self.bury_learning,
+        }
+    }
+
+    /// Like Anki: a card is a leech at the threshold and again every half of it after,
+    /// so an unsuspended leech is reported again if it keeps failing.
+    pub fn is_leech(&self, lapses: u32) -> bool {
+        let threshold = self.leech_threshold;
+        if threshold == 0 || lapses < threshold {
+            return false;
+        }
+        (lapses - threshold) % (threshold / 2).max(1) == 0
+    }
+}
@@ -0,0 +1,93 @@
+use rs_fsrs::Parameters;
+use serde::{Deserialize, Serialize};
+
+use crate::logic::review::scheduler::{Scheduler, Steps};
+
+pub type PresetId = u32;
+
+/// Scheduling settings shared by decks, the FSRS weights are fitted per preset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeckPreset {
+    pub id: PresetId,
+    pub name: String,
+    pub weights: [f64; 19],
+    pub request_retention: f64,
+    pub maximum_interval: i32,
+    #[serde(default = "default_learning_steps")]
+    pub learning_steps: Steps,
+    /// Steps of a card forgotten in review, empty sends it straight back to review.
+    #[serde(default = "default_relearning_steps")]
+    pub relearning_steps: Steps,
+}
+
+fn default_learning_steps() -> Steps {
+    Steps::from_minutes(&[1, 10])
+}
+
+fn default_relearning_steps() -> Steps {
+    Steps::from_minutes(&[10])
+}
+
+impl DeckPreset {
+    pub const DEFAULT_ID: PresetId = 1;
+
+    pub fn new(id: PresetId, name: &str) -> Self {
+        let parameters = Parameters::default();
+        Self {
+            id,
+            name: name.to_string(),
+            weights: parameters.w,
+            request_retention: parameters.request_retention,
+            maximum_interval: parameters.maximum_interval,
+            learning_steps: default_learning_steps(),
+            relearning_steps: default_relearning_steps(),
+        }
+    }
+
+    pub fn scheduler(&self) -> Scheduler {
+        let parameters = Parameters {
+            w: self.weights,
+            request_retention: self.request_retention,
+            maximum_interval: self.maximum_interval,
+            ..Default::default()
+        };
+        Scheduler::new(
+            parameters,
+            self.learning_steps.clone(),
+            self.relearning_steps.clone(),
+        )
+    }
+}
+
+/// All presets, stored in `presets.jsn` on the SD card.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeckPresets {
+    pub presets: Vec<DeckPreset>,
+}
+
+impl Default for DeckPresets {
+    fn default() -> Self {
+        Self {
+            presets: vec![DeckPreset::new(DeckPreset::DEFAULT_ID, "Default")],
+        }
+    }
+}
+
+impl DeckPresets {
+    pub const FILE_NAME: &'static str = "presets.jsn";
+
+    pub fn find(&self, id: PresetId) -> Option<&DeckPreset> {
+        self.presets.iter().find(|p| p.id == id)
+    }
+
+    pub fn find_mut(&mut self, id: PresetId) -> Option<&mut DeckPreset> {
+        self.presets.iter_mut().find(|p| p.id == id)
+    }
+
+    /// The preset of a deck, a deck pointing to a removed preset falls back to FSRS defaults.
+    pub fn preset_or_default(&self, id: PresetId) -> DeckPreset {
+        self.find(id)
+            .cloned()
+            .unwrap_or_else(|| DeckPreset::new(id, "Default"))
+    }
+}
@@ -0,0 +1,245 @@
+//! Decks nest by name: `Russian::Verbs::Motion` is a child of `Russian::Verbs`.
+//!
+//! The deck files stay flat on the SD card, only the names in the index carry the hierarchy.
+//! A parent doesn't need a deck of its own, `Russian` exists as long as a deck is nested in it.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::logic::{
+    deck::model::{DeckId, DeckInfo},
+    review::session::QueueCounts,
+};
+
+pub const SEPARATOR: &str = "::";
+
+/// Last part of the name, the one shown in the tree.
+pub fn leaf_name(name: &str) -> &str {
+    name.rsplit_once(SEPARATOR).map_or(name, |(_, leaf)| leaf)
+}
+
+/// Whether the deck is `root` itself or nested in it.
+pub fn is_in_subtree(name: &str, root: &str) -> bool {
+    name.strip_prefix(root)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(SEPARATOR))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeckTreeRow {
+    /// Full name of the deck.
+    pub name: String,
+    pub depth: usize,
+    /// `None` for a parent that only exists through the names of its children.
+    pub deck: Option<DeckInfo>,
+    /// Cards to study today in the deck and everything nested in it.
+    pub counts: QueueCounts,
+    pub has_children: bool,
+}
+
+/// Every deck and parent in name order, a parent right before its children.
+#[derive(Debug, Clone, Default)]
+pub struct DeckTree {
+    rows: Vec<DeckTreeRow>,
+    collapsed: HashSet<String>,
+}
+
+impl DeckTree {
+    /// Decks missing from `counts` count as having nothing to study.
+    pub fn new(decks: &[DeckInfo], counts: &HashMap<DeckId, QueueCounts>) -> Self {
+        let mut nodes: BTreeMap<Vec<&str>, Option<&DeckInfo>> = BTreeMap::new();
+        for deck in decks {
+            let path: Vec<&str> = deck.name.split(SEPARATOR).collect();
+            for depth in 1..path.len() {
+                nodes.entry(path[..depth].to_vec()).or_insert(None);
+            }
+            nodes.insert(path, Some(deck));
+        }
+
+        let mut rows: Vec<DeckTreeRow> = nodes
+            .into_iter()
+            .map(|(path, deck)| {
+                let name = path.join(SEPARATOR);
+                let mut total = QueueCounts::default();
+                for deck in decks.iter().filter(|d| is_in_subtree(&d.name, &name)) {
+                    total += counts.get(&deck.id).copied().unwrap_or_default();
+                }
+                DeckTreeRow {
+                    name,
+                    depth: path.len() - 1,
+                    deck: deck.cloned(),
+                    counts: total,
+                    has_children: false,
+                }
+            })
+            .collect();
+        for i in 1..rows.len() {
+            if rows[i].depth > rows[i - 1].depth {
+                rows[i - 1].has_children = true;
+            }
+        }
+
+        Self {
+            rows,
+            collapsed: HashSet::new(),
+        }
+    }
+
+    pub fn rows(&self) -> &[DeckTreeRow] {
+        &self.rows
+    }
+
+    /// Rows not hidden inside a collapsed parent.
+    pub fn visible_rows(&self) -> Vec<&DeckTreeRow> {
+        let mut visible = Vec::new();
+        let mut hidden_below: Option<usize> = None;
+        for row in &self.rows {
+            match hidden_below {
+                Some(depth) if row.depth > depth => continue,
+                _ => hidden_below = None,
+            }
+            if self.is_collapsed(&row.name) {
+                hidden_below = Some(row.depth);
+            }
+            visible.push(row);
+        }
+        visible
+    }
+
+    pub fn is_collapsed(&self, name: &str) -> bool {
+        self.collapsed.contains(name)
+    }
+
+    pub fn set_collapsed(&mut self, name: &str, collapsed: bool) {
+        if collapsed {
+            self.collapsed.insert(name.to_string());
+        } else {
+            self.collapsed.remove(name);
+        }
+    }
+
+    /// Decks studied together when `name` is chosen: the deck itself and all nested ones.
+    pub fn subtree_decks(&self, name: &str) -> Vec<DeckInfo> {
+        self.rows
+            .iter()
+            .filter(|row| is_in_subtree(&row.name, name))
+            .filter_map(|row| row.deck.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(id: DeckId, name: &str) -> DeckInfo {
+        DeckInfo {
+            id,
+            name: name.to_string(),
+        }
+    }
+
+    fn counts(new: usize, learning: usize, review: usize) -> QueueCounts {
+        QueueCounts {
+            new,
+            learning,
+            review,
+        }
+    }
+
+    /// `Russian` only exists as a parent, `Russian::Verbs` is a deck with children.
+    fn tree() -> DeckTree {
+        let decks = [
+            info(1, "Russian::Verbs::Motion"),
+            info(2, "Russian::Verbs"),
+            info(3, "Russian::Nouns"),
+            info(4, "German"),
+            info(5, "Russian::Verbs::Aspect"),
+        ];
+        let counts = HashMap::from([
+            (1, counts(1, 0, 2)),
+            (2, counts(10, 1, 0)),
+            (3, counts(0, 0, 5)),
+            (4, counts(3, 0, 0)),
+        ]);
+        DeckTree::new(&decks, &counts)
+    }
+
+    fn names<'a>(rows: impl IntoIterator<Item = &'a DeckTreeRow>) -> Vec<&'a str> {
+        rows.into_iter().map(|row| row.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parents_add_up_their_subtree() {
+        let tree = tree();
+        assert_eq!(
+            names(tree.rows()),
+            [
+                "German",
+                "Russian",
+                "Russian::Nouns",
+                "Russian::Verbs",
+                "Russian::Verbs::Aspect",
+                "Russian::Verbs::Motion",
+            ]
+        );
+        let row = |name: &str| tree.rows().iter().find(|r| r.name == name).unwrap();
+
+        let russian = row("Russian");
+        assert_eq!((russian.depth, russian.deck.as_ref()), (0, None));
+        assert_eq!(russian.counts, counts(11, 1, 7));
+        assert!(russian.has_children);
+
+        let verbs = row("Russian::Verbs");
+        assert_eq!(verbs.depth, 1);
+        assert_eq!(verbs.deck, Some(info(2, "Russian::Verbs")));
+        assert_eq!(verbs.counts, counts(11, 1, 2));
+        assert!(verbs.has_children);
+
+        // a deck without counts has nothing to study
+        let aspect = row("Russian::Verbs::Aspect");
+        assert_eq!(aspect.counts, QueueCounts::default());
+        assert!(!aspect.has_children);
+        assert!(!row("German").has_children);
+        assert!(!row("Russian::Nouns").has_children);
+    }
+
+    #[test]
+    fn collapsed_parent_hides_its_subtree() {
+        let mut tree = tree();
+        tree.set_collapsed("Russian::Verbs", true);
+        assert_eq!(
+            names(tree.visible_rows()),
+            ["German", "Russian", "Russian::Nouns", "Russian::Verbs"]
+        );
+
+        tree.set_collapsed("Russian", true);
+        assert_eq!(names(tree.visible_rows()), ["German", "Russian"]);
+
+        // the inner parent stays collapsed when the outer one is opened
+        tree.set_collapsed("Russian", false);
+        assert!(tree.is_collapsed("Russian::Verbs"));
+        assert_eq!(tree.visible_rows().len(), 4);
+        tree.set_collapsed("Russian::Verbs", false);
+        assert_eq!(tree.visible_rows().len(), tree.rows().len());
+    }
+
+    #[test]
+    fn subtree_goes_by_whole_names() {
+        let decks = [
+            info(1, "Verbs"),
+            info(2, "Verbs::Motion"),
+            info(3, "Verbsy"),
+        ];
+        let tree = DeckTree::new(&decks, &HashMap::new());
+        let ids = |name| {
+            tree.subtree_decks(name)
+                .iter()
+                .map(|d| d.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("Verbs"), [1, 2]);
+        assert_eq!(ids("Verbs::Motion"), [2]);
+        assert_eq!(ids("Verb"), Vec::<DeckId>::new());
+        assert_eq!(leaf_name("Verbs::Motion"), "Motion");
+        assert_eq!(leaf_name("Verbs"), "Verbs");
+    }
+}
@@ -0,0 +1,281 @@
+//! Filtered decks borrow cards from their home decks for custom study, picked by a search
+//! like `tag:verbs is:due`, `rated:3:1` (forgotten in the last 3 days) or `is:new` in random order.
+//!
+//! The cards stay in the files of their home decks, marked with the id of the filtered deck.
+//! A marked card is left out of its home deck's queue until it is studied in the filtered deck
+//! or the filtered deck is emptied.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::logic::{
+    deck::{card::Card, model::DeckId},
+    review::{queue::splitmix64, session::CardKey},
+    study_day::DayNumber,
+};
+
+pub type FilteredDeckId = u32;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterOrder {
+    /// Most overdue first, new cards after the others.
+    #[default]
+    Due,
+    Random,
+    /// Most often forgotten first.
+    Lapses,
+}
+
+/// Orders cards of several decks, smaller goes first.
+pub type SortKey = (i64, i64, u64);
+
+impl FilterOrder {
+    pub const ALL: [FilterOrder; 3] = [FilterOrder::Due, FilterOrder::Random, FilterOrder::Lapses];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterOrder::Due => "due",
+            FilterOrder::Random => "random",
+            FilterOrder::Lapses => "lapses",
+        }
+    }
+
+    /// The random order is seeded, so it stays the same from one session to the next.
+    pub fn sort_key(&self, seed: u64, deck_id: DeckId, card: &Card) -> SortKey {
+        let due = card.fsrs.due.timestamp();
+        match self {
+            FilterOrder::Due => (card.is_new() as i64, due, card.id),
+            FilterOrder::Random => {
+                let mixed = splitmix64(seed ^ splitmix64((deck_id as u64) << 32 ^ card.id));
+                (0, 0, mixed)
+            }
+            FilterOrder::Lapses => (-(card.fsrs.lapses as i64), due, card.id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilteredDeck {
+    pub id: FilteredDeckId,
+    pub name: String,
+    pub search: String,
+    /// Most cards picked when the deck is built.
+    pub limit: usize,
+    pub order: FilterOrder,
+    /// Off makes it a preview: grades don't change the scheduling and aren't logged.
+    pub reschedule: bool,
+    /// Home decks of the borrowed cards, the only ones to load for studying or emptying it.
+    #[serde(default)]
+    pub home_decks: Vec<DeckId>,
+    /// When the deck was built. Ids of emptied decks are given again, the time gives a deck
+    /// with a reused id its own random order.
+    #[serde(default)]
+    pub created: DateTime<Utc>,
+}
+
+impl FilteredDeck {
+    pub fn seed(&self) -> u64 {
+        splitmix64(self.id as u64 ^ splitmix64(self.created.timestamp() as u64))
+    }
+
+    pub fn sort_key(&self, deck_id: DeckId, card: &Card) -> SortKey {
+        self.order.sort_key(self.seed(), deck_id, card)
+    }
+}
+
+/// All filtered decks, stored in `filtered.jsn` on the SD card.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilteredDecks {
+    pub decks: Vec<FilteredDeck>,
+}
+
+impl FilteredDecks {
+    pub const FILE_NAME: &'static str = "filtered.jsn";
+
+    pub fn next_id(&self) -> FilteredDeckId {
+        self.decks.iter().map(|d| d.id).max().unwrap_or(0) + 1
+    }
+
+    pub fn find(&self, id: FilteredDeckId) -> Option<&FilteredDeck> {
+        self.decks.iter().find(|d| d.id == id)
+    }
+
+    pub fn remove(&mut self, id: FilteredDeckId) -> Option<FilteredDeck> {
+        let index = self.decks.iter().position(|d| d.id == id)?;
+        Some(self.decks.remove(index))
+    }
+}
+
+/// Picks the cards of a new filtered deck out of the search matches of every home deck,
+/// keeping no more than about twice the limit in memory.
+pub struct CardPicker<'a> {
+    filtered: &'a FilteredDeck,
+    picked: Vec<(SortKey, CardKey)>,
+}
+
+impl<'a> CardPicker<'a> {
+    pub fn new(filtered: &'a FilteredDeck) -> Self {
+        Self {
+            filtered,
+            picked: Vec::new(),
+        }
+    }
+
+    /// A card matching the search, skipped if suspended, buried or already lent to a filtered deck.
+    pub fn push_card(&mut self, deck_id: DeckId, card: &Card, today: DayNumber) {
+        if card.suspended || card.is_buried(today) || card.filtered.is_some() {
+            return;
+        }
+        let key = CardKey {
+            deck_id,
+            card_id: card.id,
+        };
+        let sort_key = self.filtered.sort_key(deck_id, card);
+        self.picked.push((sort_key, key));
+        if self.picked.len() >= 2 * self.filtered.limit.max(1) {
+            self.trim();
+        }
+    }
+
+    /// The picked cards in study order.
+    pub fn finish(mut self) -> Vec<CardKey> {
+        self.trim();
+        self.picked.into_iter().map(|(_, key)| key).collect()
+    }
+
+    /// Only the first `limit` can make it.
+    fn trim(&mut self) {
+        self.picked.sort_unstable();
+        self.picked.truncate(self.filtered.limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::logic::deck::model::{Deck, DeckInfo};
+
+    fn filtered(limit: usize, order: FilterOrder) -> FilteredDeck {
+        FilteredDeck {
+            id: 1,
+            name: "Custom".into(),
+            search: "is:due".into(),
+            limit,
+            order,
+            reschedule: true,
+            home_decks: vec![7],
+            created: Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap(),
+        }
+    }
+
+    /// Six cards, card n is due n days ago.
+    fn deck() -> Deck {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+        let mut deck = Deck::new(DeckInfo {
+            id: 7,
+            name: "Words".into(),
+        });
+        for i in 1..=6 {
+            let note_id = deck.add_note(vec![format!("front {}", i), "back".into()], now);
+            let card_id = deck.cards_of_note(note_id).next().unwrap().id;
+            let card = deck.card_mut(card_id).unwrap();
+            card.fsrs.state = rs_fsrs::State::Review;
+            card.fsrs.due = now - Duration::days(card_id as i64);
+        }
+        deck
+    }
+
+    fn pick(filtered: &FilteredDeck, deck: &Deck, today: DayNumber) -> Vec<u64> {
+        let mut picker = CardPicker::new(filtered);
+        for card in deck.cards() {
+            picker.push_card(deck.info.id, card, today);
+        }
+        picker.finish().iter().map(|key| key.card_id).collect()
+    }
+
+    #[test]
+    fn picker_skips_suspended_buried_and_lent_cards() {
+        let mut deck = deck();
+        deck.set_suspended(1, true);
+        deck.card_mut(2).unwrap().buried_on = Some(100);
+        deck.card_mut(3).unwrap().buried_on = Some(99);
+        deck.move_to_filtered(&[4], 2);
+
+        let filtered = filtered(10, FilterOrder::Due);
+        assert_eq!(pick(&filtered, &deck, 100), [6, 5, 3]);
+    }
+
+    #[test]
+    fn picker_keeps_the_first_cards_of_the_order() {
+        let deck = deck();
+        assert_eq!(pick(&filtered(2, FilterOrder::Due), &deck, 0), [6, 5]);
+        assert_eq!(
+            pick(&filtered(0, FilterOrder::Due), &deck, 0),
+            Vec::<u64>::new()
+        );
+
+        let mut deck = deck;
+        deck.card_mut(2).unwrap().fsrs.lapses = 3;
+        deck.card_mut(4).unwrap().fsrs.lapses = 1;
+        assert_eq!(pick(&filtered(3, FilterOrder::Lapses), &deck, 0), [2, 4, 6]);
+
+        // the random order is the same for every limit
+        let random = pick(&filtered(6, FilterOrder::Random), &deck, 0);
+        assert_eq!(random.len(), 6);
+        assert_eq!(
+            pick(&filtered(3, FilterOrder::Random), &deck, 0),
+            random[..3]
+        );
+    }
+
+    #[test]
+    fn emptying_returns_only_the_lent_cards() {
+        let mut deck = deck();
+        assert_eq!(deck.move_to_filtered(&[1, 2, 99], 1), 2);
+        assert_eq!(deck.move_to_filtered(&[3], 2), 1);
+        assert_eq!(deck.return_from_filtered(1), 2);
+        assert_eq!(deck.return_from_filtered(1), 0);
+        let lent: Vec<(u64, Option<FilteredDeckId>)> = deck
+            .cards()
+            .iter()
+            .filter(|c| c.filtered.is_some())
+            .map(|c| (c.id, c.filtered))
+            .collect();
+        assert_eq!(lent, [(3, Some(2))]);
+    }
+
+    #[test]
+    fn next_id_follows_the_largest() {
+        let mut decks = FilteredDecks::default();
+        assert_eq!(decks.next_id(), 1);
+        decks.decks.push(FilteredDeck {
+            id: 4,
+            ..filtered(10, FilterOrder::Due)
+        });
+        assert_eq!(decks.next_id(), 5);
+        assert_eq!(decks.remove(4).map(|d| d.id), Some(4));
+        assert!(decks.find(4).is_none());
+    }
+
+    #[test]
+    fn reused_id_gets_another_random_order() {
+        let deck = deck();
+        let first = filtered(6, FilterOrder::Random);
+        let rebuilt = FilteredDeck {
+            created: first.created + Duration::minutes(1),
+            ..first.clone()
+        };
+        assert_eq!(pick(&first, &deck, 0), pick(&first.clone(), &deck, 0));
+        assert_ne!(pick(&first, &deck, 0), pick(&rebuilt, &deck, 0));
+    }
+
+    #[test]
+    fn deck_saved_without_creation_time_loads() {
+        let json = r#"{"id":3,"name":"Custom","search":"is:new","limit":5,"order":"Random","reschedule":false}"#;
+        let filtered: FilteredDeck = serde_json::from_str(json).unwrap();
+        assert_eq!(filtered.created, DateTime::<Utc>::default());
+        assert!(filtered.home_decks.is_empty());
+    }
+}
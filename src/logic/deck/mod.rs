@@ -0,0 +1,12 @@
+pub mod card;
+pub mod card_template;
+pub mod cloze;
+pub mod deck_file;
+pub mod deck_index;
+pub mod deck_options;
+pub mod deck_preset;
+pub mod deck_tree;
+pub mod filtered_deck;
+pub mod model;
+pub mod note;
+pub mod note_draft;
@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use rs_fsrs::State;
+use serde::{Deserialize, Serialize};
+
+use crate::logic::{
+    deck::{
+        card::{Card, CardId},
+        card_template::{CardSides, CardTemplate, TemplateId},
+        cloze::ClozeNumber,
+        deck_options::{DeckOptions, LeechAction},
+        filtered_deck::FilteredDeckId,
+        note::{Note, NoteId},
+    },
+    review::scheduler::Scheduled,
+    study_day::DayNumber,
+};
+
+pub type DeckId = u32;
+
+/// Tag of notes whose cards keep being forgotten.
+pub const LEECH_TAG: &str = "leech";
+
+/// Which card of a note: its template and the cloze deletion it asks.
+type CardSlot = (TemplateId, Option<ClozeNumber>);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeckInfo {
+    pub id: DeckId,
+    pub name: String,
+}
+
+impl DeckInfo {
+    /// SD card only knows 8.3 names, so the file is named after the deck id.
+    pub fn file_name(&self) -> String {
+        format!("deck{:04}.dck", self.id)
+    }
+}
+
+/// Cards studied on one study day, counted against the daily limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayCounts {
+    pub day: DayNumber,
+    pub new: usize,
+    pub review: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deck {
+    pub info: DeckInfo,
+    pub options: DeckOptions,
+    pub today: DayCounts,
+    notes: Vec<Note>,
+    cards: Vec<Card>,
+    next_note_id: NoteId,
+    next_card_id: CardId,
+}
+
+impl Deck {
+    pub fn new(info: DeckInfo) -> Self {
+        Self {
+            info,
+            options: DeckOptions::default(),
+            today: DayCounts::default(),
+            notes: Vec::new(),
+            cards: Vec::new(),
+            next_note_id: 1,
+            next_card_id: 1,
+        }
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        &self.cards
+    }
+
+    pub fn note(&self, id: NoteId) -> Option<&Note> {
+        self.notes.iter().find(|n| n.id == id)
+    }
+
+    pub fn note_mut(&mut self, id: NoteId) -> Option<&mut Note> {
+        self.notes.iter_mut().find(|n| n.id == id)
+    }
+
+    pub fn card(&self, id: CardId) -> Option<&Card> {
+        self.cards.iter().find(|c| c.id == id)
+    }
+
+    pub fn card_mut(&mut self, id: CardId) -> Option<&mut Card> {
+        self.cards.iter_mut().find(|c| c.id == id)
+    }
+
+    pub fn cards_of_note(&self, note_id: NoteId) -> impl Iterator<Item = &Card> {
+        self.cards.iter().filter(move |c| c.note_id == note_id)
+    }
+
+    /// Cards of every note in deck order, for going over all notes without a scan of the
+    /// cards for each of them.
+    pub fn cards_by_note(&self) -> HashMap<NoteId, Vec<&Card>> {
+        let mut by_note: HashMap<NoteId, Vec<&Card>> = HashMap::new();
+        for card in &self.cards {
+            by_note.entry(card.note_id).or_default().push(card);
+        }
+        by_note
+    }
+
+    /// Other cards of the same note.
+    pub fn siblings(&self, card_id: CardId) -> impl Iterator<Item = &Card> {
+        let note_id = self.card(card_id).map(|c| c.note_id);
+        self.cards
+            .iter()
+            .filter(move |c| Some(c.note_id) == note_id && c.id != card_id)
+    }
+
+    /// Buries the siblings of a studied card until the next study day, as far as the options
+    /// allow for their state. Returns the buried cards.
+    pub fn bury_siblings(&mut self, card_id: CardId, today: DayNumber) -> Vec<CardId> {
+        let buried: Vec<CardId> = self
+            .siblings(card_id)
+            .filter(|c| !c.suspended && self.options.is_buried_sibling(c.state()))
+            .map(|c| c.id)
+            .collect();
+        for id in &buried {
+            if let Some(card) = self.card_mut(*id) {
+                card.buried_on = Some(today);
+            }
+        }
+        buried
+    }
+
+    pub fn due_cards(&self, now: DateTime<Utc>) -> impl Iterator<Item = &Card> {
+        self.cards
+            .iter()
+            .filter(move |c| !c.suspended && c.is_due(now))
+    }
+
+    /// Adds a note with a card for every enabled template and returns the new note id.
+    pub fn add_note(&mut self, fields: Vec<String>, now: DateTime<Utc>) -> NoteId {
+        let note = Note::new(self.next_note_id, fields, now);
+        let missing = self.missing_cards(&note, &[]);
+        let note_id = note.id;
+        self.insert_note(note);
+        self.insert_missing_cards(note_id, &missing, now);
+        note_id
+    }
+
+    /// Replaces the fields of a note. Its cards show the new text right away, templates
+    /// whose question field got filled in generate their cards and cards with nothing left
+    /// to ask (a deleted cloze, an emptied question field) are removed.
+    /// Returns false if there is no such note.
+    pub fn update_note(
+        &mut self,
+        note_id: NoteId,
+        fields: Vec<String>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let Some(note) = self.note_mut(note_id) else {
+            return false;
+        };
+        note.fields = fields;
+        note.modified = now;
+        let note = note.clone();
+        self.remove_stale_cards(&HashMap::from([(note_id, self.card_slots(&note))]));
+        self.generate_cards(note_id, now);
+        true
+    }
+
+    /// Gives the note the cards of enabled templates it doesn't have yet, returns how many were added.
+    pub fn generate_cards(&mut self, note_id: NoteId, now: DateTime<Utc>) -> usize {
+        let Some(note) = self.note(note_id) else {
+            return 0;
+        };
+        let cards: Vec<&Card> = self.cards_of_note(note_id).collect();
+        let missing = self.missing_cards(note, &cards);
+        self.insert_missing_cards(note_id, &missing, now)
+    }
+
+    /// Cards the enabled templates make of the note.
+    fn card_slots(&self, note: &Note) -> Vec<CardSlot> {
+        self.options
+            .templates
+            .iter()
+            .flat_map(|t| t.cards_for(note).into_iter().map(|cloze| (t.id, cloze)))
+            .collect()
+    }
+
+    /// Cards the enabled templates make of the note which are not among its `cards`.
+    fn missing_cards(&self, note: &Note, cards: &[&Card]) -> Vec<CardSlot> {
+        self.card_slots(note)
+            .into_iter()
+            .filter(|(template, cloze)| {
+                !cards
+                    .iter()
+                    .any(|c| c.template == *template && c.cloze == *cloze)
+            })
+            .collect()
+    }
+
+    /// Removes the cards of the notes in `slots` their template doesn't make any more, cards
+    /// of an unknown template are kept. Returns how many were removed.
+    fn remove_stale_cards(&mut self, slots: &HashMap<NoteId, Vec<CardSlot>>) -> usize {
+        let count = self.cards.len();
+        let templates = &self.options.templates;
+        self.cards.retain(|c| {
+            let is_known = templates.iter().any(|t| t.id == c.template);
+            !is_known
+                || slots
+                    .get(&c.note_id)
+                    .map_or(true, |s| s.contains(&(c.template, c.cloze)))
+        });
+        count - self.cards.len()
+    }
+
+    fn insert_missing_cards(
+        &mut self,
+        note_id: NoteId,
+        missing: &[CardSlot],
+        now: DateTime<Utc>,
+    ) -> usize {
+        for (template, cloze) in missing {
+            let card_id = self.next_card_id;
+            self.insert_card(Card {
+                cloze: *cloze,
+                ..Card::new(card_id, note_id, *template, now)
+            });
+        }
+        missing.len()
+    }
+
+    /// Brings the cards in line with the templates: cards of disabled templates and cards with
+    /// nothing left to ask are removed, the other cards are kept as they are and missing cards
+    /// of enabled templates are generated. Returns how many cards were added and removed.
+    pub fn sync_cards(&mut self, now: DateTime<Utc>) -> (usize, usize) {
+        let slots: HashMap<NoteId, Vec<CardSlot>> = self
+            .notes
+            .iter()
+            .map(|note| (note.id, self.card_slots(note)))
+            .collect();
+        let removed = self.remove_stale_cards(&slots);
+
+        let by_note = self.cards_by_note();
+        let missing: Vec<(NoteId, Vec<CardSlot>)> = self
+            .notes
+            .iter()
+            .map(|note| {
+                let cards = by_note.get(&note.id).map_or(&[][..], |c| c.as_slice());
+                let missing = slots[&note.id]
+                    .iter()
+                    .filter(|(template, cloze)| {
+                        !cards
+                            .iter()
+                            .any(|c| c.template == *template && c.cloze == *cloze)
+                    })
+                    .copied()
+                    .collect();
+                (note.id, missing)
+            })
+            .collect();
+        let added = missing
+            .iter()
+            .map(|(note_id, missing)| self.insert_missing_cards(*note_id, missing, now))
+            .sum();
+        (added, removed)
+    }
+
+    /// The template a card is asked with, cards of an unknown template are asked front -> back.
+    pub fn template_of(&self, card: &Card) -> CardTemplate {
+        self.options
+            .templates
+            .iter()
+            .find(|t| t.id == card.template)
+            .cloned()
+            .unwrap_or_else(CardTemplate::forward)
+    }
+
+    /// What the card asks and answers, `None` if its note is gone.
+    pub fn sides(&self, card: &Card) -> Option<CardSides> {
+        let note = self.note(card.note_id)?;
+        Some(self.template_of(card).sides(note, card.cloze))
+    }
+
+    /// Puts an already existing note (e.g. read from SD) into the deck keeping its id.
+    pub fn insert_note(&mut self, note: Note) {
+        self.next_note_id = self.next_note_id.max(note.id + 1);
+        self.notes.push(note);
+    }
+
+    /// Puts an already existing card (e.g. read from SD) into the deck keeping its id.
+    pub fn insert_card(&mut self, card: Card) {
+        self.next_card_id = self.next_card_id.max(card.id + 1);
+        self.cards.push(card);
+    }
+
+    /// Puts back a note and its cards as they were, e.g. to undo a change. Cards the note got
+    /// since are removed, the others keep their place in the deck.
+    pub fn restore_note(&mut self, note: Note, cards: &[Card]) {
+        let note_id = note.id;
+        match self.note_mut(note_id) {
+            Some(current) => *current = note,
+            None => self.insert_note(note),
+        }
+        self.cards
+            .retain(|c| c.note_id != note_id || cards.iter().any(|saved| saved.id == c.id));
+        for saved in cards {
+            match self.card_mut(saved.id) {
+                Some(card) => *card = saved.clone(),
+                None => self.insert_card(saved.clone()),
+            }
+        }
+    }
+
+    /// Cards studied on `day`, the counters of an earlier day are stale.
+    pub fn studied_on(&self, day: DayNumber) -> DayCounts {
+        if self.today.day == day {
+            self.today
+        } else {
+            DayCounts {
+                day,
+                ..Default::default()
+            }
+        }
+    }
+
+    /// Counts a graded card towards the daily limits of `day`.
+    pub fn count_studied(&mut self, day: DayNumber, state_before: State) {
+        let mut today = self.studied_on(day);
+        match state_before {
+            State::New => today.new += 1,
+            State::Review => today.review += 1,
+            State::Learning | State::Relearning => {}
+        }
+        self.today = today;
+    }
+
+    /// Returns false if the card is not in the deck.
+    pub fn set_suspended(&mut self, card_id: CardId, suspended: bool) -> bool {
+        match self.card_mut(card_id) {
+            Some(card) => {
+                card.suspended = suspended;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Tags the note of a card which keeps being forgotten and, if the deck says so,
+    /// suspends the card. Returns false if the card is not in the deck.
+    pub fn mark_leech(&mut self, card_id: CardId) -> bool {
+        let suspend = self.options.leech_action == LeechAction::Suspend;
+        let Some(card) = self.card_mut(card_id) else {
+            return false;
+        };
+        card.suspended |= suspend;
+        let note_id = card.note_id;
+        if let Some(note) = self.note_mut(note_id) {
+            note.add_tag(LEECH_TAG);
+        }
+        true
+    }
+
+    /// Lends the cards to a filtered deck, returns how many of them are in this deck.
+    pub fn move_to_filtered(&mut self, card_ids: &[CardId], filtered: FilteredDeckId) -> usize {
+        let mut moved = 0;
+        for card in self.cards.iter_mut().filter(|c| card_ids.contains(&c.id)) {
+            card.filtered = Some(filtered);
+            moved += 1;
+        }
+        moved
+    }
+
+    /// Takes back every card lent to the filtered deck, returns how many there were.
+    pub fn return_from_filtered(&mut self, filtered: FilteredDeckId) -> usize {
+        let mut returned = 0;
+        for card in self
+            .cards
+            .iter_mut()
+            .filter(|c| c.filtered == Some(filtered))
+        {
+            card.filtered = None;
+            returned += 1;
+        }
+        returned
+    }
+
+    /// Stores the chosen scheduling of a review, returns false if the card is not in the deck.
+    pub fn apply_review(&mut self, card_id: CardId, scheduled: &Scheduled) -> bool {
+        match self.card_mut(card_id) {
+            Some(card) => {
+                card.apply(scheduled);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use rs_fsrs::Rating;
+
+    use super::*;
+    use crate::logic::deck::{
+        deck_file::{read_deck, write_deck, DeckRecord},
+        deck_preset::DeckPreset,
+    };
+
+    #[test]
+    fn scheduled_card_survives_a_save() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let mut deck = Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        });
+        let note_id = deck.add_note(vec!["кошка".into(), "cat".into()], now);
+        let card_id = deck.cards_of_note(note_id).next().unwrap().id;
+
+        let scheduler = DeckPreset::new(1, "Default").scheduler();
+        let first = scheduler.next(deck.card(card_id).unwrap(), now, Rating::Good);
+        assert!(deck.apply_review(card_id, &first));
+        let later = now + Duration::minutes(10);
+        let second = scheduler.next(deck.card(card_id).unwrap(), later, Rating::Good);
+        assert!(deck.apply_review(card_id, &second));
+
+        let card = deck.card(card_id).unwrap().clone();
+        assert_eq!(card.state(), State::Review);
+        assert_eq!(card.fsrs.reps, 2);
+
+        let loaded = read_deck(&write_deck(&deck).unwrap()).unwrap();
+        assert_eq!(loaded.card(card_id), Some(&card));
+        assert_eq!(loaded, deck);
+        assert!(!loaded.card(card_id).unwrap().is_due(later));
+        assert!(loaded.card(card_id).unwrap().is_due(card.fsrs.due));
+    }
+
+    #[test]
+    fn generated_cards_are_saved_after_their_notes() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let mut deck = Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        });
+        for front in ["кошка", "собака", "дом"] {
+            deck.add_note(vec![front.into(), "back".into()], now);
+        }
+        let reverse = deck
+            .options
+            .templates
+            .iter_mut()
+            .find(|t| t.id == CardTemplate::REVERSE_ID)
+            .unwrap();
+        reverse.enabled = true;
+        assert_eq!(deck.sync_cards(now), (3, 0));
+        assert_eq!(deck.sync_cards(now), (0, 0));
+
+        // the reverse cards come last in the deck, the file keeps every card with its note
+        let file = write_deck(&deck).unwrap();
+        let records: Vec<(&str, NoteId)> = file
+            .lines()
+            .skip(1)
+            .map(|line| match DeckRecord::parse_line(line).unwrap() {
+                DeckRecord::Note(note) => ("note", note.id),
+                DeckRecord::Card(card) => ("card", card.note_id),
+                DeckRecord::Deck(_) => panic!("second deck record"),
+            })
+            .collect();
+        assert_eq!(
+            records,
+            [
+                ("note", 1),
+                ("card", 1),
+                ("card", 1),
+                ("note", 2),
+                ("card", 2),
+                ("card", 2),
+                ("note", 3),
+                ("card", 3),
+                ("card", 3),
+            ]
+        );
+        let loaded = read_deck(&file).unwrap();
+        assert_eq!(loaded.cards().len(), 6);
+        for card in deck.cards() {
+            assert_eq!(loaded.card(card.id), Some(card));
+        }
+    }
+
+    fn words() -> Deck {
+        Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        })
+    }
+
+    /// Template and cloze number of the note's cards, by card id.
+    fn slots(deck: &Deck, note_id: NoteId) -> Vec<(CardId, TemplateId, Option<ClozeNumber>)> {
+        deck.cards_of_note(note_id)
+            .map(|c| (c.id, c.template, c.cloze))
+            .collect()
+    }
+
+    #[test]
+    fn edit_removes_the_card_of_a_deleted_cloze() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let cloze = CardTemplate::CLOZE_ID;
+        let mut deck = words();
+        let note_id = deck.add_note(
+            vec!["{{c1::Москва}} is on the {{c2::Moskva}}".into(), "".into()],
+            now,
+        );
+        assert_eq!(
+            slots(&deck, note_id),
+            [(1, cloze, Some(1)), (2, cloze, Some(2))]
+        );
+
+        let edited = vec!["{{c1::Москва}} is on the Moskva".to_string(), "".into()];
+        assert!(deck.update_note(note_id, edited, now));
+        assert_eq!(slots(&deck, note_id), [(1, cloze, Some(1))]);
+
+        let edited = vec![
+            "{{c1::Москва}} is on the {{c3::Moskva}}".to_string(),
+            "".into(),
+        ];
+        assert!(deck.update_note(note_id, edited, now));
+        assert_eq!(
+            slots(&deck, note_id),
+            [(1, cloze, Some(1)), (3, cloze, Some(3))]
+        );
+    }
+
+    #[test]
+    fn edit_removes_the_card_of_an_emptied_question() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let (forward, reverse) = (CardTemplate::FORWARD_ID, CardTemplate::REVERSE_ID);
+        let mut deck = words();
+        deck.options.templates[1].enabled = true;
+        let note_id = deck.add_note(vec!["кошка".into(), "cat".into()], now);
+        assert_eq!(
+            slots(&deck, note_id),
+            [(1, forward, None), (2, reverse, None)]
+        );
+
+        assert!(deck.update_note(note_id, vec!["кошка".into(), " ".into()], now));
+        assert_eq!(slots(&deck, note_id), [(1, forward, None)]);
+        assert!(deck.update_note(note_id, vec!["кошка".into(), "cat".into()], now));
+        assert_eq!(
+            slots(&deck, note_id),
+            [(1, forward, None), (3, reverse, None)]
+        );
+    }
+
+    #[test]
+    fn sync_removes_cards_with_nothing_to_ask() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let mut deck = words();
+        let cloze_note = deck.add_note(vec!["{{c1::Москва}}".into(), "".into()], now);
+        let basic_note = deck.add_note(vec!["кошка".into(), "".into()], now);
+        // saved before the fields were changed elsewhere
+        deck.insert_card(Card {
+            cloze: Some(2),
+            ..Card::new(3, cloze_note, CardTemplate::CLOZE_ID, now)
+        });
+        deck.insert_card(Card::new(4, basic_note, CardTemplate::REVERSE_ID, now));
+        // a template this version doesn't know
+        deck.insert_card(Card::new(5, basic_note, 7, now));
+        deck.options.templates[1].enabled = true;
+
+        assert_eq!(deck.sync_cards(now), (0, 2));
+        assert_eq!(
+            slots(&deck, cloze_note),
+            [(1, CardTemplate::CLOZE_ID, Some(1))]
+        );
+        assert_eq!(
+            slots(&deck, basic_note),
+            [(2, CardTemplate::FORWARD_ID, None), (5, 7, None)]
+        );
+    }
+
+    /// A note with a new, a learning, a review and a suspended review card besides the
+    /// studied one, and a note of its own for card 6.
+    fn deck_with_siblings(now: DateTime<Utc>) -> Deck {
+        let mut deck = Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        });
+        let note_id = deck.add_note(vec!["дом".into(), "house".into()], now);
+        for (id, state) in [
+            (2, State::New),
+            (3, State::Learning),
+            (4, State::Review),
+            (5, State::Review),
+        ] {
+            let mut card = Card::new(id, note_id, CardTemplate::FORWARD_ID, now);
+            card.fsrs.state = state;
+            deck.insert_card(card);
+        }
+        deck.set_suspended(5, true);
+        deck.add_note(vec!["кот".into(), "cat".into()], now);
+        deck
+    }
+
+    #[test]
+    fn siblings_are_buried_as_the_options_allow() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let bury = |new, reviews, learning| {
+            let mut deck = deck_with_siblings(now);
+            deck.options.bury_new = new;
+            deck.options.bury_reviews = reviews;
+            deck.options.bury_learning = learning;
+            let buried = deck.bury_siblings(1, 12);
+            let marked: Vec<CardId> = deck
+                .cards()
+                .iter()
+                .filter(|c| c.is_buried(12))
+                .map(|c| c.id)
+                .collect();
+            assert_eq!(buried, marked);
+            buried
+        };
+        assert_eq!(bury(true, false, false), [2]);
+        assert_eq!(bury(false, false, true), [3]);
+        assert_eq!(bury(false, true, false), [4]);
+        assert_eq!(bury(true, true, true), [2, 3, 4]);
+        assert_eq!(bury(false, false, false), Vec::<CardId>::new());
+
+        // buried until the next study day
+        let mut deck = deck_with_siblings(now);
+        deck.bury_siblings(1, 12);
+        let card = deck.card(2).unwrap();
+        assert!(card.is_buried(12));
+        assert!(!card.is_buried(13));
+    }
+}
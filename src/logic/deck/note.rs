@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::logic::deck::cloze;
+
+pub type NoteId = u64;
+
+/// A piece of knowledge (usually a word and its translation) that one or more cards are made from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub id: NoteId,
+    pub fields: Vec<String>,
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+    /// Position in a frequency list, 1 is the most frequent word.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Note {
+    pub fn new(id: NoteId, fields: Vec<String>, now: DateTime<Utc>) -> Self {
+        Self {
+            id,
+            fields,
+            created: now,
+            modified: now,
+            rank: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Returns the field by index or an empty string if the note has less fields.
+    pub fn field(&self, index: usize) -> &str {
+        self.fields.get(index).map(|f| f.as_str()).unwrap_or("")
+    }
+
+    /// The first field identifies the note for sorting and duplicate checks.
+    pub fn sort_field(&self) -> &str {
+        self.field(0)
+    }
+
+    /// A note with cloze deletions gets a card per deletion number instead of the basic cards.
+    pub fn is_cloze(&self) -> bool {
+        self.fields.iter().any(|f| cloze::has_deletions(f))
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Adds the tag unless the note already has it.
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    /// Replaces a field, a missing field is added together with the empty ones before it.
+    pub fn set_field(&mut self, index: usize, value: String, now: DateTime<Utc>) {
+        if self.fields.len() <= index {
+            self.fields.resize(index + 1, String::new());
+        }
+        self.fields[index] = value;
+        self.modified = now;
+    }
+}
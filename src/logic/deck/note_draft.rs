@@ -0,0 +1,185 @@
+//! A note being typed in on the device, turned into a note of a deck once it is saved.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::logic::deck::{
+    model::Deck,
+    note::{Note, NoteId},
+};
+
+/// Fields a new note starts with: front and back.
+pub const NEW_NOTE_FIELDS: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteDraft {
+    pub fields: Vec<String>,
+    /// Tags separated by spaces, as typed.
+    pub tags: String,
+}
+
+impl Default for NoteDraft {
+    fn default() -> Self {
+        Self {
+            fields: vec![String::new(); NEW_NOTE_FIELDS],
+            tags: String::new(),
+        }
+    }
+}
+
+impl NoteDraft {
+    pub fn from_note(note: &Note) -> Self {
+        let mut fields = note.fields.clone();
+        if fields.len() < NEW_NOTE_FIELDS {
+            fields.resize(NEW_NOTE_FIELDS, String::new());
+        }
+        Self {
+            fields,
+            tags: note.tags.join(" "),
+        }
+    }
+
+    pub fn field_name(index: usize) -> String {
+        match index {
+            0 => "Front".to_string(),
+            1 => "Back".to_string(),
+            i => format!("Extra {}", i - 1),
+        }
+    }
+
+    /// Typed tags without repeats, compared ignoring case like `Note::has_tag`.
+    pub fn tag_list(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tags.split_whitespace() {
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+        tags
+    }
+
+    /// Fields with the spaces around them trimmed, the front has to be filled in.
+    fn trimmed_fields(&self) -> Result<Vec<String>> {
+        let fields: Vec<String> = self.fields.iter().map(|f| f.trim().to_string()).collect();
+        if fields.first().map_or(true, |f| f.is_empty()) {
+            return Err(anyhow!("{} is empty", Self::field_name(0)));
+        }
+        Ok(fields)
+    }
+
+    /// Adds the draft to the deck as a new note with its cards.
+    pub fn add_to(&self, deck: &mut Deck, now: DateTime<Utc>) -> Result<NoteId> {
+        let note_id = deck.add_note(self.trimmed_fields()?, now);
+        if let Some(note) = deck.note_mut(note_id) {
+            note.tags = self.tag_list();
+        }
+        Ok(note_id)
+    }
+
+    /// Replaces the fields and tags of a note of the deck, its cards keep their scheduling.
+    pub fn apply_to(&self, deck: &mut Deck, note_id: NoteId, now: DateTime<Utc>) -> Result<()> {
+        if !deck.update_note(note_id, self.trimmed_fields()?, now) {
+            return Err(anyhow!("note {} is gone from {}", note_id, deck.info.name));
+        }
+        if let Some(note) = deck.note_mut(note_id) {
+            note.tags = self.tag_list();
+        }
+        Ok(())
+    }
+
+    /// Another note of the deck with the same front, which is usually a word added twice.
+    pub fn find_duplicate(&self, deck: &Deck, except: Option<NoteId>) -> Option<NoteId> {
+        let front = self.fields.first()?.trim();
+        deck.notes()
+            .iter()
+            .find(|n| Some(n.id) != except && n.sort_field().trim() == front)
+            .map(|n| n.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::logic::deck::model::DeckInfo;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 8, 1, 9, 0, 0).unwrap()
+    }
+
+    fn deck() -> Deck {
+        let mut deck = Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        });
+        deck.add_note(vec!["дом".into(), "house".into()], now());
+        deck.add_note(vec!["кот".into(), "cat".into()], now());
+        deck
+    }
+
+    fn draft(front: &str, back: &str, tags: &str) -> NoteDraft {
+        NoteDraft {
+            fields: vec![front.to_string(), back.to_string()],
+            tags: tags.to_string(),
+        }
+    }
+
+    #[test]
+    fn tags_are_typed_once() {
+        let draft = draft("", "", "  verbs Motion verbs  motion\tб б ");
+        assert_eq!(draft.tag_list(), ["verbs", "Motion", "б"]);
+        assert!(NoteDraft::default().tag_list().is_empty());
+    }
+
+    #[test]
+    fn front_has_to_be_filled_in() {
+        let mut deck = deck();
+        let error = draft("  ", "back", "")
+            .add_to(&mut deck, now())
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Front is empty");
+        assert_eq!(deck.notes().len(), 2);
+
+        let note_id = draft(" лес ", " forest\n", "nature nature")
+            .add_to(&mut deck, now())
+            .unwrap();
+        let note = deck.note(note_id).unwrap();
+        assert_eq!(note.fields, ["лес", "forest"]);
+        assert_eq!(note.tags, ["nature"]);
+        assert_eq!(deck.cards_of_note(note_id).count(), 1);
+        assert_eq!(NoteDraft::from_note(note), draft("лес", "forest", "nature"));
+    }
+
+    #[test]
+    fn edit_keeps_the_cards() {
+        let mut deck = deck();
+        let card = deck.cards()[0].clone();
+        draft("дом", "home", "b a")
+            .apply_to(&mut deck, card.note_id, now())
+            .unwrap();
+        assert_eq!(deck.note(card.note_id).unwrap().fields, ["дом", "home"]);
+        assert_eq!(deck.note(card.note_id).unwrap().tags, ["b", "a"]);
+        assert_eq!(deck.card(card.id), Some(&card));
+
+        assert!(draft("x", "y", "").apply_to(&mut deck, 99, now()).is_err());
+        assert!(draft("", "y", "")
+            .apply_to(&mut deck, card.note_id, now())
+            .is_err());
+        assert_eq!(deck.note(card.note_id).unwrap().fields, ["дом", "home"]);
+    }
+
+    #[test]
+    fn duplicate_is_found_by_the_trimmed_front() {
+        let deck = deck();
+        let cat = deck.notes()[1].id;
+        assert_eq!(
+            draft(" кот ", "", "").find_duplicate(&deck, None),
+            Some(cat)
+        );
+        // the note being edited is not a duplicate of itself
+        assert_eq!(draft("кот", "", "").find_duplicate(&deck, Some(cat)), None);
+        assert_eq!(draft("Кот", "", "").find_duplicate(&deck, None), None);
+        assert_eq!(draft("пёс", "", "").find_duplicate(&deck, None), None);
+    }
+}
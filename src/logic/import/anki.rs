@@ -0,0 +1,208 @@
+//! Pieces of the Anki package conversion which don't need the SQLite collection itself.
+
+use chrono::{DateTime, Utc};
+use rs_fsrs::Rating;
+
+use crate::logic::{
+    deck::{
+        card::{Card, CardId},
+        card_template::CardTemplate,
+        cloze::ClozeNumber,
+        model::Deck,
+        note::NoteId,
+    },
+    import::{csv_import::ColumnMapping, html::strip_html},
+    review::scheduler::Scheduler,
+};
+
+/// Anki separates note fields with the unit separator character.
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+/// Maps fields of an Anki note type to front, back and extra fields of a note.
+pub struct AnkiNoteType {
+    pub name: String,
+    mapping: ColumnMapping,
+}
+
+impl AnkiNoteType {
+    /// Fields named like "Front"/"Back" (or "Word"/"Translation") are used when present,
+    /// otherwise the first two fields in order.
+    pub fn new(name: &str, field_names: &[String]) -> Self {
+        let mapping = ColumnMapping::from_header(field_names)
+            .unwrap_or_else(|| ColumnMapping::with_extra(0, 1, field_names.len()));
+        Self {
+            name: name.to_string(),
+            mapping,
+        }
+    }
+
+    /// Splits the raw `notes.flds` value and returns plain text fields in note order.
+    pub fn note_fields(&self, flds: &str) -> Vec<String> {
+        let fields: Vec<String> = flds.split(FIELD_SEPARATOR).map(strip_html).collect();
+        self.mapping.fields(&fields)
+    }
+}
+
+/// One row of the Anki `revlog` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnkiReview {
+    pub time: DateTime<Utc>,
+    /// Answer button 1-4, 0 for a manual reschedule.
+    pub ease: u8,
+    /// 0 learn, 1 review, 2 relearn, 3 filtered deck, 4 manual, 5 rescheduled.
+    pub kind: u8,
+}
+
+impl AnkiReview {
+    fn rating(&self) -> Option<Rating> {
+        if self.kind > 3 {
+            return None;
+        }
+        match self.ease {
+            1 => Some(Rating::Again),
+            2 => Some(Rating::Hard),
+            3 => Some(Rating::Good),
+            4 => Some(Rating::Easy),
+            _ => None,
+        }
+    }
+}
+
+/// Rebuilds the FSRS state of a new card by replaying its Anki review history in time order.
+/// Manual reschedules are skipped, returns the number of replayed reviews.
+pub fn replay_reviews(card: &mut Card, reviews: &[AnkiReview], scheduler: &Scheduler) -> usize {
+    let mut reviews: Vec<&AnkiReview> = reviews.iter().collect();
+    reviews.sort_by_key(|r| r.time);
+
+    let mut replayed = 0;
+    for review in reviews {
+        let Some(rating) = review.rating() else {
+            continue;
+        };
+        let scheduled = scheduler.next(card, review.time, rating);
+        card.apply(&scheduled);
+        replayed += 1;
+    }
+    replayed
+}
+
+/// The card of the note an Anki card becomes: ord 0 and 1 of a standard note are the forward
+/// and the reverse card, ord N of a cloze note asks deletion N + 1. The reverse template is
+/// enabled for the deck once one of its notes has a reverse card.
+pub fn card_for_ord(
+    deck: &mut Deck,
+    note_id: NoteId,
+    ord: i64,
+    now: DateTime<Utc>,
+) -> Option<CardId> {
+    let (template, cloze) = match (deck.note(note_id)?.is_cloze(), ord) {
+        (true, ord) => (
+            CardTemplate::CLOZE_ID,
+            Some(ClozeNumber::try_from(ord + 1).ok()?),
+        ),
+        (false, 0) => (CardTemplate::FORWARD_ID, None),
+        (false, 1) => (CardTemplate::REVERSE_ID, None),
+        (false, _) => return None,
+    };
+    let find = |deck: &Deck| {
+        deck.cards_of_note(note_id)
+            .find(|c| c.template == template && c.cloze == cloze)
+            .map(|c| c.id)
+    };
+    if let Some(card_id) = find(deck) {
+        return Some(card_id);
+    }
+
+    let disabled = deck
+        .options
+        .templates
+        .iter_mut()
+        .find(|t| t.id == template && !t.enabled)?;
+    disabled.enabled = true;
+    deck.generate_cards(note_id, now);
+    find(deck)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::logic::deck::model::DeckInfo;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap()
+    }
+
+    /// A deck with the default templates, reverse cards are off.
+    fn deck() -> Deck {
+        Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        })
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn named_fields_are_mapped_to_front_and_back() {
+        let note_type = AnkiNoteType::new("Basic", &names(&["Example", "Back", "Front"]));
+        assert_eq!(
+            note_type.note_fields("a <i>cat</i>\u{1f}кошка\u{1f}<b>cat</b>"),
+            names(&["cat", "кошка", "a cat"])
+        );
+    }
+
+    #[test]
+    fn unnamed_fields_are_taken_in_order() {
+        let note_type = AnkiNoteType::new("Vocab", &names(&["Kanji", "Reading", "Notes"]));
+        assert_eq!(
+            note_type.note_fields("猫\u{1f}ねこ<br>neko\u{1f}"),
+            names(&["猫", "ねこ\nneko"])
+        );
+    }
+
+    #[test]
+    fn standard_ords_are_forward_and_reverse_cards() {
+        let mut deck = deck();
+        let note_id = deck.add_note(names(&["кошка", "cat"]), now());
+
+        let forward = card_for_ord(&mut deck, note_id, 0, now()).unwrap();
+        assert_eq!(
+            deck.card(forward).unwrap().template,
+            CardTemplate::FORWARD_ID
+        );
+        assert_eq!(deck.cards_of_note(note_id).count(), 1);
+
+        let reverse = card_for_ord(&mut deck, note_id, 1, now()).unwrap();
+        assert_eq!(
+            deck.card(reverse).unwrap().template,
+            CardTemplate::REVERSE_ID
+        );
+        assert!(deck
+            .options
+            .templates
+            .iter()
+            .any(|t| t.id == CardTemplate::REVERSE_ID && t.enabled));
+        assert_eq!(card_for_ord(&mut deck, note_id, 1, now()), Some(reverse));
+        assert_eq!(deck.cards_of_note(note_id).count(), 2);
+
+        assert_eq!(card_for_ord(&mut deck, note_id, 2, now()), None);
+    }
+
+    #[test]
+    fn cloze_ord_is_the_next_deletion() {
+        let mut deck = deck();
+        let note_id = deck.add_note(names(&["{{c1::кошка}} и {{c2::собака}}", ""]), now());
+
+        let second = card_for_ord(&mut deck, note_id, 1, now()).unwrap();
+        let card = deck.card(second).unwrap();
+        assert_eq!(card.template, CardTemplate::CLOZE_ID);
+        assert_eq!(card.cloze, Some(2));
+
+        assert_eq!(card_for_ord(&mut deck, note_id, 5, now()), None);
+        assert_eq!(card_for_ord(&mut deck, note_id, -1, now()), None);
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::logic::{
+    deck::{cloze, model::Deck},
+    import::csv_parser::CsvParser,
+};
+
+/// Only this many error lines are kept in a report, the rest are counted.
+const MAX_REPORTED_ERRORS: usize = 8;
+
+const FRONT_COLUMNS: [&str; 7] = [
+    "front",
+    "question",
+    "word",
+    "term",
+    "слово",
+    "вопрос",
+    "лицо",
+];
+const BACK_COLUMNS: [&str; 8] = [
+    "back",
+    "answer",
+    "translation",
+    "meaning",
+    "definition",
+    "перевод",
+    "ответ",
+    "оборот",
+];
+/// Position in a frequency list, used to introduce frequent words first.
+const RANK_COLUMNS: [&str; 5] = ["rank", "frequency", "freq", "ранг", "частотность"];
+/// Space or comma separated tags of the note.
+const TAG_COLUMNS: [&str; 4] = ["tags", "tag", "теги", "метки"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub file_name: String,
+    pub deck_name: String,
+    pub imported: usize,
+    pub duplicates: usize,
+    pub skipped: usize,
+    pub error_count: usize,
+    /// First errors with their line numbers.
+    pub errors: Vec<String>,
+}
+
+impl ImportReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "+{} dup {} skip {} err {}",
+            self.imported, self.duplicates, self.skipped, self.error_count
+        )
+    }
+}
+
+/// Which CSV column (or Anki field) goes to which note field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ColumnMapping {
+    front: usize,
+    back: usize,
+    extra: Vec<usize>,
+    rank: Option<usize>,
+    tags: Option<usize>,
+}
+
+impl ColumnMapping {
+    /// Builds the mapping from a header row, `None` if the row doesn't look like a header.
+    pub(crate) fn from_header(record: &[String]) -> Option<Self> {
+        let position = |names: &[&str]| {
+            record
+                .iter()
+                .position(|column| names.contains(&column.trim().to_lowercase().as_str()))
+        };
+        let front = position(&FRONT_COLUMNS);
+        let back = position(&BACK_COLUMNS);
+        if front.is_none() && back.is_none() {
+            return None;
+        }
+
+        let rank = position(&RANK_COLUMNS);
+        let tags = position(&TAG_COLUMNS);
+        let is_special = |i: usize| Some(i) == rank || Some(i) == tags;
+        let front = front.unwrap_or_else(|| {
+            (0..record.len())
+                .find(|i| Some(*i) != back && !is_special(*i))
+                .unwrap_or(0)
+        });
+        let back = back.unwrap_or_else(|| {
+            (0..record.len())
+                .find(|i| *i != front && !is_special(*i))
+                .unwrap_or(1)
+        });
+        let mut mapping = Self::with_extra(front, back, record.len());
+        if let Some(rank) = rank.filter(|r| *r != front && *r != back) {
+            mapping.extra.retain(|i| *i != rank);
+            mapping.rank = Some(rank);
+        }
+        if let Some(tags) = tags.filter(|t| *t != front && *t != back) {
+            mapping.extra.retain(|i| *i != tags);
+            mapping.tags = Some(tags);
+        }
+        Some(mapping)
+    }
+
+    pub(crate) fn with_extra(front: usize, back: usize, columns: usize) -> Self {
+        let extra = (0..columns).filter(|i| *i != front && *i != back).collect();
+        Self {
+            front,
+            back,
+            extra,
+            rank: None,
+            tags: None,
+        }
+    }
+
+    /// The frequency rank column if the header has one and the row holds a number there.
+    pub(crate) fn rank(&self, record: &[String]) -> Option<u32> {
+        record.get(self.rank?)?.trim().parse().ok()
+    }
+
+    /// Tags from the tags column if the header has one.
+    pub(crate) fn tags(&self, record: &[String]) -> Vec<String> {
+        let Some(column) = self.tags.and_then(|i| record.get(i)) else {
+            return Vec::new();
+        };
+        column
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty())
+            .map(|t| t.to_string())
+            .collect()
+    }
+
+    pub(crate) fn fields(&self, record: &[String]) -> Vec<String> {
+        let column = |i: usize| {
+            record
+                .get(i)
+                .map(|f| f.trim().to_string())
+                .unwrap_or_default()
+        };
+        let mut fields = vec![column(self.front), column(self.back)];
+        fields.extend(self.extra.iter().map(|i| column(*i)));
+        // extra columns are optional, don't keep empty tails
+        while fields.len() > 2 && fields.last().is_some_and(|f| f.is_empty()) {
+            fields.pop();
+        }
+        fields
+    }
+}
+
+/// Adds notes from a CSV/TSV file fed line by line to a deck.
+///
+/// The first row is a header if it names the front or back column, otherwise the first column
+/// is the front, the second is the back and the rest are extra fields.
+/// Rows whose first field already exists in the deck are counted as duplicates.
+pub struct CsvImporter {
+    parser: CsvParser,
+    deck: Deck,
+    columns: Option<ColumnMapping>,
+    known_fronts: HashSet<String>,
+    report: ImportReport,
+    line_number: usize,
+    now: DateTime<Utc>,
+}
+
+impl CsvImporter {
+    pub fn new(file_name: &str, deck: Deck, now: DateTime<Utc>) -> Result<Self> {
+        let parser = CsvParser::for_file_name(file_name)
+            .ok_or_else(|| anyhow!("{} is not a .csv or .tsv file", file_name))?;
+        let known_fronts = deck
+            .notes()
+            .iter()
+            .map(|n| duplicate_key(n.sort_field()))
+            .collect();
+        let report = ImportReport {
+            file_name: file_name.to_string(),
+            deck_name: deck.info.name.clone(),
+            ..Default::default()
+        };
+
+        Ok(Self {
+            parser,
+            deck,
+            columns: None,
+            known_fronts,
+            report,
+            line_number: 0,
+            now,
+        })
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        self.line_number += 1;
+        // spreadsheet apps save UTF-8 with a byte order mark, it would hide a header
+        let line = match self.line_number {
+            1 => line.strip_prefix('\u{feff}').unwrap_or(line),
+            _ => line,
+        };
+        match self.parser.push_line(line) {
+            Ok(Some(record)) => self.push_record(record),
+            Ok(None) => {}
+            Err(e) => self.error(e.to_string()),
+        }
+    }
+
+    pub fn finish(mut self) -> (Deck, ImportReport) {
+        if self.parser.is_pending() {
+            self.error("unterminated quoted field at end of file".to_string());
+        }
+        (self.deck, self.report)
+    }
+
+    fn push_record(&mut self, record: Vec<String>) {
+        if record.iter().all(|f| f.trim().is_empty()) {
+            return;
+        }
+
+        let columns = match &self.columns {
+            Some(columns) => columns,
+            None => {
+                // the first row is either a header or already a note
+                if let Some(header) = ColumnMapping::from_header(&record) {
+                    self.columns = Some(header);
+                    return;
+                }
+                self.columns
+                    .insert(ColumnMapping::with_extra(0, 1, record.len()))
+            }
+        };
+
+        let fields = columns.fields(&record);
+        let rank = columns.rank(&record);
+        let tags = columns.tags(&record);
+        // a cloze sentence is a card on its own, the second column is optional for it
+        if fields[0].is_empty() || (fields[1].is_empty() && !cloze::has_deletions(&fields[0])) {
+            self.report.skipped += 1;
+            return;
+        }
+
+        if !self.known_fronts.insert(duplicate_key(&fields[0])) {
+            self.report.duplicates += 1;
+            return;
+        }
+
+        let note_id = self.deck.add_note(fields, self.now);
+        if let Some(note) = self.deck.note_mut(note_id) {
+            note.rank = rank;
+            for tag in &tags {
+                note.add_tag(tag);
+            }
+        }
+        self.report.imported += 1;
+    }
+
+    fn error(&mut self, message: String) {
+        self.report.error_count += 1;
+        if self.report.errors.len() < MAX_REPORTED_ERRORS {
+            self.report
+                .errors
+                .push(format!("line {}: {}", self.line_number, message));
+        }
+    }
+}
+
+fn duplicate_key(front: &str) -> String {
+    front.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::logic::deck::model::DeckInfo;
+
+    fn import(file_name: &str, deck: Deck, contents: &str) -> (Deck, ImportReport) {
+        let now = Utc.with_ymd_and_hms(2024, 2, 1, 12, 0, 0).unwrap();
+        let mut importer = CsvImporter::new(file_name, deck, now).unwrap();
+        for line in contents.lines() {
+            importer.push_line(line);
+        }
+        importer.finish()
+    }
+
+    fn empty_deck() -> Deck {
+        Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        })
+    }
+
+    fn fields(deck: &Deck) -> Vec<Vec<&str>> {
+        deck.notes()
+            .iter()
+            .map(|n| n.fields.iter().map(|f| f.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn header_names_the_columns() {
+        let (deck, report) = import(
+            "words.csv",
+            empty_deck(),
+            "Example,Перевод,Слово\n\"Я дома, наконец\",home,дом\n",
+        );
+        assert_eq!(fields(&deck), [["дом", "home", "Я дома, наконец"]]);
+        assert_eq!(report.imported, 1);
+
+        // a named back column, the front is the first other one
+        let (deck, _) = import("words.tsv", empty_deck(), "note\tanswer\tq\nx\tcat\tкот\n");
+        assert_eq!(fields(&deck), [["x", "cat", "кот"]]);
+    }
+
+    #[test]
+    fn byte_order_mark_is_not_part_of_the_header() {
+        let (deck, report) = import(
+            "words.csv",
+            empty_deck(),
+            "\u{feff}Word,Example\nкот,a cat\n",
+        );
+        assert_eq!(fields(&deck), [["кот", "a cat"]]);
+        assert_eq!(report.imported, 1);
+
+        let (deck, _) = import("words.csv", empty_deck(), "\u{feff}кот,cat\n");
+        assert_eq!(fields(&deck), [["кот", "cat"]]);
+    }
+
+    /// The first row tells how many columns there are, empty extra fields at the end are dropped.
+    #[test]
+    fn without_header_the_first_columns_are_front_and_back() {
+        let (deck, report) = import(
+            "words.csv",
+            empty_deck(),
+            "кот,cat,\nсобака,dog,\"a \"\"good\"\" boy\"\n\n",
+        );
+        assert_eq!(
+            fields(&deck),
+            [vec!["кот", "cat"], vec!["собака", "dog", "a \"good\" boy"]]
+        );
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.deck_name, "Words");
+    }
+
+    #[test]
+    fn duplicates_and_incomplete_rows_are_counted() {
+        let (deck, _) = import("old.csv", empty_deck(), "кот,cat\n");
+        let (deck, report) = import(
+            "new.csv",
+            deck,
+            "front,back\n Кот ,cat again\nпёс,dog\nПЁС,hound\nлиса,\n,fox\n",
+        );
+        assert_eq!(fields(&deck), [["кот", "cat"], ["пёс", "dog"]]);
+        assert_eq!(
+            (report.imported, report.duplicates, report.skipped),
+            (1, 2, 2)
+        );
+        assert_eq!(report.summary(), "+1 dup 2 skip 2 err 0");
+    }
+
+    #[test]
+    fn unterminated_quote_is_reported_with_its_line() {
+        let (deck, report) = import("words.csv", empty_deck(), "кот,cat\nпёс,\"dog\n");
+        assert_eq!(deck.notes().len(), 1);
+        assert_eq!(report.error_count, 1);
+        assert_eq!(
+            report.errors,
+            ["line 2: unterminated quoted field at end of file"]
+        );
+        assert!(CsvImporter::new("words.xls", empty_deck(), Utc::now()).is_err());
+    }
+}
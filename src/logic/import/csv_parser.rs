@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Result};
+
+/// A quoted field longer than this is most likely a missing closing quote.
+const MAX_RECORD_LINES: usize = 32;
+
+/// Parses CSV/TSV records from lines fed one by one.
+///
+/// Fields may be quoted with `"`, a quoted field may contain delimiters, line breaks
+/// and `""` for a quote character.
+pub struct CsvParser {
+    delimiter: char,
+    record: Vec<String>,
+    field: String,
+    in_quotes: bool,
+    record_lines: usize,
+}
+
+impl CsvParser {
+    pub fn new(delimiter: char) -> Self {
+        Self {
+            delimiter,
+            record: Vec::new(),
+            field: String::new(),
+            in_quotes: false,
+            record_lines: 0,
+        }
+    }
+
+    /// Picks the delimiter by extension: `.csv` is comma separated, `.tsv` is tab separated.
+    pub fn for_file_name(file_name: &str) -> Option<Self> {
+        let extension = file_name.rsplit_once('.')?.1.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(Self::new(',')),
+            "tsv" | "tab" => Some(Self::new('\t')),
+            _ => None,
+        }
+    }
+
+    /// Feeds a line without its line break.
+    /// Returns the record once it is complete, `Ok(None)` while a quoted field continues on the next line.
+    pub fn push_line(&mut self, line: &str) -> Result<Option<Vec<String>>> {
+        self.record_lines += 1;
+        if self.record_lines > MAX_RECORD_LINES {
+            self.reset();
+            return Err(anyhow!("unterminated quoted field"));
+        }
+
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if self.in_quotes {
+                if c == '"' {
+                    if chars.peek() == Some(&'"') {
+                        chars.next();
+                        self.field.push('"');
+                    } else {
+                        self.in_quotes = false;
+                    }
+                } else {
+                    self.field.push(c);
+                }
+            } else if c == self.delimiter {
+                self.record.push(core::mem::take(&mut self.field));
+            } else if c == '"' && self.field.is_empty() {
+                self.in_quotes = true;
+            } else {
+                self.field.push(c);
+            }
+        }
+
+        if self.in_quotes {
+            self.field.push('\n');
+            return Ok(None);
+        }
+
+        self.record.push(core::mem::take(&mut self.field));
+        self.record_lines = 0;
+        Ok(Some(core::mem::take(&mut self.record)))
+    }
+
+    /// True if the last pushed line left a quoted field open.
+    pub fn is_pending(&self) -> bool {
+        self.in_quotes
+    }
+
+    fn reset(&mut self) {
+        self.record.clear();
+        self.field.clear();
+        self.in_quotes = false;
+        self.record_lines = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(fields: &[&str]) -> Option<Vec<String>> {
+        Some(fields.iter().map(|f| f.to_string()).collect())
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_and_quotes() {
+        let mut parser = CsvParser::new(',');
+        assert_eq!(
+            parser
+                .push_line(r#"дом,"house, home","say ""hi""""#)
+                .unwrap(),
+            record(&["дом", "house, home", "say \"hi\""])
+        );
+        assert_eq!(parser.push_line("a,,").unwrap(), record(&["a", "", ""]));
+        // a quote inside an unquoted field is just a character
+        assert_eq!(
+            parser.push_line(r#"5" disk,x"#).unwrap(),
+            record(&["5\" disk", "x"])
+        );
+
+        let mut parser = CsvParser::new('\t');
+        assert_eq!(
+            parser.push_line("a, b\t\"c\td\"").unwrap(),
+            record(&["a, b", "c\td"])
+        );
+    }
+
+    #[test]
+    fn quoted_field_goes_over_lines() {
+        let mut parser = CsvParser::new(',');
+        assert_eq!(parser.push_line(r#"word,"first line"#).unwrap(), None);
+        assert!(parser.is_pending());
+        assert_eq!(parser.push_line("").unwrap(), None);
+        assert_eq!(
+            parser.push_line(r#"last line",x"#).unwrap(),
+            record(&["word", "first line\n\nlast line", "x"])
+        );
+        assert!(!parser.is_pending());
+    }
+
+    #[test]
+    fn unterminated_quote_is_an_error() {
+        let mut parser = CsvParser::new(',');
+        parser.push_line("a,\"open").unwrap();
+        for _ in 1..MAX_RECORD_LINES {
+            assert_eq!(parser.push_line("more").unwrap(), None);
+        }
+        assert!(parser.push_line("more").is_err());
+        // the parser starts over with the next line
+        assert_eq!(parser.push_line("b,c").unwrap(), record(&["b", "c"]));
+    }
+
+    #[test]
+    fn delimiter_by_extension() {
+        let delimiter = |name| CsvParser::for_file_name(name).map(|p| p.delimiter);
+        assert_eq!(delimiter("words.csv"), Some(','));
+        assert_eq!(delimiter("WORDS.TSV"), Some('\t'));
+        assert_eq!(delimiter("words.tab"), Some('\t'));
+        assert_eq!(delimiter("words.txt"), None);
+        assert_eq!(delimiter("csv"), None);
+    }
+}
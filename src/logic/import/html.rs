@@ -0,0 +1,156 @@
+/// Turns an Anki field into plain text: tags and the contents of `<style>`/`<script>` are dropped,
+/// line breaks become `\n`, entities are decoded and `[sound:...]` references are removed.
+/// A `<` not followed by a letter, `/` or `!` is text, as in `a < b`.
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '<' if is_tag_start(&rest[1..]) => match rest.find('>') {
+                Some(end) => {
+                    let tag = &rest[1..end];
+                    if is_line_break(tag) {
+                        text.push('\n');
+                    }
+                    rest = &rest[end + 1..];
+                    if let Some(name) = raw_text_element(tag) {
+                        rest = skip_raw_text(rest, name);
+                    }
+                }
+                None => {
+                    text.push_str(rest);
+                    break;
+                }
+            },
+            '&' => {
+                let entity = rest[1..]
+                    .find(';')
+                    .filter(|end| *end <= 8)
+                    .and_then(|end| decode_entity(&rest[1..end + 1]).map(|c| (c, end + 2)));
+                match entity {
+                    Some((decoded, length)) => {
+                        text.push(decoded);
+                        rest = &rest[length..];
+                    }
+                    None => {
+                        text.push('&');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            '[' if rest.starts_with("[sound:") => match rest.find(']') {
+                Some(end) => rest = &rest[end + 1..],
+                None => break,
+            },
+            c => {
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect();
+    lines.join("\n")
+}
+
+fn is_tag_start(after: &str) -> bool {
+    after
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!')
+}
+
+/// Lowercase name of the tag between `<` and `>`, without the `/` of a closing tag.
+fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+/// `<br>`, `<div>` and `<p>` start a new line.
+fn is_line_break(tag: &str) -> bool {
+    matches!(tag_name(tag).as_str(), "br" | "div" | "p" | "li")
+}
+
+/// `<style>` and `<script>` hold CSS and code rather than text.
+fn raw_text_element(tag: &str) -> Option<&'static str> {
+    if tag.starts_with('/') {
+        return None;
+    }
+    match tag_name(tag).as_str() {
+        "style" => Some("style"),
+        "script" => Some("script"),
+        _ => None,
+    }
+}
+
+/// The rest of the field after the closing tag of `name`, nothing if it's never closed.
+fn skip_raw_text<'a>(rest: &'a str, name: &str) -> &'a str {
+    let closing = format!("</{}", name);
+    // ASCII lowercasing keeps byte offsets
+    rest.to_ascii_lowercase()
+        .find(&closing)
+        .and_then(|start| rest[start..].find('>').map(|end| &rest[start + end + 1..]))
+        .unwrap_or("")
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "nbsp" => Some(' '),
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_dropped_and_breaks_kept() {
+        assert_eq!(
+            strip_html("<b>кошка</b><br>cat<div>&nbsp;a&amp;b</div>"),
+            "кошка\ncat\na&b"
+        );
+        assert_eq!(strip_html("word [sound:word.mp3]"), "word");
+        assert_eq!(strip_html("<!-- note -->text"), "text");
+    }
+
+    #[test]
+    fn style_and_script_contents_are_dropped() {
+        assert_eq!(
+            strip_html("<style>.card { color: red; }</style>кошка"),
+            "кошка"
+        );
+        assert_eq!(
+            strip_html("cat<SCRIPT type=\"text/javascript\">if (a > b) {}</Script> dog"),
+            "cat dog"
+        );
+        assert_eq!(strip_html("cat<script>never closed"), "cat");
+    }
+
+    #[test]
+    fn less_than_sign_is_text() {
+        assert_eq!(strip_html("a < b"), "a < b");
+        assert_eq!(strip_html("x<3 and y>2"), "x<3 and y>2");
+        assert_eq!(strip_html("1 <= 2 <i>ok</i>"), "1 <= 2 ok");
+        assert_eq!(strip_html("a <"), "a <");
+    }
+}
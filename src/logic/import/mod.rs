@@ -0,0 +1,4 @@
+pub mod anki;
+pub mod csv_import;
+pub mod csv_parser;
+pub mod html;
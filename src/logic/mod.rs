@@ -1,3 +1,11 @@
 pub mod view_manager;
 pub mod view;
-pub mod views;
\ No newline at end of file
+pub mod views;
+pub mod deck;
+pub mod import;
+pub mod optimizer;
+pub mod review;
+pub mod search;
+pub mod settings;
+pub mod stats;
+pub mod study_day;
\ No newline at end of file
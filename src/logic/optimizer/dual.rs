@@ -0,0 +1,242 @@
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Number of FSRS weights, every dual number carries the derivative by each of them.
+pub const PARAMS: usize = 19;
+
+/// Forward mode automatic differentiation: a value together with its gradient by the weights.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub grad: [f64; PARAMS],
+}
+
+impl Dual {
+    pub fn constant(value: f64) -> Self {
+        Self {
+            value,
+            grad: [0.0; PARAMS],
+        }
+    }
+
+    /// The weight `index` itself, its derivative by itself is 1.
+    pub fn variable(value: f64, index: usize) -> Self {
+        let mut grad = [0.0; PARAMS];
+        grad[index] = 1.0;
+        Self { value, grad }
+    }
+
+    fn chain(self, value: f64, derivative: f64) -> Self {
+        Self {
+            value,
+            grad: self.grad.map(|g| g * derivative),
+        }
+    }
+
+    pub fn exp(self) -> Self {
+        let value = self.value.exp();
+        self.chain(value, value)
+    }
+
+    pub fn ln(self) -> Self {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    pub fn powf(self, exponent: f64) -> Self {
+        let value = self.value.powf(exponent);
+        self.chain(value, exponent * self.value.powf(exponent - 1.0))
+    }
+
+    /// `self ^ exponent` where both depend on the weights, `self` must be positive.
+    pub fn pow(self, exponent: Dual) -> Self {
+        (exponent * self.ln()).exp()
+    }
+
+    /// Outside of the range the value is constant, so is its gradient.
+    pub fn clamp(self, min: f64, max: f64) -> Self {
+        if self.value < min {
+            Self::constant(min)
+        } else if self.value > max {
+            Self::constant(max)
+        } else {
+            self
+        }
+    }
+
+    pub fn min(self, other: Self) -> Self {
+        if self.value <= other.value {
+            self
+        } else {
+            other
+        }
+    }
+
+    pub fn max(self, other: Self) -> Self {
+        if self.value >= other.value {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl From<f64> for Dual {
+    fn from(value: f64) -> Self {
+        Self::constant(value)
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+
+    fn add(self, rhs: Dual) -> Dual {
+        let mut grad = self.grad;
+        for (g, r) in grad.iter_mut().zip(rhs.grad) {
+            *g += r;
+        }
+        Dual {
+            value: self.value + rhs.value,
+            grad,
+        }
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+
+    fn sub(self, rhs: Dual) -> Dual {
+        self + -rhs
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+
+    fn neg(self) -> Dual {
+        self.chain(-self.value, -1.0)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+
+    fn mul(self, rhs: Dual) -> Dual {
+        let mut grad = [0.0; PARAMS];
+        for (i, g) in grad.iter_mut().enumerate() {
+            *g = self.grad[i] * rhs.value + rhs.grad[i] * self.value;
+        }
+        Dual {
+            value: self.value * rhs.value,
+            grad,
+        }
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+
+    fn div(self, rhs: Dual) -> Dual {
+        let mut grad = [0.0; PARAMS];
+        let denominator = rhs.value * rhs.value;
+        for (i, g) in grad.iter_mut().enumerate() {
+            *g = (self.grad[i] * rhs.value - rhs.grad[i] * self.value) / denominator;
+        }
+        Dual {
+            value: self.value / rhs.value,
+            grad,
+        }
+    }
+}
+
+impl Add<f64> for Dual {
+    type Output = Dual;
+
+    fn add(self, rhs: f64) -> Dual {
+        Dual {
+            value: self.value + rhs,
+            grad: self.grad,
+        }
+    }
+}
+
+impl Sub<f64> for Dual {
+    type Output = Dual;
+
+    fn sub(self, rhs: f64) -> Dual {
+        self + -rhs
+    }
+}
+
+impl Mul<f64> for Dual {
+    type Output = Dual;
+
+    fn mul(self, rhs: f64) -> Dual {
+        self.chain(self.value * rhs, rhs)
+    }
+}
+
+impl Div<f64> for Dual {
+    type Output = Dual;
+
+    fn div(self, rhs: f64) -> Dual {
+        self * (1.0 / rhs)
+    }
+}
+
+impl Sub<Dual> for f64 {
+    type Output = Dual;
+
+    fn sub(self, rhs: Dual) -> Dual {
+        -rhs + self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Uses every operation on three weights.
+    fn function(x: Dual, y: Dual, z: Dual) -> Dual {
+        let a = (x * y + x.exp() / y).ln();
+        let b = (2.0 - z).powf(1.5) * 3.0 - x / 4.0 + 1.0;
+        let c = y.pow(z) - (-x).max(z).min(y.clamp(0.0, 10.0));
+        a * b + c
+    }
+
+    #[test]
+    fn gradient_matches_finite_differences() {
+        let point = [0.7, 1.3, 0.4];
+        let value = function(
+            Dual::variable(point[0], 0),
+            Dual::variable(point[1], 1),
+            Dual::variable(point[2], 2),
+        );
+        let at =
+            |point: [f64; 3]| function(point[0].into(), point[1].into(), point[2].into()).value;
+        assert_eq!(value.value, at(point));
+
+        let step = 1e-6;
+        for i in 0..3 {
+            let mut above = point;
+            above[i] += step;
+            let mut below = point;
+            below[i] -= step;
+            let numeric = (at(above) - at(below)) / (2.0 * step);
+            assert!(
+                (value.grad[i] - numeric).abs() < 1e-6,
+                "d/dw{}: dual {} numeric {}",
+                i,
+                value.grad[i],
+                numeric
+            );
+        }
+        assert!(value.grad[3..].iter().all(|g| *g == 0.0));
+    }
+
+    #[test]
+    fn clamped_value_is_constant() {
+        let x = Dual::variable(12.0, 0);
+        assert_eq!(x.clamp(1.0, 10.0), Dual::constant(10.0));
+        assert_eq!(x.clamp(1.0, 20.0), x);
+        assert_eq!((x * 2.0).grad[0], 2.0);
+    }
+}
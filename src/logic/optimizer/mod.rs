@@ -0,0 +1,3 @@
+pub mod dual;
+pub mod model;
+pub mod trainer;
\ No newline at end of file
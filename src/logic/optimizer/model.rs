@@ -0,0 +1,111 @@
+//! FSRS memory model over dual numbers, the same formulas `rs_fsrs::Parameters` schedules with.
+
+use rs_fsrs::State;
+
+use crate::logic::optimizer::dual::{Dual, PARAMS};
+
+const DECAY: f64 = -0.5;
+/// (9/10) ^ (1 / DECAY) - 1
+const FACTOR: f64 = 19.0 / 81.0;
+
+/// One grade of a card as the model sees it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelReview {
+    /// 1 Again .. 4 Easy.
+    pub rating: u8,
+    pub elapsed_days: f64,
+    pub state_before: State,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MemoryState {
+    stability: Dual,
+    difficulty: Dual,
+}
+
+pub struct Model {
+    w: [Dual; PARAMS],
+}
+
+impl Model {
+    pub fn new(weights: &[f64; PARAMS]) -> Self {
+        Self {
+            w: core::array::from_fn(|i| Dual::variable(weights[i], i)),
+        }
+    }
+
+    /// Replays the history of one card and calls `on_prediction(recall probability, recalled)`
+    /// for every review of a learned card made at least a day after the previous one.
+    pub fn predict(&self, reviews: &[ModelReview], mut on_prediction: impl FnMut(Dual, bool)) {
+        let mut memory: Option<MemoryState> = None;
+        for review in reviews {
+            memory = Some(match (memory, review.state_before) {
+                (None, _) | (_, State::New) => self.init_memory(review.rating),
+                (Some(memory), State::Learning | State::Relearning) => MemoryState {
+                    stability: self.short_term_stability(memory.stability, review.rating),
+                    difficulty: self.next_difficulty(memory.difficulty, review.rating),
+                },
+                (Some(memory), State::Review) => {
+                    let retrievability = forgetting_curve(review.elapsed_days, memory.stability);
+                    if review.elapsed_days >= 1.0 {
+                        on_prediction(retrievability, review.rating > 1);
+                    }
+                    let stability = if review.rating == 1 {
+                        self.forget_stability(memory, retrievability)
+                    } else {
+                        self.recall_stability(memory, retrievability, review.rating)
+                    };
+                    MemoryState {
+                        stability,
+                        difficulty: self.next_difficulty(memory.difficulty, review.rating),
+                    }
+                }
+            });
+        }
+    }
+
+    fn init_memory(&self, rating: u8) -> MemoryState {
+        MemoryState {
+            stability: self.w[rating as usize - 1].max(Dual::constant(0.1)),
+            difficulty: self.init_difficulty(rating),
+        }
+    }
+
+    fn init_difficulty(&self, rating: u8) -> Dual {
+        (self.w[4] - (self.w[5] * (rating as f64 - 1.0)).exp() + 1.0).clamp(1.0, 10.0)
+    }
+
+    fn next_difficulty(&self, difficulty: Dual, rating: u8) -> Dual {
+        let next = difficulty - self.w[6] * (rating as f64 - 3.0);
+        let mean_reversion = self.w[7] * self.init_difficulty(4) + (1.0 - self.w[7]) * next;
+        mean_reversion.clamp(1.0, 10.0)
+    }
+
+    fn short_term_stability(&self, stability: Dual, rating: u8) -> Dual {
+        stability * (self.w[17] * (self.w[18] + (rating as f64 - 3.0))).exp()
+    }
+
+    fn recall_stability(&self, memory: MemoryState, retrievability: Dual, rating: u8) -> Dual {
+        let modifier = match rating {
+            2 => self.w[15],
+            4 => self.w[16],
+            _ => Dual::constant(1.0),
+        };
+        let growth = self.w[8].exp()
+            * (11.0 - memory.difficulty)
+            * memory.stability.pow(-self.w[9])
+            * (((1.0 - retrievability) * self.w[10]).exp() - 1.0);
+        memory.stability * (growth * modifier + 1.0)
+    }
+
+    fn forget_stability(&self, memory: MemoryState, retrievability: Dual) -> Dual {
+        self.w[11]
+            * memory.difficulty.pow(-self.w[12])
+            * ((memory.stability + 1.0).pow(self.w[13]) - 1.0)
+            * ((1.0 - retrievability) * self.w[14]).exp()
+    }
+}
+
+fn forgetting_curve(elapsed_days: f64, stability: Dual) -> Dual {
+    (Dual::constant(FACTOR * elapsed_days) / stability + 1.0).powf(DECAY)
+}
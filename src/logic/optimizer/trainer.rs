@@ -0,0 +1,438 @@
+//! Fits FSRS weights to the review log with Adam over the log-loss of recall predictions,
+//! the way the reference FSRS optimizer does.
+
+use std::collections::BTreeMap;
+
+use rs_fsrs::{Rating, State};
+
+use crate::logic::{
+    deck::card::CardId,
+    optimizer::{
+        dual::{Dual, PARAMS},
+        model::{Model, ModelReview},
+    },
+    review::review_log::ReviewLogEntry,
+};
+
+/// Allowed ranges of the weights, the same as the reference optimizer clips to.
+const BOUNDS: [(f64, f64); PARAMS] = [
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (1.0, 10.0),
+    (0.001, 4.0),
+    (0.001, 4.0),
+    (0.001, 0.75),
+    (0.0, 4.5),
+    (0.0, 0.8),
+    (0.001, 3.5),
+    (0.001, 5.0),
+    (0.001, 0.25),
+    (0.001, 0.9),
+    (0.0, 4.0),
+    (0.0, 1.0),
+    (1.0, 6.0),
+    (0.0, 2.0),
+    (0.0, 2.0),
+];
+
+/// Predictions are kept away from 0 and 1 so a single surprise doesn't dominate the loss.
+const PROBABILITY_EPSILON: f64 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrainerConfig {
+    pub epochs: usize,
+    pub learning_rate: f64,
+    /// Reviews per Adam step, cards are never split between batches.
+    pub batch_size: usize,
+    /// Pull towards the initial weights, keeps small logs from overfitting.
+    pub regularization: f64,
+}
+
+impl Default for TrainerConfig {
+    fn default() -> Self {
+        Self {
+            epochs: 5,
+            learning_rate: 4e-2,
+            batch_size: 512,
+            regularization: 1e-3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Metrics {
+    pub log_loss: f64,
+    pub rmse: f64,
+    /// Reviews the metrics are computed over.
+    pub reviews: usize,
+}
+
+/// Review histories of cards, ready for training.
+#[derive(Debug, Clone, Default)]
+pub struct TrainingSet {
+    histories: Vec<Vec<ModelReview>>,
+}
+
+impl TrainingSet {
+    /// Groups the log by card. Histories that don't start from a new card (e.g. cards rebuilt
+    /// from another app) are dropped, the model can't know their memory state.
+    pub fn from_log<'a>(entries: impl IntoIterator<Item = &'a ReviewLogEntry>) -> Self {
+        let mut by_card: BTreeMap<CardId, Vec<&ReviewLogEntry>> = BTreeMap::new();
+        for entry in entries {
+            by_card.entry(entry.card_id).or_default().push(entry);
+        }
+
+        let histories = by_card
+            .into_values()
+            .filter_map(|mut entries| {
+                entries.sort_by_key(|e| e.time);
+                if entries.first()?.state_before != State::New {
+                    return None;
+                }
+                let history: Vec<ModelReview> = entries
+                    .iter()
+                    .map(|e| ModelReview {
+                        rating: rating_number(e.rating),
+                        elapsed_days: e.elapsed_days as f64,
+                        state_before: e.state_before,
+                    })
+                    .collect();
+                (history.len() > 1).then_some(history)
+            })
+            .collect();
+        Self { histories }
+    }
+
+    pub fn card_count(&self) -> usize {
+        self.histories.len()
+    }
+
+    pub fn evaluate(&self, weights: &[f64; PARAMS]) -> Metrics {
+        let model = Model::new(weights);
+        let mut loss = 0.0;
+        let mut squared_error = 0.0;
+        let mut reviews = 0;
+        for history in &self.histories {
+            model.predict(history, |probability, recalled| {
+                let p = probability
+                    .value
+                    .clamp(PROBABILITY_EPSILON, 1.0 - PROBABILITY_EPSILON);
+                let y = if recalled { 1.0 } else { 0.0 };
+                loss -= y * p.ln() + (1.0 - y) * (1.0 - p).ln();
+                squared_error += (p - y) * (p - y);
+                reviews += 1;
+            });
+        }
+        if reviews == 0 {
+            return Metrics::default();
+        }
+        Metrics {
+            log_loss: loss / reviews as f64,
+            rmse: (squared_error / reviews as f64).sqrt(),
+            reviews,
+        }
+    }
+}
+
+/// Adam over mini batches of card histories, one `run_epoch` call per pass over the set.
+pub struct Trainer {
+    config: TrainerConfig,
+    initial: [f64; PARAMS],
+    weights: [f64; PARAMS],
+    moment: [f64; PARAMS],
+    velocity: [f64; PARAMS],
+    step: i32,
+    epoch: usize,
+    shuffle_state: u64,
+}
+
+impl Trainer {
+    const BETA1: f64 = 0.9;
+    const BETA2: f64 = 0.999;
+    const EPSILON: f64 = 1e-8;
+
+    pub fn new(initial: [f64; PARAMS], config: TrainerConfig) -> Self {
+        Self {
+            config,
+            initial,
+            weights: clip(initial),
+            moment: [0.0; PARAMS],
+            velocity: [0.0; PARAMS],
+            step: 0,
+            epoch: 0,
+            shuffle_state: 0x2545_F491_4F6C_DD1D,
+        }
+    }
+
+    pub fn weights(&self) -> &[f64; PARAMS] {
+        &self.weights
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.epoch >= self.config.epochs
+    }
+
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    pub fn run_epoch(&mut self, set: &TrainingSet) {
+        let mut order: Vec<usize> = (0..set.histories.len()).collect();
+        self.shuffle(&mut order);
+
+        let mut batch: Vec<&[ModelReview]> = Vec::new();
+        let mut batch_reviews = 0;
+        for index in order {
+            let history = &set.histories[index];
+            batch.push(history);
+            batch_reviews += history.len();
+            if batch_reviews >= self.config.batch_size {
+                self.train_batch(&batch);
+                batch.clear();
+                batch_reviews = 0;
+            }
+        }
+        if !batch.is_empty() {
+            self.train_batch(&batch);
+        }
+        self.epoch += 1;
+    }
+
+    /// Runs the remaining epochs, `on_epoch` gets the number of finished ones.
+    pub fn run(&mut self, set: &TrainingSet, mut on_epoch: impl FnMut(usize)) -> [f64; PARAMS] {
+        while !self.is_finished() {
+            self.run_epoch(set);
+            on_epoch(self.epoch);
+        }
+        self.weights
+    }
+
+    fn train_batch(&mut self, batch: &[&[ModelReview]]) {
+        let Some(loss) = mean_loss(&Model::new(&self.weights), batch) else {
+            return;
+        };
+
+        let mut gradient = loss.grad;
+        for (i, g) in gradient.iter_mut().enumerate() {
+            let scale = self.initial[i].abs().max(0.1);
+            *g += 2.0 * self.config.regularization * (self.weights[i] - self.initial[i])
+                / (scale * scale);
+        }
+        self.adam_step(&gradient);
+    }
+
+    fn adam_step(&mut self, gradient: &[f64; PARAMS]) {
+        self.step += 1;
+        let moment_correction = 1.0 - Self::BETA1.powi(self.step);
+        let velocity_correction = 1.0 - Self::BETA2.powi(self.step);
+        for (i, g) in gradient.iter().enumerate() {
+            if !g.is_finite() {
+                continue;
+            }
+            self.moment[i] = Self::BETA1 * self.moment[i] + (1.0 - Self::BETA1) * g;
+            self.velocity[i] = Self::BETA2 * self.velocity[i] + (1.0 - Self::BETA2) * g * g;
+            let moment = self.moment[i] / moment_correction;
+            let velocity = self.velocity[i] / velocity_correction;
+            self.weights[i] -=
+                self.config.learning_rate * moment / (velocity.sqrt() + Self::EPSILON);
+        }
+        self.weights = clip(self.weights);
+    }
+
+    /// Fisher-Yates with xorshift, the order only needs to differ between epochs.
+    fn shuffle(&mut self, order: &mut [usize]) {
+        for i in (1..order.len()).rev() {
+            self.shuffle_state ^= self.shuffle_state << 13;
+            self.shuffle_state ^= self.shuffle_state >> 7;
+            self.shuffle_state ^= self.shuffle_state << 17;
+            order.swap(i, (self.shuffle_state % (i as u64 + 1)) as usize);
+        }
+    }
+}
+
+/// Log-loss of the predictions over the histories with its gradient by the weights,
+/// `None` if there is nothing to predict.
+fn mean_loss(model: &Model, histories: &[&[ModelReview]]) -> Option<Dual> {
+    let mut loss = Dual::constant(0.0);
+    let mut reviews = 0;
+    for history in histories {
+        model.predict(history, |probability, recalled| {
+            let p = probability.clamp(PROBABILITY_EPSILON, 1.0 - PROBABILITY_EPSILON);
+            loss = loss - if recalled { p.ln() } else { (1.0 - p).ln() };
+            reviews += 1;
+        });
+    }
+    (reviews > 0).then(|| loss / reviews as f64)
+}
+
+fn clip(weights: [f64; PARAMS]) -> [f64; PARAMS] {
+    core::array::from_fn(|i| weights[i].clamp(BOUNDS[i].0, BOUNDS[i].1))
+}
+
+fn rating_number(rating: Rating) -> u8 {
+    match rating {
+        Rating::Again => 1,
+        Rating::Hard => 2,
+        Rating::Good => 3,
+        Rating::Easy => 4,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizeReport {
+    pub cards: usize,
+    pub before: Metrics,
+    pub after: Metrics,
+    pub weights: [f64; PARAMS],
+}
+
+impl OptimizeReport {
+    /// Fitted weights are only worth keeping if they predict the log better.
+    pub fn is_improved(&self) -> bool {
+        self.after.reviews > 0 && self.after.log_loss < self.before.log_loss
+    }
+}
+
+/// Fits the weights starting from `initial`, `on_epoch` gets the number of finished epochs.
+pub fn optimize(
+    set: &TrainingSet,
+    initial: [f64; PARAMS],
+    config: TrainerConfig,
+    on_epoch: impl FnMut(usize),
+) -> OptimizeReport {
+    let before = set.evaluate(&initial);
+    let weights = Trainer::new(initial, config).run(set, on_epoch);
+    OptimizeReport {
+        cards: set.card_count(),
+        before,
+        after: set.evaluate(&weights),
+        weights,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use super::*;
+
+    /// Every card is learned, checked again the same day and then reviewed twice. The grades
+    /// of the first three steps go through all the buttons, so every weight plays a part.
+    fn review_log(cards: u64) -> Vec<ReviewLogEntry> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        let ratings = [Rating::Again, Rating::Hard, Rating::Good, Rating::Easy];
+        let mut entries = Vec::new();
+        for card_id in 1..=cards {
+            let i = card_id as usize;
+            let history = [
+                (0, ratings[i / 4 % 4], State::New),
+                (0, ratings[2 + i % 2], State::Learning),
+                (20, ratings[i % 4], State::Review),
+                (30, Rating::Good, State::Review),
+            ];
+            let mut time = start;
+            for (elapsed_days, rating, state_before) in history {
+                time += Duration::days(elapsed_days) + Duration::minutes(10);
+                entries.push(ReviewLogEntry {
+                    deck_id: 1,
+                    card_id,
+                    note_id: card_id,
+                    template: 0,
+                    cloze: None,
+                    time,
+                    rating,
+                    elapsed_days,
+                    state_before,
+                    state_after: State::Review,
+                    duration_ms: 3000,
+                });
+            }
+        }
+        entries
+    }
+
+    fn default_weights() -> [f64; PARAMS] {
+        rs_fsrs::Parameters::default().w
+    }
+
+    #[test]
+    fn histories_start_from_a_new_card() {
+        let mut entries = review_log(3);
+        // a card rebuilt from another app, its first grade is already a review
+        entries.retain(|e| e.card_id != 2 || e.state_before == State::Review);
+        let set = TrainingSet::from_log(&entries);
+        assert_eq!(set.card_count(), 2);
+        assert_eq!(set.evaluate(&default_weights()).reviews, 4);
+        assert_eq!(
+            TrainingSet::from_log(&[]).evaluate(&default_weights()),
+            Metrics::default()
+        );
+    }
+
+    #[test]
+    fn loss_gradient_matches_finite_differences() {
+        let set = TrainingSet::from_log(&review_log(12));
+        let histories: Vec<&[ModelReview]> = set.histories.iter().map(|h| h.as_slice()).collect();
+        let weights = default_weights();
+        let loss = mean_loss(&Model::new(&weights), &histories).unwrap();
+        assert!((loss.value - set.evaluate(&weights).log_loss).abs() < 1e-12);
+
+        let step = 1e-6;
+        for i in 0..PARAMS {
+            let mut above = weights;
+            above[i] += step;
+            let mut below = weights;
+            below[i] -= step;
+            let numeric =
+                (set.evaluate(&above).log_loss - set.evaluate(&below).log_loss) / (2.0 * step);
+            assert!(
+                (loss.grad[i] - numeric).abs() <= 1e-5 * numeric.abs().max(1.0),
+                "w[{}]: dual {} numeric {}",
+                i,
+                loss.grad[i],
+                numeric
+            );
+        }
+    }
+
+    #[test]
+    fn training_lowers_log_loss() {
+        let set = TrainingSet::from_log(&review_log(40));
+        let config = TrainerConfig {
+            epochs: 20,
+            ..Default::default()
+        };
+        let mut epochs = Vec::new();
+        let report = optimize(&set, default_weights(), config, |epoch| epochs.push(epoch));
+
+        assert_eq!(epochs, (1..=20).collect::<Vec<_>>());
+        assert_eq!(report.cards, 40);
+        assert_eq!(report.before.reviews, report.after.reviews);
+        assert!(report.is_improved(), "{:?}", report);
+    }
+
+    #[test]
+    fn weights_stay_inside_their_bounds() {
+        let set = TrainingSet::from_log(&review_log(40));
+        let config = TrainerConfig {
+            epochs: 10,
+            learning_rate: 50.0,
+            ..Default::default()
+        };
+        let mut initial = default_weights();
+        // out of range to begin with
+        initial[4] = 20.0;
+        initial[7] = -1.0;
+        let mut trainer = Trainer::new(initial, config);
+        for _ in 0..config.epochs {
+            trainer.run_epoch(&set);
+            for (i, weight) in trainer.weights().iter().enumerate() {
+                let (min, max) = BOUNDS[i];
+                assert!((min..=max).contains(weight), "w[{}] = {}", i, weight);
+            }
+        }
+        assert!(trainer.is_finished());
+    }
+}
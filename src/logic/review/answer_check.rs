@@ -0,0 +1,334 @@
+use rs_fsrs::Rating;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::logic::review::diff::{diff_chars, AnswerDiff};
+
+const COMBINING_ACUTE: char = '\u{0301}';
+const COMBINING_GRAVE: char = '\u{0300}';
+
+/// Rules used to accept a typed answer which is not exactly the expected one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnswerCheckOptions {
+    pub ignore_case: bool,
+    /// Treat ё as е, Russian texts often omit the dots.
+    pub yo_as_ye: bool,
+    pub strip_stress_marks: bool,
+    /// Remove accents from latin letters (é -> e), Cyrillic letters like й are kept.
+    pub strip_diacritics: bool,
+    /// Leading words which may be omitted, e.g. "the" and "to".
+    pub ignored_articles: Vec<String>,
+    /// Any of the alternatives separated by `;` or `/` is a correct answer.
+    pub split_alternatives: bool,
+    /// Levenshtein distance still accepted as a typo.
+    pub max_typos: usize,
+}
+
+impl Default for AnswerCheckOptions {
+    fn default() -> Self {
+        Self {
+            ignore_case: true,
+            yo_as_ye: true,
+            strip_stress_marks: true,
+            strip_diacritics: true,
+            ignored_articles: ["the", "a", "an", "to"]
+                .iter()
+                .map(|a| a.to_string())
+                .collect(),
+            split_alternatives: true,
+            max_typos: 1,
+        }
+    }
+}
+
+/// The rule which made the typed answer match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchRule {
+    Exact,
+    IgnoredCase,
+    IgnoredStressMarks,
+    YoAsYe,
+    IgnoredDiacritics,
+    IgnoredArticle,
+    Typo(usize),
+    Wrong,
+}
+
+impl MatchRule {
+    pub fn description(&self) -> &'static str {
+        match self {
+            MatchRule::Exact => "correct",
+            MatchRule::IgnoredCase => "accepted ignoring case",
+            MatchRule::IgnoredStressMarks => "accepted ignoring stress",
+            MatchRule::YoAsYe => "accepted with е for ё",
+            MatchRule::IgnoredDiacritics => "accepted ignoring accents",
+            MatchRule::IgnoredArticle => "accepted without article",
+            MatchRule::Typo(_) => "accepted with typo",
+            MatchRule::Wrong => "wrong",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnswerCheck {
+    pub rule: MatchRule,
+    /// The alternative the typed answer was compared with.
+    pub expected: String,
+    /// Character diff of the normalized typed and expected answers.
+    pub diff: AnswerDiff,
+}
+
+impl AnswerCheck {
+    pub fn is_accepted(&self) -> bool {
+        self.rule != MatchRule::Wrong
+    }
+
+    /// Rating by the distance left after normalization, so an answer matched by a rule
+    /// suggests Good and typos are judged against the length of the answer.
+    /// A typo accepted by `max_typos` is never worse than Hard, a wrong answer is always Again.
+    pub fn suggested_rating(&self) -> Rating {
+        match (self.rule, self.diff.suggested_rating()) {
+            (MatchRule::Wrong, _) => Rating::Again,
+            (MatchRule::Typo(_), Rating::Again) => Rating::Hard,
+            (_, rating) => rating,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Case,
+    Stress,
+    Yo,
+    Diacritics,
+    Article,
+}
+
+const STEPS: [(Step, MatchRule); 5] = [
+    (Step::Case, MatchRule::IgnoredCase),
+    (Step::Stress, MatchRule::IgnoredStressMarks),
+    (Step::Yo, MatchRule::YoAsYe),
+    (Step::Diacritics, MatchRule::IgnoredDiacritics),
+    (Step::Article, MatchRule::IgnoredArticle),
+];
+
+pub struct AnswerChecker {
+    options: AnswerCheckOptions,
+}
+
+impl AnswerChecker {
+    pub fn new(options: AnswerCheckOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn check(&self, typed: &str, expected: &str) -> AnswerCheck {
+        let alternatives = self.alternatives(expected);
+        let mut best: Option<AnswerCheck> = None;
+        for alternative in alternatives {
+            let check = self.check_alternative(typed, alternative);
+            let is_better = match &best {
+                None => true,
+                Some(b) => rank(&check) < rank(b),
+            };
+            if is_better {
+                best = Some(check);
+            }
+        }
+        // `alternatives` is never empty, it contains at least the whole expected answer
+        best.unwrap()
+    }
+
+    fn alternatives<'a>(&self, expected: &'a str) -> Vec<&'a str> {
+        let mut alternatives = Vec::new();
+        if self.options.split_alternatives {
+            alternatives.extend(
+                expected
+                    .split([';', '/'])
+                    .map(|a| a.trim())
+                    .filter(|a| !a.is_empty()),
+            );
+        }
+        if alternatives.is_empty() {
+            alternatives.push(expected.trim());
+        }
+        alternatives
+    }
+
+    fn check_alternative(&self, typed: &str, expected: &str) -> AnswerCheck {
+        let mut typed = collapse_whitespace(typed);
+        let mut expected_normalized = collapse_whitespace(expected);
+        let result = |rule, typed: &str, expected_normalized: &str| AnswerCheck {
+            rule,
+            expected: expected.to_string(),
+            diff: diff_chars(typed, expected_normalized),
+        };
+
+        if typed == expected_normalized {
+            return result(MatchRule::Exact, &typed, &expected_normalized);
+        }
+
+        for (step, rule) in STEPS {
+            if !self.is_enabled(step) {
+                continue;
+            }
+            typed = self.apply(step, &typed);
+            expected_normalized = self.apply(step, &expected_normalized);
+            if typed == expected_normalized {
+                return result(rule, &typed, &expected_normalized);
+            }
+        }
+
+        let diff = diff_chars(&typed, &expected_normalized);
+        let expected_len = expected_normalized.chars().count();
+        let rule = if diff.distance <= self.options.max_typos && diff.distance < expected_len {
+            MatchRule::Typo(diff.distance)
+        } else {
+            MatchRule::Wrong
+        };
+        AnswerCheck {
+            rule,
+            expected: expected.to_string(),
+            diff,
+        }
+    }
+
+    fn is_enabled(&self, step: Step) -> bool {
+        match step {
+            Step::Case => self.options.ignore_case,
+            Step::Stress => self.options.strip_stress_marks,
+            Step::Yo => self.options.yo_as_ye,
+            Step::Diacritics => self.options.strip_diacritics,
+            Step::Article => !self.options.ignored_articles.is_empty(),
+        }
+    }
+
+    fn apply(&self, step: Step, text: &str) -> String {
+        match step {
+            Step::Case => text.to_lowercase(),
+            Step::Stress => strip_marks(text, |base, mark| {
+                is_cyrillic(base) && (mark == COMBINING_ACUTE || mark == COMBINING_GRAVE)
+            }),
+            Step::Yo => text
+                .nfc()
+                .map(|c| match c {
+                    'ё' => 'е',
+                    'Ё' => 'Е',
+                    c => c,
+                })
+                .collect(),
+            Step::Diacritics => strip_marks(text, |base, _| !is_cyrillic(base)),
+            Step::Article => self.strip_article(text).to_string(),
+        }
+    }
+
+    fn strip_article<'a>(&self, text: &'a str) -> &'a str {
+        if let Some((first, rest)) = text.split_once(' ') {
+            let first = first.to_lowercase();
+            let is_article = self
+                .options
+                .ignored_articles
+                .iter()
+                .any(|a| a.to_lowercase() == first);
+            if is_article && !rest.is_empty() {
+                return rest;
+            }
+        }
+        text
+    }
+}
+
+/// Lower is better: exact match first, then normalization rules in order, then typos, then wrong.
+fn rank(check: &AnswerCheck) -> (u8, usize) {
+    match check.rule {
+        MatchRule::Exact => (0, 0),
+        MatchRule::IgnoredCase => (1, 0),
+        MatchRule::IgnoredStressMarks => (2, 0),
+        MatchRule::YoAsYe => (3, 0),
+        MatchRule::IgnoredDiacritics => (4, 0),
+        MatchRule::IgnoredArticle => (5, 0),
+        MatchRule::Typo(distance) => (6, distance),
+        MatchRule::Wrong => (7, check.diff.distance),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Decomposes the text and drops the combining marks `is_removed(base, mark)` selects.
+fn strip_marks(text: &str, is_removed: impl Fn(char, char) -> bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut base = ' ';
+    for c in text.nfd() {
+        if !is_combining_mark(c) {
+            base = c;
+        } else if is_removed(base, c) {
+            continue;
+        }
+        result.push(c);
+    }
+    result.nfc().collect()
+}
+
+/// Stress marks only appear over Cyrillic vowels, while diacritics of Cyrillic letters
+/// are parts of the letters themselves (й, ё).
+fn is_cyrillic(c: char) -> bool {
+    ('\u{0400}'..='\u{04FF}').contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalization_rules() {
+        let checker = AnswerChecker::new(AnswerCheckOptions::default());
+        assert_eq!(checker.check("cat", "cat").rule, MatchRule::Exact);
+        assert_eq!(checker.check("Cat", "cat").rule, MatchRule::IgnoredCase);
+        assert_eq!(checker.check("елка", "ёлка").rule, MatchRule::YoAsYe);
+        assert_eq!(
+            checker.check("молоко", "молоко\u{301}").rule,
+            MatchRule::IgnoredStressMarks
+        );
+        assert_eq!(
+            checker.check("cafe", "café").rule,
+            MatchRule::IgnoredDiacritics
+        );
+        assert_eq!(checker.check("go", "to go").rule, MatchRule::IgnoredArticle);
+        assert_eq!(checker.check("kitty", "cat; kitty").rule, MatchRule::Exact);
+        assert_eq!(checker.check("иначе", "йначе").rule, MatchRule::Typo(1));
+        assert_eq!(checker.check("dog", "cat").rule, MatchRule::Wrong);
+    }
+
+    #[test]
+    fn rating_is_suggested_by_distance_after_normalization() {
+        let checker = AnswerChecker::new(AnswerCheckOptions::default());
+        assert_eq!(
+            checker.check("Ёлка", "ёлка").suggested_rating(),
+            Rating::Good
+        );
+        assert_eq!(
+            checker.check("the cat", "cat").suggested_rating(),
+            Rating::Good
+        );
+        assert_eq!(
+            checker.check("кошко", "кошка").suggested_rating(),
+            Rating::Hard
+        );
+        // a third of the word wrong would be Again if the typo were not accepted
+        assert_eq!(checker.check("cot", "cat").suggested_rating(), Rating::Hard);
+        // beyond `max_typos` the answer is wrong even if the distance alone would be Hard
+        let check = checker.check("переводчек", "переводчик");
+        assert_eq!(check.rule, MatchRule::Typo(1));
+        assert_eq!(check.suggested_rating(), Rating::Hard);
+        let check = checker.check("пириводчик", "переводчик");
+        assert_eq!(check.rule, MatchRule::Wrong);
+        assert_eq!(check.diff.suggested_rating(), Rating::Hard);
+        assert_eq!(check.suggested_rating(), Rating::Again);
+        assert_eq!(
+            checker.check("dog", "cat").suggested_rating(),
+            Rating::Again
+        );
+    }
+}
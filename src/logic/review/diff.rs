@@ -0,0 +1,165 @@
+use rs_fsrs::Rating;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    /// Typed as expected.
+    Correct(char),
+    /// Expected but not typed.
+    Missing(char),
+    /// Typed but not expected.
+    Extra(char),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnswerDiff {
+    pub ops: Vec<DiffOp>,
+    /// Levenshtein distance between the typed and the expected answer.
+    pub distance: usize,
+}
+
+impl AnswerDiff {
+    pub fn is_exact(&self) -> bool {
+        self.distance == 0
+    }
+
+    /// Good for an exact answer, Hard for a small typo, Again otherwise.
+    pub fn suggested_rating(&self) -> Rating {
+        let expected_len = self
+            .ops
+            .iter()
+            .filter(|op| !matches!(op, DiffOp::Extra(_)))
+            .count();
+
+        if self.distance == 0 {
+            Rating::Good
+        } else if self.distance * 5 <= expected_len {
+            Rating::Hard
+        } else {
+            Rating::Again
+        }
+    }
+}
+
+/// Aligns the typed answer to the expected one character by character.
+///
+/// A substituted character is reported as `Extra` typed char followed by the `Missing` expected one.
+pub fn diff_chars(typed: &str, expected: &str) -> AnswerDiff {
+    let typed: Vec<char> = typed.chars().collect();
+    let expected: Vec<char> = expected.chars().collect();
+    let table = distance_table(&typed, &expected);
+    let columns = expected.len() + 1;
+
+    let mut ops = Vec::with_capacity(typed.len().max(expected.len()));
+    let (mut i, mut j) = (typed.len(), expected.len());
+    while i > 0 || j > 0 {
+        let current = table[i * columns + j];
+        if i > 0
+            && j > 0
+            && typed[i - 1] == expected[j - 1]
+            && current == table[(i - 1) * columns + j - 1]
+        {
+            ops.push(DiffOp::Correct(expected[j - 1]));
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && j > 0 && current == table[(i - 1) * columns + j - 1] + 1 {
+            ops.push(DiffOp::Missing(expected[j - 1]));
+            ops.push(DiffOp::Extra(typed[i - 1]));
+            i -= 1;
+            j -= 1;
+        } else if j > 0 && current == table[i * columns + j - 1] + 1 {
+            ops.push(DiffOp::Missing(expected[j - 1]));
+            j -= 1;
+        } else {
+            ops.push(DiffOp::Extra(typed[i - 1]));
+            i -= 1;
+        }
+    }
+    ops.reverse();
+
+    AnswerDiff {
+        ops,
+        distance: table[typed.len() * columns + expected.len()],
+    }
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    distance_table(&a, &b)[a.len() * (b.len() + 1) + b.len()]
+}
+
+/// Classic Wagner–Fischer table stored row by row, `(a.len() + 1) x (b.len() + 1)`.
+fn distance_table(a: &[char], b: &[char]) -> Vec<usize> {
+    let columns = b.len() + 1;
+    let mut table = vec![0; (a.len() + 1) * columns];
+    for (j, cell) in table.iter_mut().take(columns).enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        table[i * columns] = i;
+        for j in 1..=b.len() {
+            let substitution = table[(i - 1) * columns + j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let deletion = table[(i - 1) * columns + j] + 1;
+            let insertion = table[i * columns + j - 1] + 1;
+            table[i * columns + j] = substitution.min(deletion).min(insertion);
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffOp::*;
+
+    #[test]
+    fn empty_typed_answer_misses_everything() {
+        let diff = diff_chars("", "cat");
+        assert_eq!(diff.distance, 3);
+        assert_eq!(diff.ops, vec![Missing('c'), Missing('a'), Missing('t')]);
+        assert!(diff_chars("", "").is_exact());
+    }
+
+    #[test]
+    fn insert_delete_and_substitute() {
+        let extra = diff_chars("cart", "cat");
+        assert_eq!(extra.distance, 1);
+        assert_eq!(
+            extra.ops,
+            vec![Correct('c'), Correct('a'), Extra('r'), Correct('t')]
+        );
+
+        let missing = diff_chars("ct", "cat");
+        assert_eq!(missing.distance, 1);
+        assert_eq!(missing.ops, vec![Correct('c'), Missing('a'), Correct('t')]);
+
+        let substituted = diff_chars("kat", "cat");
+        assert_eq!(substituted.distance, 1);
+        assert_eq!(
+            substituted.ops,
+            vec![Extra('k'), Missing('c'), Correct('a'), Correct('t')]
+        );
+    }
+
+    #[test]
+    fn cyrillic_is_compared_by_chars() {
+        let diff = diff_chars("кошк", "кошка");
+        assert_eq!(diff.distance, 1);
+        assert_eq!(
+            diff.ops,
+            vec![
+                Correct('к'),
+                Correct('о'),
+                Correct('ш'),
+                Correct('к'),
+                Missing('а')
+            ]
+        );
+        assert_eq!(
+            diff_chars("ёж", "еж").ops,
+            vec![Extra('ё'), Missing('е'), Correct('ж')]
+        );
+        assert_eq!(levenshtein("молоко", "малако"), 2);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+}
@@ -0,0 +1,21 @@
+use chrono::{DateTime, Utc};
+
+/// Formats the time until `due` the short way Anki does on its answer buttons: 1m, 10m, 3h, 5d, 2mo, 1.5y.
+pub fn format_interval(now: DateTime<Utc>, due: DateTime<Utc>) -> String {
+    let minutes = (due - now).num_minutes().max(0);
+    let days = minutes as f64 / (60.0 * 24.0);
+
+    if minutes < 1 {
+        "<1m".to_string()
+    } else if minutes < 60 {
+        format!("{}m", minutes)
+    } else if minutes < 60 * 24 {
+        format!("{}h", minutes / 60)
+    } else if days < 30.0 {
+        format!("{}d", days.round() as i64)
+    } else if days < 365.0 {
+        format!("{}mo", (days / 30.0).round() as i64)
+    } else {
+        format!("{:.1}y", days / 365.0)
+    }
+}
@@ -0,0 +1,8 @@
+pub mod answer_check;
+pub mod diff;
+pub mod interval;
+pub mod queue;
+pub mod review_log;
+pub mod scheduler;
+pub mod session;
+pub mod undo;
\ No newline at end of file
@@ -0,0 +1,184 @@
+//! Picks the cards of a study session out of the deck according to its daily limits.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use rs_fsrs::State;
+
+use crate::logic::{
+    deck::{
+        card::{Card, CardId},
+        deck_options::NewCardOrder,
+        model::Deck,
+        note::NoteId,
+    },
+    study_day::{DayNumber, StudyDay},
+};
+
+/// Cards to study today: learning cards first so their short steps are not delayed,
+/// then reviews, new cards last. The same deck, day and `now` always give the same queue.
+/// Buried cards are left out, so is a sibling of a queued card if the deck buries its state.
+/// Cards lent to a filtered deck are studied there.
+pub fn build_queue(deck: &Deck, day: &StudyDay, now: DateTime<Utc>) -> Vec<CardId> {
+    let today = day.day_number(now);
+    let studied = deck.studied_on(today);
+    let day_end = day.end(now);
+    let cards = || {
+        deck.cards()
+            .iter()
+            .filter(move |c| !c.suspended && !c.is_buried(today) && c.filtered.is_none())
+    };
+
+    let mut learning: Vec<&Card> = cards()
+        .filter(|c| {
+            matches!(c.state(), State::Learning | State::Relearning) && c.fsrs.due < day_end
+        })
+        .collect();
+    learning.sort_by_key(|c| (c.fsrs.due, c.id));
+
+    let mut queued_notes = HashSet::new();
+    learning.retain(|c| is_first_sibling(deck, c, &mut queued_notes));
+
+    let mut review: Vec<&Card> = cards()
+        .filter(|c| c.state() == State::Review && c.fsrs.due < day_end)
+        .collect();
+    review.sort_by_key(|c| (c.fsrs.due, c.id));
+    review.retain(|c| is_first_sibling(deck, c, &mut queued_notes));
+    review.truncate(deck.options.reviews_per_day.saturating_sub(studied.review));
+
+    let mut new: Vec<&Card> = cards().filter(|c| c.is_new()).collect();
+    order_new_cards(deck, &mut new, today);
+    new.retain(|c| is_first_sibling(deck, c, &mut queued_notes));
+    new.truncate(deck.options.new_per_day.saturating_sub(studied.new));
+
+    learning
+        .iter()
+        .chain(review.iter())
+        .chain(new.iter())
+        .map(|c| c.id)
+        .collect()
+}
+
+/// False for a card whose sibling is already queued and would bury it once studied.
+fn is_first_sibling(deck: &Deck, card: &Card, queued_notes: &mut HashSet<NoteId>) -> bool {
+    let is_first = queued_notes.insert(card.note_id);
+    is_first || !deck.options.is_buried_sibling(card.state())
+}
+
+fn order_new_cards(deck: &Deck, cards: &mut [&Card], today: DayNumber) {
+    match deck.options.new_order {
+        NewCardOrder::FileOrder => {}
+        NewCardOrder::Random => shuffle(cards, seed(deck, today)),
+        NewCardOrder::FrequencyRank => {
+            let ranks: HashMap<NoteId, u32> = deck
+                .notes()
+                .iter()
+                .filter_map(|n| Some((n.id, n.rank?)))
+                .collect();
+            // stable, so unranked notes keep the file order
+            cards.sort_by_key(|c| ranks.get(&c.note_id).copied().unwrap_or(u32::MAX));
+        }
+    }
+}
+
+/// Differs between decks and days, so the order is kept when a session is reopened the same day.
+fn seed(deck: &Deck, today: DayNumber) -> u64 {
+    // xorshift needs a well mixed non zero state
+    splitmix64((deck.info.id as u64) << 32 ^ today as u64) | 1
+}
+
+/// Spreads close inputs (ids, days) over the whole range.
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Fisher-Yates with xorshift.
+fn shuffle(cards: &mut [&Card], mut state: u64) {
+    for i in (1..cards.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        cards.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, FixedOffset, TimeZone};
+
+    use super::*;
+    use crate::logic::deck::model::DeckInfo;
+
+    /// Four new cards and three reviews due today, one note each.
+    fn deck(now: DateTime<Utc>) -> Deck {
+        let mut deck = Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        });
+        for i in 0..7 {
+            deck.add_note(vec![format!("front {}", i), format!("back {}", i)], now);
+        }
+        let review_ids: Vec<CardId> = deck.cards()[4..].iter().map(|c| c.id).collect();
+        for card_id in review_ids {
+            let card = deck.card_mut(card_id).unwrap();
+            card.fsrs.state = State::Review;
+            card.fsrs.due = now - Duration::hours(1);
+        }
+        deck
+    }
+
+    fn count(deck: &Deck, queue: &[CardId], state: State) -> usize {
+        queue
+            .iter()
+            .filter(|id| deck.card(**id).unwrap().state() == state)
+            .count()
+    }
+
+    #[test]
+    fn limits_are_applied_and_exhausted() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let day = StudyDay::new(FixedOffset::east_opt(0).unwrap(), 4);
+        let mut deck = deck(now);
+        deck.options.new_per_day = 2;
+        deck.options.reviews_per_day = 2;
+
+        let queue = build_queue(&deck, &day, now);
+        assert_eq!(count(&deck, &queue, State::Review), 2);
+        assert_eq!(count(&deck, &queue, State::New), 2);
+        assert_eq!(queue.len(), 4);
+
+        let today = day.day_number(now);
+        deck.count_studied(today, State::New);
+        deck.count_studied(today, State::New);
+        let queue = build_queue(&deck, &day, now);
+        assert_eq!(count(&deck, &queue, State::New), 0);
+        assert_eq!(count(&deck, &queue, State::Review), 2);
+
+        deck.count_studied(today, State::Review);
+        deck.count_studied(today, State::Review);
+        deck.count_studied(today, State::Review);
+        assert!(build_queue(&deck, &day, now).is_empty());
+
+        // the counters are for today only
+        let tomorrow = now + Duration::days(1);
+        assert_eq!(build_queue(&deck, &day, tomorrow).len(), 4);
+    }
+
+    #[test]
+    fn learning_cards_are_not_limited() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        let day = StudyDay::new(FixedOffset::east_opt(0).unwrap(), 4);
+        let mut deck = deck(now);
+        deck.options.new_per_day = 0;
+        deck.options.reviews_per_day = 0;
+        let learning_id = deck.cards()[0].id;
+        let card = deck.card_mut(learning_id).unwrap();
+        card.fsrs.state = State::Learning;
+        card.fsrs.due = now + Duration::minutes(10);
+
+        assert_eq!(build_queue(&deck, &day, now), vec![learning_id]);
+    }
+}
@@ -0,0 +1,275 @@
+//! Journal of every grade, appended to `reviews.log` on the SD card.
+//!
+//! Each line is a JSON record followed by a tab and the CRC32 of the JSON in hex:
+//! ```text
+//! {"deck_id":1,"card_id":7,"time":"2024-05-01T10:00:00Z","rating":"Good",...}<TAB>1c291ca3
+//! ```
+//! A line torn by a power loss fails the checksum and is skipped on load.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rs_fsrs::{Rating, State};
+use serde::{Deserialize, Serialize};
+
+use crate::logic::{
+    deck::{
+        card::{Card, CardId},
+        card_template::TemplateId,
+        cloze::ClozeNumber,
+        model::{Deck, DeckId},
+        note::NoteId,
+    },
+    review::scheduler::Scheduler,
+};
+
+pub const FILE_NAME: &str = "reviews.log";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewLogEntry {
+    pub deck_id: DeckId,
+    pub card_id: CardId,
+    /// Lets a card lost from the deck file be recreated with its id.
+    pub note_id: NoteId,
+    #[serde(default)]
+    pub template: TemplateId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloze: Option<ClozeNumber>,
+    pub time: DateTime<Utc>,
+    pub rating: Rating,
+    /// Days since the previous review of the card.
+    pub elapsed_days: i64,
+    pub state_before: State,
+    pub state_after: State,
+    /// Time from showing the card to grading it.
+    pub duration_ms: u32,
+}
+
+impl ReviewLogEntry {
+    pub fn to_line(&self) -> Result<String> {
+        let json = serde_json::to_string(self)?;
+        Ok(format!("{}\t{:08x}\n", json, crc32(json.as_bytes())))
+    }
+
+    pub fn parse_line(line: &str) -> Result<Self> {
+        let (json, checksum) = line
+            .rsplit_once('\t')
+            .ok_or_else(|| anyhow!("no checksum"))?;
+        let checksum = u32::from_str_radix(checksum.trim(), 16)?;
+        if checksum != crc32(json.as_bytes()) {
+            return Err(anyhow!("checksum mismatch"));
+        }
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// Collects journal entries from lines fed one by one, damaged lines are counted and skipped.
+#[derive(Default)]
+pub struct ReviewLogLoader {
+    deck_ids: Option<Vec<DeckId>>,
+    entries: Vec<ReviewLogEntry>,
+    skipped_lines: usize,
+}
+
+impl ReviewLogLoader {
+    /// Keeps only the entries of the given decks.
+    pub fn for_decks(deck_ids: &[DeckId]) -> Self {
+        Self {
+            deck_ids: Some(deck_ids.to_vec()),
+            ..Default::default()
+        }
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        match ReviewLogEntry::parse_line(line) {
+            Ok(entry) if self.is_wanted(&entry) => self.entries.push(entry),
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("skip review log line: {:?}", e);
+                self.skipped_lines += 1;
+            }
+        }
+    }
+
+    pub fn skipped_lines(&self) -> usize {
+        self.skipped_lines
+    }
+
+    pub fn finish(self) -> Vec<ReviewLogEntry> {
+        self.entries
+    }
+
+    fn is_wanted(&self, entry: &ReviewLogEntry) -> bool {
+        self.deck_ids
+            .as_ref()
+            .map_or(true, |ids| ids.contains(&entry.deck_id))
+    }
+}
+
+/// Rebuilds the FSRS state of every journaled card of the deck by replaying its grades,
+/// cards missing from the deck are recreated if their note still makes them.
+/// Cards without journal entries are left as they are, returns the number of rebuilt cards.
+pub fn rebuild_deck(deck: &mut Deck, entries: &[ReviewLogEntry], scheduler: &Scheduler) -> usize {
+    let mut entries: Vec<&ReviewLogEntry> = entries
+        .iter()
+        .filter(|e| e.deck_id == deck.info.id)
+        .collect();
+    entries.sort_by_key(|e| (e.card_id, e.time));
+
+    let mut rebuilt = 0;
+    for card_entries in entries.chunk_by(|a, b| a.card_id == b.card_id) {
+        let first = card_entries[0];
+        let is_made = deck.note(first.note_id).is_some_and(|note| {
+            deck.options
+                .templates
+                .iter()
+                .any(|t| t.id == first.template && t.cards_for(note).contains(&first.cloze))
+        });
+        if deck.card(first.card_id).is_none() && is_made {
+            deck.insert_card(Card {
+                cloze: first.cloze,
+                ..Card::new(first.card_id, first.note_id, first.template, first.time)
+            });
+        }
+        let Some(card) = deck.card_mut(first.card_id) else {
+            continue;
+        };
+
+        let mut replayed = Card::new(card.id, card.note_id, card.template, first.time);
+        for entry in card_entries {
+            replayed.apply(&scheduler.next(&replayed, entry.time, entry.rating));
+        }
+        card.fsrs = replayed.fsrs;
+        card.step = replayed.step;
+        rebuilt += 1;
+    }
+    rebuilt
+}
+
+/// CRC-32 (IEEE), the one used by zip and PNG.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::logic::deck::{deck_preset::DeckPreset, model::DeckInfo};
+
+    fn entry(deck_id: DeckId, card: &Card, time: DateTime<Utc>, rating: Rating) -> ReviewLogEntry {
+        ReviewLogEntry {
+            deck_id,
+            card_id: card.id,
+            note_id: card.note_id,
+            template: card.template,
+            cloze: card.cloze,
+            time,
+            rating,
+            elapsed_days: 0,
+            state_before: card.state(),
+            state_after: card.state(),
+            duration_ms: 0,
+        }
+    }
+
+    fn deck_with_notes(count: usize, now: DateTime<Utc>) -> Deck {
+        let mut deck = Deck::new(DeckInfo {
+            id: 1,
+            name: "Words".into(),
+        });
+        for i in 0..count {
+            deck.add_note(vec![format!("front {}", i), format!("back {}", i)], now);
+        }
+        deck
+    }
+
+    #[test]
+    fn loader_skips_bad_checksum_and_torn_last_line() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap();
+        let deck = deck_with_notes(3, now);
+        let cards = deck.cards();
+        let first = entry(1, &cards[0], now, Rating::Good);
+        let other_deck = entry(2, &cards[1], now, Rating::Again);
+        let last = entry(1, &cards[2], now, Rating::Easy);
+
+        let bad_checksum = entry(1, &cards[1], now, Rating::Hard)
+            .to_line()
+            .unwrap()
+            .replace("\"Hard\"", "\"Easy\"");
+        let torn = last.to_line().unwrap();
+        let torn = &torn[..torn.len() - 12];
+        let log = [
+            first.to_line().unwrap(),
+            bad_checksum,
+            other_deck.to_line().unwrap(),
+            "   ".to_string(),
+            torn.to_string(),
+        ]
+        .concat();
+
+        let mut loader = ReviewLogLoader::for_decks(&[1]);
+        for line in log.lines() {
+            loader.push_line(line);
+        }
+        assert_eq!(loader.skipped_lines(), 2);
+        assert_eq!(loader.finish(), vec![first.clone()]);
+
+        let mut loader = ReviewLogLoader::default();
+        for line in log.lines() {
+            loader.push_line(line);
+        }
+        assert_eq!(loader.finish(), vec![first, other_deck]);
+    }
+
+    #[test]
+    fn rebuild_replays_grades_and_recreates_lost_cards() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap();
+        let scheduler = DeckPreset::new(DeckPreset::DEFAULT_ID, "Default").scheduler();
+        let mut deck = deck_with_notes(3, now);
+
+        let mut entries = Vec::new();
+        let graded: Vec<CardId> = deck.cards()[..2].iter().map(|c| c.id).collect();
+        for (i, rating) in [Rating::Good, Rating::Good, Rating::Hard]
+            .iter()
+            .enumerate()
+        {
+            let time = now + Duration::minutes(10 * i as i64);
+            for card_id in &graded {
+                let card = deck.card(*card_id).unwrap();
+                entries.push(entry(1, card, time, *rating));
+                let scheduled = scheduler.next(card, time, *rating);
+                deck.apply_review(*card_id, &scheduled);
+            }
+        }
+        let expected = deck.clone();
+
+        // the deck file was saved before the grades and lost its last graded card
+        let mut damaged = deck_with_notes(3, now);
+        let lost = damaged.cards()[1].clone();
+        damaged.restore_note(damaged.note(lost.note_id).unwrap().clone(), &[]);
+        assert!(damaged.card(lost.id).is_none());
+
+        assert_eq!(rebuild_deck(&mut damaged, &entries, &scheduler), 2);
+        for card_id in &graded {
+            assert_eq!(damaged.card(*card_id), expected.card(*card_id));
+        }
+        let untouched = &expected.cards()[2];
+        assert_eq!(damaged.card(untouched.id), Some(untouched));
+        assert!(untouched.is_new());
+    }
+}
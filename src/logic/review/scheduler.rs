@@ -0,0 +1,323 @@
+//! Anki style (re)learning steps on top of FSRS.
+//!
+//! FSRS keeps updating stability and difficulty on every grade, the steps only decide when a
+//! card in the `Learning`/`Relearning` state is shown again and when it graduates to `Review`.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use rs_fsrs::{Parameters, Rating, SchedulingInfo, State, FSRS};
+use serde::{Deserialize, Serialize};
+
+use crate::logic::deck::card::Card;
+
+/// Delays of the (re)learning steps, written the Anki way: `"1m 10m"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Steps {
+    seconds: Vec<u32>,
+}
+
+impl Steps {
+    pub fn from_minutes(minutes: &[u32]) -> Self {
+        Self {
+            seconds: minutes.iter().map(|m| m * 60).collect(),
+        }
+    }
+
+    fn delay(&self, step: usize) -> Option<Duration> {
+        self.seconds
+            .get(step)
+            .map(|seconds| Duration::seconds(*seconds as i64))
+    }
+
+    /// Delay of Hard on a step: halfway to the next step on the first one, the same step later.
+    fn hard_delay(&self, step: usize) -> Option<Duration> {
+        let current = self.delay(step)?;
+        match (step, self.delay(step + 1)) {
+            (0, Some(next)) => Some((current + next) / 2),
+            (0, None) => Some((current * 3 / 2).min(current + Duration::days(1))),
+            _ => Some(current),
+        }
+    }
+}
+
+impl FromStr for Steps {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let seconds = s
+            .split_whitespace()
+            .map(|step| {
+                let unit_at = step
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(step.len());
+                let value: u32 = step[..unit_at]
+                    .parse()
+                    .map_err(|_| anyhow!("bad step {}", step))?;
+                let unit = match &step[unit_at..] {
+                    "s" => 1,
+                    "m" | "" => 60,
+                    "h" => 60 * 60,
+                    "d" => 24 * 60 * 60,
+                    _ => return Err(anyhow!("bad step {}, use s, m, h or d", step)),
+                };
+                value
+                    .checked_mul(unit)
+                    .ok_or_else(|| anyhow!("bad step {}", step))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { seconds })
+    }
+}
+
+impl TryFrom<String> for Steps {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Steps> for String {
+    fn from(steps: Steps) -> Self {
+        steps.to_string()
+    }
+}
+
+impl fmt::Display for Steps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, seconds) in self.seconds.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match seconds {
+                s if s % (24 * 60 * 60) == 0 && *s > 0 => write!(f, "{}d", s / (24 * 60 * 60))?,
+                s if s % (60 * 60) == 0 && *s > 0 => write!(f, "{}h", s / (60 * 60))?,
+                s if s % 60 == 0 => write!(f, "{}m", s / 60)?,
+                s => write!(f, "{}s", s)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of one grade: the new FSRS state and the step the card is on.
+#[derive(Debug, Clone)]
+pub struct Scheduled {
+    pub info: SchedulingInfo,
+    pub step: u16,
+}
+
+impl Scheduled {
+    pub fn due(&self) -> DateTime<Utc> {
+        self.info.card.due
+    }
+}
+
+pub type Preview = HashMap<Rating, Scheduled>;
+
+pub struct Scheduler {
+    fsrs: FSRS,
+    parameters: Parameters,
+    learning_steps: Steps,
+    relearning_steps: Steps,
+}
+
+impl Scheduler {
+    pub fn new(parameters: Parameters, learning_steps: Steps, relearning_steps: Steps) -> Self {
+        Self {
+            fsrs: FSRS::new(parameters.clone()),
+            parameters,
+            learning_steps,
+            relearning_steps,
+        }
+    }
+
+    pub fn preview(&self, card: &Card, now: DateTime<Utc>) -> Preview {
+        Rating::iter()
+            .map(|rating| (*rating, self.next(card, now, *rating)))
+            .collect()
+    }
+
+    pub fn next(&self, card: &Card, now: DateTime<Utc>, rating: Rating) -> Scheduled {
+        let info = self.fsrs.next(card.fsrs.clone(), now, rating);
+        let step = card.step as usize;
+        let state = match card.state() {
+            State::Review if rating == Rating::Again => {
+                return self.step(info, State::Relearning, 0, now)
+            }
+            State::Review => return Scheduled { info, step: 0 },
+            State::Relearning => State::Relearning,
+            State::New | State::Learning => State::Learning,
+        };
+        match rating {
+            Rating::Again => self.step(info, state, 0, now),
+            Rating::Hard => match self.steps(state).hard_delay(step) {
+                Some(delay) => stay(info, state, step, now + delay),
+                None => self.graduate(info, now),
+            },
+            // a new card is on step 0 before its first grade, Good moves it to step 1
+            Rating::Good => self.step(info, state, step + 1, now),
+            Rating::Easy => self.graduate(info, now),
+        }
+    }
+
+    fn steps(&self, state: State) -> &Steps {
+        match state {
+            State::Relearning => &self.relearning_steps,
+            _ => &self.learning_steps,
+        }
+    }
+
+    /// Puts the card on `step`, or graduates it if there are no more steps.
+    fn step(
+        &self,
+        info: SchedulingInfo,
+        state: State,
+        step: usize,
+        now: DateTime<Utc>,
+    ) -> Scheduled {
+        match self.steps(state).delay(step) {
+            Some(delay) => stay(info, state, step, now + delay),
+            None => self.graduate(info, now),
+        }
+    }
+
+    fn graduate(&self, mut info: SchedulingInfo, now: DateTime<Utc>) -> Scheduled {
+        if info.card.state != State::Review {
+            let days = self
+                .parameters
+                .next_interval(info.card.stability, info.card.elapsed_days)
+                as i64;
+            info.card.state = State::Review;
+            info.card.scheduled_days = days;
+            info.card.due = now + Duration::days(days);
+        }
+        Scheduled { info, step: 0 }
+    }
+}
+
+fn stay(mut info: SchedulingInfo, state: State, step: usize, due: DateTime<Utc>) -> Scheduled {
+    info.card.state = state;
+    info.card.scheduled_days = 0;
+    info.card.due = due;
+    Scheduled {
+        info,
+        step: step as u16,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn steps(s: &str) -> Steps {
+        s.parse().unwrap()
+    }
+
+    fn scheduler() -> Scheduler {
+        Scheduler::new(Parameters::default(), steps("1m 10m"), steps("10m"))
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap()
+    }
+
+    /// Grades the card and returns its state, step and minutes until it is due.
+    fn grade(card: &mut Card, rating: Rating) -> (State, u16, i64) {
+        let scheduled = scheduler().next(card, now(), rating);
+        card.apply(&scheduled);
+        (
+            card.state(),
+            card.step,
+            (scheduled.due() - now()).num_minutes(),
+        )
+    }
+
+    #[test]
+    fn steps_parse_and_display() {
+        assert_eq!(steps("1m 10m").to_string(), "1m 10m");
+        assert_eq!(steps("30s 1h 2d").to_string(), "30s 1h 2d");
+        assert_eq!(steps("10 60m 1440m 90s").to_string(), "10m 1h 1d 90s");
+        assert_eq!(steps("  ").to_string(), "");
+        assert_eq!(steps("49710d").to_string(), "49710d");
+        for bad in ["5x", "m", "1.5m", "-1m", "49711d", "4294967296s"] {
+            assert!(bad.parse::<Steps>().is_err(), "{}", bad);
+        }
+
+        let json = serde_json::to_string(&steps("1m 1d")).unwrap();
+        assert_eq!(json, "\"1m 1d\"");
+        assert_eq!(
+            serde_json::from_str::<Steps>(&json).unwrap(),
+            steps("1m 1d")
+        );
+        assert!(serde_json::from_str::<Steps>("\"1w\"").is_err());
+    }
+
+    #[test]
+    fn hard_delay() {
+        let minutes = |steps: &Steps, step| steps.hard_delay(step).map(|d| d.num_minutes());
+        // halfway to the next step on the first one
+        assert_eq!(minutes(&steps("1m 10m"), 0), Some(5));
+        assert_eq!(minutes(&steps("1m 10m"), 1), Some(10));
+        assert_eq!(minutes(&steps("1m 10m"), 2), None);
+        // half again as long with a single step, but no more than a day longer
+        assert_eq!(minutes(&steps("10m"), 0), Some(15));
+        assert_eq!(minutes(&steps("4d"), 0), Some(5 * 24 * 60));
+        assert_eq!(minutes(&steps(""), 0), None);
+    }
+
+    #[test]
+    fn learning_card_goes_through_the_steps() {
+        let mut card = Card::new(1, 1, 0, now());
+        assert_eq!(grade(&mut card, Rating::Again), (State::Learning, 0, 1));
+        assert_eq!(grade(&mut card, Rating::Hard), (State::Learning, 0, 5));
+        assert_eq!(grade(&mut card, Rating::Good), (State::Learning, 1, 10));
+        assert_eq!(grade(&mut card, Rating::Hard), (State::Learning, 1, 10));
+        assert_eq!(grade(&mut card, Rating::Again), (State::Learning, 0, 1));
+        assert_eq!(grade(&mut card, Rating::Good), (State::Learning, 1, 10));
+
+        let (state, step, minutes) = grade(&mut card, Rating::Good);
+        assert_eq!((state, step), (State::Review, 0));
+        assert_eq!(minutes, card.fsrs.scheduled_days * 24 * 60);
+        assert!(card.fsrs.scheduled_days >= 1);
+    }
+
+    #[test]
+    fn easy_graduates_right_away() {
+        let mut card = Card::new(1, 1, 0, now());
+        let (state, step, _) = grade(&mut card, Rating::Easy);
+        assert_eq!((state, step), (State::Review, 0));
+        assert!(card.fsrs.scheduled_days >= 1);
+    }
+
+    #[test]
+    fn forgotten_review_is_relearned() {
+        let mut card = Card::new(1, 1, 0, now());
+        grade(&mut card, Rating::Easy);
+        assert_eq!(grade(&mut card, Rating::Again), (State::Relearning, 0, 10));
+        assert_eq!(grade(&mut card, Rating::Hard), (State::Relearning, 0, 15));
+        let (state, step, _) = grade(&mut card, Rating::Good);
+        assert_eq!((state, step), (State::Review, 0));
+        assert_eq!(card.fsrs.lapses, 1);
+
+        // a review card stays in review on a pass, the steps are not used
+        let (state, _, minutes) = grade(&mut card, Rating::Hard);
+        assert_eq!(state, State::Review);
+        assert!(minutes >= 24 * 60);
+    }
+
+    #[test]
+    fn without_relearning_steps_a_lapse_goes_back_to_review() {
+        let scheduler = Scheduler::new(Parameters::default(), steps("1m"), steps(""));
+        let mut card = Card::new(1, 1, 0, now());
+        card.apply(&scheduler.next(&card, now(), Rating::Easy));
+        let scheduled = scheduler.next(&card, now(), Rating::Again);
+        assert_eq!(scheduled.info.card.state, State::Review);
+        assert_eq!(scheduled.step, 0);
+    }
+}
@@ -0,0 +1,598 @@
+use std::{collections::VecDeque, ops::AddAssign};
+
+use chrono::{DateTime, Duration, Utc};
+use rs_fsrs::{Rating, State};
+
+use crate::logic::{
+    deck::{
+        card::{Card, CardId},
+        card_template::CardSides,
+        filtered_deck::{FilteredDeck, FilteredDeckId},
+        model::{Deck, DeckId},
+        note::NoteId,
+    },
+    review::{
+        queue::build_queue,
+        review_log::ReviewLogEntry,
+        scheduler::{Preview, Scheduler},
+        undo::{UndoAction, UndoStep},
+    },
+    study_day::StudyDay,
+};
+
+/// A learning card due this soon is shown early when nothing else is left.
+const LEARN_AHEAD_MINUTES: i64 = 20;
+/// A previewed card graded Again is shown again this much later.
+const PREVIEW_AGAIN_MINUTES: i64 = 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueCounts {
+    pub new: usize,
+    pub learning: usize,
+    pub review: usize,
+}
+
+impl QueueCounts {
+    pub fn total(&self) -> usize {
+        self.new + self.learning + self.review
+    }
+
+    fn count(&mut self, state: State) {
+        match state {
+            State::New => self.new += 1,
+            State::Learning | State::Relearning => self.learning += 1,
+            State::Review => self.review += 1,
+        }
+    }
+}
+
+impl AddAssign for QueueCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.new += other.new;
+        self.learning += other.learning;
+        self.review += other.review;
+    }
+}
+
+/// Cards the deck has to study today, as a session would start with them.
+pub fn queue_counts(deck: &Deck, day: &StudyDay, now: DateTime<Utc>) -> QueueCounts {
+    let mut counts = QueueCounts::default();
+    for card in build_queue(deck, day, now)
+        .into_iter()
+        .filter_map(|id| deck.card(id))
+    {
+        counts.count(card.state());
+    }
+    counts
+}
+
+/// Cards of the deck lent to the filtered deck, unless suspended or buried.
+fn filtered_cards<'d>(
+    deck: &'d Deck,
+    filtered: FilteredDeckId,
+    day: &StudyDay,
+    now: DateTime<Utc>,
+) -> impl Iterator<Item = &'d Card> {
+    let today = day.day_number(now);
+    deck.cards()
+        .iter()
+        .filter(move |c| c.filtered == Some(filtered) && !c.suspended && !c.is_buried(today))
+}
+
+/// Cards the filtered deck still has to study from this home deck.
+pub fn filtered_counts(
+    deck: &Deck,
+    filtered: FilteredDeckId,
+    day: &StudyDay,
+    now: DateTime<Utc>,
+) -> QueueCounts {
+    let mut counts = QueueCounts::default();
+    for card in filtered_cards(deck, filtered, day, now) {
+        counts.count(card.state());
+    }
+    counts
+}
+
+/// A card of one of the studied decks, card ids are only unique within a deck.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CardKey {
+    pub deck_id: DeckId,
+    pub card_id: CardId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Due cards of the home decks within their daily limits.
+    Home,
+    /// Cards lent to a filtered deck, all of them whether due or not.
+    Filtered {
+        id: FilteredDeckId,
+        reschedule: bool,
+    },
+}
+
+/// Outcome of a grade.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graded {
+    pub key: CardKey,
+    /// `None` for a preview, it leaves the scheduling as it was.
+    pub log: Option<ReviewLogEntry>,
+    /// Takes the grade back, the log entry included.
+    pub undo: Option<UndoStep>,
+}
+
+struct StudiedDeck {
+    deck: Deck,
+    /// From the preset of the deck.
+    scheduler: Scheduler,
+}
+
+/// Goes through the due cards of one or more decks (a parent and its children) card by card
+/// and schedules them with FSRS, every deck keeps its own limits and preset.
+///
+/// Cards on (re)learning steps wait in an intraday queue and are shown again, in between
+/// the other cards, once their step is over.
+///
+/// A filtered deck is studied the same way, a card goes back home once it is out of learning.
+/// A filtered deck that doesn't reschedule only previews: Again shows the card again
+/// a minute later, the other grades send it home untouched.
+pub struct ReviewSession {
+    decks: Vec<StudiedDeck>,
+    day: StudyDay,
+    mode: Mode,
+    /// Reviews of all decks by due time, then new cards deck by deck.
+    queue: VecDeque<CardKey>,
+    /// Cards on a step, sorted by due time.
+    learning: Vec<(DateTime<Utc>, CardKey)>,
+    current: Option<CardKey>,
+    /// Card that just became a leech, until the notice is dismissed.
+    leech: Option<CardKey>,
+    preview: Option<Preview>,
+    /// When the current card was shown, for the answer duration.
+    shown_at: DateTime<Utc>,
+}
+
+impl ReviewSession {
+    /// Takes the cards due on the study day of `now`, within the daily limits of each deck.
+    pub fn new(decks: Vec<(Deck, Scheduler)>, day: StudyDay, now: DateTime<Utc>) -> Self {
+        let mut reviews = Vec::new();
+        let mut new = Vec::new();
+        let mut learning = Vec::new();
+        for (deck, _) in &decks {
+            for card in build_queue(deck, &day, now)
+                .into_iter()
+                .filter_map(|id| deck.card(id))
+            {
+                let key = CardKey {
+                    deck_id: deck.info.id,
+                    card_id: card.id,
+                };
+                match card.state() {
+                    State::Learning | State::Relearning => learning.push((card.fsrs.due, key)),
+                    State::Review => reviews.push((card.fsrs.due, key)),
+                    State::New => new.push(key),
+                }
+            }
+        }
+        // stable, cards of one deck due at the same time keep the order of the deck's queue
+        learning.sort_by_key(|(due, _)| *due);
+        reviews.sort_by_key(|(due, _)| *due);
+        let queue = reviews.into_iter().map(|(_, key)| key).chain(new).collect();
+        Self::start(decks, day, Mode::Home, queue, learning, now)
+    }
+
+    /// Takes every card the home decks lent to the filtered deck, in the deck's order.
+    pub fn filtered(
+        decks: Vec<(Deck, Scheduler)>,
+        filtered: &FilteredDeck,
+        day: StudyDay,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut cards = Vec::new();
+        for (deck, _) in &decks {
+            for card in filtered_cards(deck, filtered.id, &day, now) {
+                let key = CardKey {
+                    deck_id: deck.info.id,
+                    card_id: card.id,
+                };
+                cards.push((filtered.sort_key(deck.info.id, card), key));
+            }
+        }
+        cards.sort_unstable();
+        let queue = cards.into_iter().map(|(_, key)| key).collect();
+        let mode = Mode::Filtered {
+            id: filtered.id,
+            reschedule: filtered.reschedule,
+        };
+        Self::start(decks, day, mode, queue, Vec::new(), now)
+    }
+
+    fn start(
+        decks: Vec<(Deck, Scheduler)>,
+        day: StudyDay,
+        mode: Mode,
+        queue: VecDeque<CardKey>,
+        learning: Vec<(DateTime<Utc>, CardKey)>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut session = Self {
+            decks: decks
+                .into_iter()
+                .map(|(deck, scheduler)| StudiedDeck { deck, scheduler })
+                .collect(),
+            day,
+            mode,
+            queue,
+            learning,
+            current: None,
+            leech: None,
+            preview: None,
+            shown_at: now,
+        };
+        session.update(now);
+        session
+    }
+
+    pub fn decks(&self) -> impl Iterator<Item = &Deck> {
+        self.decks.iter().map(|d| &d.deck)
+    }
+
+    pub fn deck(&self, id: DeckId) -> Option<&Deck> {
+        self.decks().find(|d| d.info.id == id)
+    }
+
+    fn deck_mut(&mut self, id: DeckId) -> Option<&mut Deck> {
+        self.decks
+            .iter_mut()
+            .map(|d| &mut d.deck)
+            .find(|d| d.info.id == id)
+    }
+
+    pub fn into_decks(self) -> Vec<Deck> {
+        self.decks.into_iter().map(|d| d.deck).collect()
+    }
+
+    pub fn card(&self, key: CardKey) -> Option<&Card> {
+        self.deck(key.deck_id)?.card(key.card_id)
+    }
+
+    /// False for a preview of a filtered deck, its grades don't change the scheduling.
+    pub fn is_rescheduling(&self) -> bool {
+        !matches!(
+            self.mode,
+            Mode::Filtered {
+                reschedule: false,
+                ..
+            }
+        )
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current.is_none() && self.queue.is_empty() && self.learning.is_empty()
+    }
+
+    /// `None` when the session is finished or waits for a learning step to end.
+    pub fn current(&self) -> Option<&Card> {
+        self.current.and_then(|key| self.card(key))
+    }
+
+    pub fn current_key(&self) -> Option<CardKey> {
+        self.current
+    }
+
+    /// The deck of the current card.
+    pub fn current_deck(&self) -> Option<&Deck> {
+        self.current.and_then(|key| self.deck(key.deck_id))
+    }
+
+    /// When the next learning card is due if there is nothing to show before it.
+    pub fn waiting_until(&self) -> Option<DateTime<Utc>> {
+        match self.current {
+            Some(_) => None,
+            None => self.learning.first().map(|(due, _)| *due),
+        }
+    }
+
+    /// Shows the next card if none is shown: a learning card whose step is over goes first,
+    /// then the queue, then learning cards due soon. Returns true if a card was picked.
+    pub fn update(&mut self, now: DateTime<Utc>) -> bool {
+        if self.current.is_some() {
+            return false;
+        }
+
+        let learning_due =
+            |limit: DateTime<Utc>, learning: &mut Vec<(DateTime<Utc>, CardKey)>| match learning
+                .first()
+            {
+                Some((due, _)) if *due <= limit => Some(learning.remove(0).1),
+                _ => None,
+            };
+        self.current = learning_due(now, &mut self.learning)
+            .or_else(|| self.queue.pop_front())
+            .or_else(|| {
+                learning_due(
+                    now + Duration::minutes(LEARN_AHEAD_MINUTES),
+                    &mut self.learning,
+                )
+            });
+        if self.current.is_some() {
+            self.shown_at = now;
+        }
+        self.current.is_some()
+    }
+
+    /// Question and answer of the current card, as its template renders them from the note.
+    pub fn current_sides(&self) -> Option<CardSides> {
+        let deck = self.current_deck()?;
+        deck.sides(self.current()?)
+    }
+
+    /// Remaining cards by queue, the current card included.
+    pub fn counts(&self) -> QueueCounts {
+        let mut counts = QueueCounts::default();
+        let keys = self
+            .current
+            .iter()
+            .chain(self.queue.iter())
+            .chain(self.learning.iter().map(|(_, key)| key));
+        for card in keys.filter_map(|key| self.card(*key)) {
+            counts.count(card.state());
+        }
+        counts
+    }
+
+    /// The card that just became a leech, its note is already tagged.
+    pub fn leech(&self) -> Option<CardKey> {
+        self.leech
+    }
+
+    pub fn dismiss_leech(&mut self) {
+        self.leech = None;
+    }
+
+    /// Takes the card out of this session and of the later ones until unsuspended.
+    /// Returns the step undoing it.
+    pub fn set_suspended(
+        &mut self,
+        key: CardKey,
+        suspended: bool,
+        now: DateTime<Utc>,
+    ) -> Option<UndoStep> {
+        let action = if suspended {
+            UndoAction::Suspend
+        } else {
+            UndoAction::Unsuspend
+        };
+        let undo = self.undo_step(action, key);
+        if let Some(deck) = self.deck_mut(key.deck_id) {
+            deck.set_suspended(key.card_id, suspended);
+        }
+        if suspended {
+            self.remove_from_queues(key, now);
+        } else {
+            self.requeue_learning(key, now);
+            self.update(now);
+        }
+        undo
+    }
+
+    /// Takes the card out of the session until the next study day.
+    pub fn bury(&mut self, key: CardKey, now: DateTime<Utc>) -> Option<UndoStep> {
+        let undo = self.undo_step(UndoAction::Bury, key);
+        let today = self.day.day_number(now);
+        if let Some(card) = self
+            .deck_mut(key.deck_id)
+            .and_then(|d| d.card_mut(key.card_id))
+        {
+            card.buried_on = Some(today);
+        }
+        self.remove_from_queues(key, now);
+        undo
+    }
+
+    /// Forgets the scheduling of the card, it comes back as a new card on a later day.
+    pub fn reset(&mut self, key: CardKey, now: DateTime<Utc>) -> Option<UndoStep> {
+        let undo = self.undo_step(UndoAction::Reset, key);
+        if let Some(card) = self
+            .deck_mut(key.deck_id)
+            .and_then(|d| d.card_mut(key.card_id))
+        {
+            card.reset(now);
+        }
+        self.remove_from_queues(key, now);
+        undo
+    }
+
+    pub fn set_note_field(
+        &mut self,
+        deck_id: DeckId,
+        note_id: NoteId,
+        index: usize,
+        value: String,
+        now: DateTime<Utc>,
+    ) -> Option<UndoStep> {
+        let deck = self.deck_mut(deck_id)?;
+        let undo = UndoStep::before(UndoAction::EditNote, deck, note_id, None);
+        deck.note_mut(note_id)?.set_field(index, value, now);
+        undo
+    }
+
+    /// Takes back a change made to one of the studied decks. A card that is in study again
+    /// is shown right away, the card shown before it goes back to the front of the queue.
+    /// Returns false if the deck is not in this session.
+    pub fn undo(&mut self, step: &UndoStep, now: DateTime<Utc>) -> bool {
+        let Some(deck) = self.deck_mut(step.deck_id) else {
+            return false;
+        };
+        step.restore(deck);
+        let Some(key) = step.card_key() else {
+            return true;
+        };
+        if self.leech == Some(key) {
+            self.leech = None;
+        }
+
+        let today = self.day.day_number(now);
+        let is_studied = self
+            .card(key)
+            .is_some_and(|c| !c.suspended && !c.is_buried(today));
+        self.queue.retain(|k| *k != key);
+        self.learning.retain(|(_, k)| *k != key);
+        if !is_studied {
+            self.remove_from_queues(key, now);
+            return true;
+        }
+        if let Some(current) = self.current.replace(key) {
+            if current != key {
+                self.queue.push_front(current);
+            }
+        }
+        self.preview = None;
+        self.shown_at = now;
+        true
+    }
+
+    fn undo_step(&self, action: UndoAction, key: CardKey) -> Option<UndoStep> {
+        UndoStep::before_card(action, self.deck(key.deck_id)?, key.card_id)
+    }
+
+    /// Outcome of every grade for the current card, computed once per shown card.
+    /// `None` when not rescheduling.
+    pub fn preview(&mut self, now: DateTime<Utc>) -> Option<&Preview> {
+        if !self.is_rescheduling() {
+            return None;
+        }
+        if self.preview.is_none() {
+            let key = self.current?;
+            let studied = self.decks.iter().find(|d| d.deck.info.id == key.deck_id)?;
+            let card = studied.deck.card(key.card_id)?;
+            self.preview = Some(studied.scheduler.preview(card, now));
+        }
+        self.preview.as_ref()
+    }
+
+    /// Stores the chosen grade for the current card, moves on to the next one
+    /// and returns the record for the review log.
+    pub fn grade(&mut self, rating: Rating, now: DateTime<Utc>) -> Option<Graded> {
+        if !self.is_rescheduling() {
+            return self.grade_preview(rating, now);
+        }
+        let (note_id, template, cloze, state_before) = self
+            .current()
+            .map(|c| (c.note_id, c.template, c.cloze, c.state()))?;
+        let scheduled = self.preview(now)?.get(&rating)?.clone();
+        let key = self.current.take()?;
+        self.preview = None;
+        let undo = self.undo_step(UndoAction::Grade, key);
+
+        let today = self.day.day_number(now);
+        let deck = self.deck_mut(key.deck_id)?;
+        deck.apply_review(key.card_id, &scheduled);
+        deck.count_studied(today, state_before);
+        let buried = deck.bury_siblings(key.card_id, today);
+        let is_leech = state_before == State::Review
+            && rating == Rating::Again
+            && deck
+                .options
+                .is_leech(scheduled.info.card.lapses.max(0) as u32);
+        if is_leech {
+            deck.mark_leech(key.card_id);
+            self.leech = Some(key);
+        }
+        for card_id in buried {
+            let sibling = CardKey {
+                deck_id: key.deck_id,
+                card_id,
+            };
+            self.remove_from_queues(sibling, now);
+        }
+        if let Mode::Filtered { .. } = self.mode {
+            self.return_home_unless_learning(key);
+        }
+        self.requeue_learning(key, now);
+
+        let duration_ms = (now - self.shown_at)
+            .num_milliseconds()
+            .clamp(0, u32::MAX as i64);
+        self.update(now);
+        let log = ReviewLogEntry {
+            deck_id: key.deck_id,
+            card_id: key.card_id,
+            note_id,
+            template,
+            cloze,
+            time: now,
+            rating,
+            elapsed_days: scheduled.info.review_log.elapsed_days,
+            state_before,
+            state_after: scheduled.info.card.state,
+            duration_ms: duration_ms as u32,
+        };
+        Some(Graded {
+            key,
+            undo: undo.map(|step| step.with_log(Some(log.clone()))),
+            log: Some(log),
+        })
+    }
+
+    /// A preview grade: Again shows the card again soon, the others send it home.
+    fn grade_preview(&mut self, rating: Rating, now: DateTime<Utc>) -> Option<Graded> {
+        let key = self.current.take()?;
+        self.preview = None;
+        let undo = self.undo_step(UndoAction::Grade, key);
+        if rating == Rating::Again {
+            let due = now + Duration::minutes(PREVIEW_AGAIN_MINUTES);
+            let position = self.learning.partition_point(|(d, _)| *d <= due);
+            self.learning.insert(position, (due, key));
+        } else if let Some(card) = self
+            .deck_mut(key.deck_id)
+            .and_then(|d| d.card_mut(key.card_id))
+        {
+            card.filtered = None;
+        }
+        self.update(now);
+        Some(Graded {
+            key,
+            log: None,
+            undo,
+        })
+    }
+
+    /// A rescheduled card stays in the filtered deck while it is on learning steps.
+    fn return_home_unless_learning(&mut self, key: CardKey) {
+        if let Some(card) = self
+            .deck_mut(key.deck_id)
+            .and_then(|d| d.card_mut(key.card_id))
+        {
+            if !matches!(card.state(), State::Learning | State::Relearning) {
+                card.filtered = None;
+            }
+        }
+    }
+
+    /// Puts a card on a (re)learning step due today into the intraday queue.
+    fn requeue_learning(&mut self, key: CardKey, now: DateTime<Utc>) {
+        let Some(card) = self.card(key) else {
+            return;
+        };
+        let is_learning = matches!(card.state(), State::Learning | State::Relearning);
+        if card.suspended || !is_learning || card.fsrs.due >= self.day.end(now) {
+            return;
+        }
+        let due = card.fsrs.due;
+        if self.current != Some(key) && !self.learning.iter().any(|(_, k)| *k == key) {
+            let position = self.learning.partition_point(|(d, _)| *d <= due);
+            self.learning.insert(position, (due, key));
+        }
+    }
+
+    fn remove_from_queues(&mut self, key: CardKey, now: DateTime<Utc>) {
+        self.queue.retain(|k| *k != key);
+        self.learning.retain(|(_, k)| *k != key);
+        if self.current == Some(key) {
+            self.current = None;
+            self.preview = None;
+            self.update(now);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Undo of grades, buries, suspends and note edits.
+//!
+//! A step keeps the note and all of its cards as they were before the change, so undoing puts
+//! back the exact FSRS state and whatever else the change touched (buried siblings, the leech tag).
+//! The history is shared by every view and kept in RAM only: it is gone after a reboot.
+
+use std::sync::Mutex;
+
+use crate::logic::{
+    deck::{
+        card::{Card, CardId},
+        model::{DayCounts, Deck, DeckId},
+        note::{Note, NoteId},
+    },
+    review::{review_log::ReviewLogEntry, session::CardKey},
+};
+
+/// Older steps are forgotten, each one holds a whole note.
+pub const MAX_UNDO_STEPS: usize = 30;
+
+static HISTORY: Mutex<Vec<UndoStep>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoAction {
+    Grade,
+    Bury,
+    Suspend,
+    Unsuspend,
+    Reset,
+    EditNote,
+}
+
+impl UndoAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UndoAction::Grade => "grade",
+            UndoAction::Bury => "bury",
+            UndoAction::Suspend => "suspend",
+            UndoAction::Unsuspend => "unsuspend",
+            UndoAction::Reset => "reset",
+            UndoAction::EditNote => "note edit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoStep {
+    pub action: UndoAction,
+    pub deck_id: DeckId,
+    /// The card the change was made to, `None` for a note edit.
+    pub card_id: Option<CardId>,
+    note: Note,
+    cards: Vec<Card>,
+    today: DayCounts,
+    /// Journal entry written by a grade, taken out of the review log on undo.
+    pub log: Option<ReviewLogEntry>,
+}
+
+impl UndoStep {
+    /// Remembers the note and its cards before `action` changes them.
+    /// `None` if the deck has no such note.
+    pub fn before(
+        action: UndoAction,
+        deck: &Deck,
+        note_id: NoteId,
+        card_id: Option<CardId>,
+    ) -> Option<Self> {
+        Some(Self {
+            action,
+            deck_id: deck.info.id,
+            card_id,
+            note: deck.note(note_id)?.clone(),
+            cards: deck.cards_of_note(note_id).cloned().collect(),
+            today: deck.today,
+            log: None,
+        })
+    }
+
+    /// Snapshot of the note of a card.
+    pub fn before_card(action: UndoAction, deck: &Deck, card_id: CardId) -> Option<Self> {
+        let note_id = deck.card(card_id)?.note_id;
+        Self::before(action, deck, note_id, Some(card_id))
+    }
+
+    pub fn with_log(self, log: Option<ReviewLogEntry>) -> Self {
+        Self { log, ..self }
+    }
+
+    pub fn card_key(&self) -> Option<CardKey> {
+        self.card_id.map(|card_id| CardKey {
+            deck_id: self.deck_id,
+            card_id,
+        })
+    }
+
+    /// Puts the note and its cards back as they were. A grade also gives back its place
+    /// in the daily limits. Returns false if it is not the deck of the step.
+    pub fn restore(&self, deck: &mut Deck) -> bool {
+        if deck.info.id != self.deck_id {
+            return false;
+        }
+        deck.restore_note(self.note.clone(), &self.cards);
+        if self.action == UndoAction::Grade {
+            deck.today = self.today;
+        }
+        true
+    }
+}
+
+/// Remembers a change, the oldest step goes once there are `MAX_UNDO_STEPS`.
+pub fn push_undo(step: UndoStep) {
+    if let Ok(mut history) = HISTORY.lock() {
+        push_step(&mut history, step);
+    }
+}
+
+fn push_step(history: &mut Vec<UndoStep>, step: UndoStep) {
+    if history.len() >= MAX_UNDO_STEPS {
+        history.remove(0);
+    }
+    history.push(step);
+}
+
+/// Takes the latest change off the history.
+pub fn pop_undo() -> Option<UndoStep> {
+    HISTORY.lock().ok()?.pop()
+}
+
+/// What Ctrl+Z would undo.
+pub fn last_undo_action() -> Option<UndoAction> {
+    HISTORY.lock().ok()?.last().map(|step| step.action)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+    use rs_fsrs::{Rating, State};
+
+    use super::*;
+    use crate::logic::deck::{
+        deck_preset::DeckPreset,
+        model::{DeckInfo, LEECH_TAG},
+    };
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 9, 1, 9, 0, 0).unwrap()
+    }
+
+    /// A note with a forward and a reverse card.
+    fn deck() -> Deck {
+        let mut deck = Deck::new(DeckInfo {
+            id: 3,
+            name: "Words".into(),
+        });
+        for template in deck.options.templates.iter_mut() {
+            template.enabled = true;
+        }
+        deck.add_note(vec!["дом".into(), "house".into()], now());
+        deck
+    }
+
+    #[test]
+    fn grade_is_taken_back_with_everything_it_touched() {
+        let mut deck = deck();
+        let (card_id, sibling_id) = (deck.cards()[0].id, deck.cards()[1].id);
+        let today = 20;
+        deck.count_studied(today, State::Review);
+        let before = deck.clone();
+
+        let step = UndoStep::before_card(UndoAction::Grade, &deck, card_id).unwrap();
+        let scheduler = DeckPreset::new(DeckPreset::DEFAULT_ID, "Default").scheduler();
+        let scheduled = scheduler.next(deck.card(card_id).unwrap(), now(), Rating::Easy);
+        deck.apply_review(card_id, &scheduled);
+        deck.count_studied(today, State::New);
+        assert_eq!(deck.bury_siblings(card_id, today), [sibling_id]);
+        deck.mark_leech(card_id);
+        assert!(deck.card(card_id).unwrap().suspended);
+        assert!(deck.notes()[0].has_tag(LEECH_TAG));
+        assert_eq!(deck.today.new, 1);
+
+        assert_eq!(step.card_key().map(|key| key.card_id), Some(card_id));
+        assert!(step.restore(&mut deck));
+        assert_eq!(deck, before);
+        assert_eq!(deck.card(card_id).unwrap().state(), State::New);
+        assert!(!deck.card(sibling_id).unwrap().is_buried(today));
+        assert!(!deck.notes()[0].has_tag(LEECH_TAG));
+        assert_eq!((deck.today.new, deck.today.review), (0, 1));
+    }
+
+    #[test]
+    fn only_a_grade_gives_back_the_daily_counts() {
+        let mut deck = deck();
+        let card_id = deck.cards()[0].id;
+        let step = UndoStep::before_card(UndoAction::Suspend, &deck, card_id).unwrap();
+        deck.set_suspended(card_id, true);
+        deck.count_studied(5, State::New);
+        assert!(step.restore(&mut deck));
+        assert!(!deck.card(card_id).unwrap().suspended);
+        assert_eq!(deck.today.new, 1);
+
+        let mut other = deck.clone();
+        other.info.id = 4;
+        assert!(!step.restore(&mut other));
+        assert!(UndoStep::before_card(UndoAction::Bury, &deck, 99).is_none());
+    }
+
+    #[test]
+    fn note_edit_drops_the_cards_it_generated() {
+        let mut deck = deck();
+        deck.options.templates[1].enabled = false;
+        deck.sync_cards(now());
+        let note_id = deck.notes()[0].id;
+        let before = deck.clone();
+
+        let step = UndoStep::before(UndoAction::EditNote, &deck, note_id, None).unwrap();
+        deck.options.templates[1].enabled = true;
+        deck.update_note(note_id, vec!["дом".into(), "home".into()], now());
+        assert_eq!(deck.cards().len(), 2);
+
+        assert_eq!(step.card_key(), None);
+        assert!(step.restore(&mut deck));
+        assert_eq!(deck.notes(), before.notes());
+        assert_eq!(deck.cards(), before.cards());
+    }
+
+    #[test]
+    fn history_keeps_the_latest_steps() {
+        let deck = deck();
+        let card_id = deck.cards()[0].id;
+        let mut history = Vec::new();
+        for i in 0..MAX_UNDO_STEPS + 5 {
+            let step = UndoStep::before_card(UndoAction::Grade, &deck, card_id).unwrap();
+            push_step(
+                &mut history,
+                UndoStep {
+                    deck_id: i as DeckId,
+                    ..step
+                },
+            );
+        }
+        assert_eq!(history.len(), MAX_UNDO_STEPS);
+        assert_eq!(history[0].deck_id, 5);
+        assert_eq!(
+            history.last().unwrap().deck_id,
+            (MAX_UNDO_STEPS + 4) as DeckId
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Pages of the card browser. Decks are searched line by line and only one page of rows
+//! is kept, the next page is the rows sorting right after the last row of the current one.
+
+use chrono::{DateTime, Utc};
+use rs_fsrs::State;
+
+use crate::logic::{
+    deck::{
+        card::{Card, CardId},
+        filtered_deck::SortKey,
+        model::{DeckId, DeckInfo},
+        note::{Note, NoteId},
+    },
+    review::session::CardKey,
+};
+
+/// Longest sort field text kept in a row, the screen shows less anyway.
+pub const SORT_FIELD_CHARS: usize = 40;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BrowserSort {
+    #[default]
+    Due,
+    Interval,
+    Difficulty,
+    Lapses,
+    /// When the note was added.
+    Created,
+}
+
+impl BrowserSort {
+    pub const ALL: [BrowserSort; 5] = [
+        BrowserSort::Due,
+        BrowserSort::Interval,
+        BrowserSort::Difficulty,
+        BrowserSort::Lapses,
+        BrowserSort::Created,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrowserSort::Due => "due",
+            BrowserSort::Interval => "interval",
+            BrowserSort::Difficulty => "difficulty",
+            BrowserSort::Lapses => "lapses",
+            BrowserSort::Created => "created",
+        }
+    }
+
+    /// Ties are broken by deck and card id in either direction, so every card has its own place.
+    pub fn sort_key(
+        &self,
+        descending: bool,
+        deck_id: DeckId,
+        note: Option<&Note>,
+        card: &Card,
+    ) -> SortKey {
+        let value = match self {
+            BrowserSort::Due => card.fsrs.due.timestamp(),
+            BrowserSort::Interval => card.fsrs.scheduled_days,
+            BrowserSort::Difficulty => (card.fsrs.difficulty * 1000.0) as i64,
+            BrowserSort::Lapses => card.fsrs.lapses as i64,
+            BrowserSort::Created => note.map_or(0, |n| n.created.timestamp()),
+        };
+        let value = if descending { -value } else { value };
+        (value, deck_id as i64, card.id)
+    }
+}
+
+/// What the browser shows of a card.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowserRow {
+    pub key: SortKey,
+    pub deck_id: DeckId,
+    pub card_id: CardId,
+    pub note_id: NoteId,
+    pub sort_field: String,
+    pub state: State,
+    pub suspended: bool,
+    pub due: DateTime<Utc>,
+    pub last_review: DateTime<Utc>,
+}
+
+impl BrowserRow {
+    pub fn card_key(&self) -> CardKey {
+        CardKey {
+            deck_id: self.deck_id,
+            card_id: self.card_id,
+        }
+    }
+}
+
+/// Collects one page of search matches in browser order, keeping no more than twice
+/// the page size in memory.
+pub struct BrowserPage {
+    sort: BrowserSort,
+    descending: bool,
+    /// Key of the last row of the previous page, `None` for the first page.
+    after: Option<SortKey>,
+    size: usize,
+    rows: Vec<BrowserRow>,
+    matches: usize,
+}
+
+impl BrowserPage {
+    pub fn new(sort: BrowserSort, descending: bool, after: Option<SortKey>, size: usize) -> Self {
+        Self {
+            sort,
+            descending,
+            after,
+            size: size.max(1),
+            rows: Vec::new(),
+            matches: 0,
+        }
+    }
+
+    pub fn push_card(&mut self, deck: &DeckInfo, note: Option<&Note>, card: &Card) {
+        self.matches += 1;
+        let key = self.sort.sort_key(self.descending, deck.id, note, card);
+        if self.after.is_some_and(|after| key <= after) {
+            return;
+        }
+        let sort_field = note.map_or("", |n| n.sort_field());
+        self.rows.push(BrowserRow {
+            key,
+            deck_id: deck.id,
+            card_id: card.id,
+            note_id: card.note_id,
+            sort_field: sort_field.chars().take(SORT_FIELD_CHARS).collect(),
+            state: card.state(),
+            suspended: card.suspended,
+            due: card.fsrs.due,
+            last_review: card.fsrs.last_review,
+        });
+        if self.rows.len() >= 2 * self.size {
+            self.trim();
+        }
+    }
+
+    /// The rows of the page and how many cards matched in all.
+    pub fn finish(mut self) -> (Vec<BrowserRow>, usize) {
+        self.trim();
+        (self.rows, self.matches)
+    }
+
+    fn trim(&mut self) {
+        self.rows.sort_unstable_by_key(|row| row.key);
+        self.rows.truncate(self.size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::logic::deck::model::Deck;
+
+    /// Two decks of five cards, the cards are due on two days only so most keys tie on the value.
+    fn decks() -> Vec<Deck> {
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 9, 0, 0).unwrap();
+        (1..=2)
+            .map(|id| {
+                let mut deck = Deck::new(DeckInfo {
+                    id,
+                    name: format!("Deck {}", id),
+                });
+                for i in 0..5 {
+                    let note_id = deck.add_note(vec![format!("word {}", i), "back".into()], now);
+                    let card_id = deck.cards_of_note(note_id).next().unwrap().id;
+                    let card = deck.card_mut(card_id).unwrap();
+                    card.fsrs.due = now + Duration::days(i % 2);
+                    card.fsrs.lapses = i as i32;
+                }
+                deck
+            })
+            .collect()
+    }
+
+    fn page(
+        decks: &[Deck],
+        sort: BrowserSort,
+        descending: bool,
+        after: Option<SortKey>,
+    ) -> (Vec<BrowserRow>, usize) {
+        let mut page = BrowserPage::new(sort, descending, after, 3);
+        for deck in decks {
+            for card in deck.cards() {
+                page.push_card(&deck.info, deck.note(card.note_id), card);
+            }
+        }
+        page.finish()
+    }
+
+    /// Every card in browser order, page by page.
+    fn all_pages(decks: &[Deck], sort: BrowserSort, descending: bool) -> Vec<(DeckId, CardId)> {
+        let mut cards = Vec::new();
+        let mut after = None;
+        loop {
+            let (rows, matches) = page(decks, sort, descending, after);
+            assert_eq!(matches, 10);
+            assert!(rows.len() <= 3);
+            let Some(last) = rows.last() else {
+                return cards;
+            };
+            after = Some(last.key);
+            cards.extend(rows.iter().map(|row| (row.deck_id, row.card_id)));
+        }
+    }
+
+    #[test]
+    fn pages_go_through_every_card_once() {
+        let decks = decks();
+        for sort in BrowserSort::ALL {
+            for descending in [false, true] {
+                let cards = all_pages(&decks, sort, descending);
+                let mut expected: Vec<(SortKey, DeckId, CardId)> = decks
+                    .iter()
+                    .flat_map(|deck| {
+                        deck.cards().iter().map(|card| {
+                            let note = deck.note(card.note_id);
+                            let key = sort.sort_key(descending, deck.info.id, note, card);
+                            (key, deck.info.id, card.id)
+                        })
+                    })
+                    .collect();
+                expected.sort();
+                let expected: Vec<(DeckId, CardId)> = expected
+                    .into_iter()
+                    .map(|(_, deck, card)| (deck, card))
+                    .collect();
+                assert_eq!(cards, expected, "{} descending {}", sort.name(), descending);
+            }
+        }
+    }
+
+    #[test]
+    fn descending_reverses_the_value_only() {
+        let decks = decks();
+        let (rows, _) = page(&decks, BrowserSort::Lapses, true, None);
+        let lapses: Vec<(i32, DeckId, CardId)> = rows
+            .iter()
+            .map(|row| {
+                let card = decks[row.deck_id as usize - 1].card(row.card_id).unwrap();
+                (card.fsrs.lapses, row.deck_id, row.card_id)
+            })
+            .collect();
+        // ties stay in deck and card order
+        assert_eq!(lapses, [(4, 1, 5), (4, 2, 5), (3, 1, 4)]);
+        assert_eq!(rows[0].sort_field, "word 4");
+    }
+}
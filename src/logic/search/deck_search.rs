@@ -0,0 +1,148 @@
+use crate::logic::{
+    deck::{card::Card, deck_file::DeckRecord, model::DeckInfo, note::Note},
+    search::query::{Query, SearchContext},
+};
+
+/// Runs a query over deck files fed line by line, so a search over all decks never holds
+/// more than one note. Relies on every card following its note, as `write_deck` writes them;
+/// a card away from its note is searched as if the note was gone.
+pub struct DeckSearch<'a> {
+    query: &'a Query,
+    deck: Option<DeckInfo>,
+    note: Option<Note>,
+    skipped_lines: usize,
+}
+
+impl<'a> DeckSearch<'a> {
+    pub fn new(query: &'a Query) -> Self {
+        Self {
+            query,
+            deck: None,
+            note: None,
+            skipped_lines: 0,
+        }
+    }
+
+    /// Feeds the next line of a deck file and calls `on_match` if it is a matching card.
+    /// The deck record of the next file starts a new deck, damaged lines are counted and skipped.
+    pub fn push_line(
+        &mut self,
+        line: &str,
+        context: &SearchContext,
+        mut on_match: impl FnMut(&DeckInfo, Option<&Note>, &Card),
+    ) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let record = match DeckRecord::parse_line(line) {
+            Ok(record) => record,
+            Err(e) => {
+                log::warn!("skip deck line: {:?}", e);
+                self.skipped_lines += 1;
+                return;
+            }
+        };
+        match record {
+            DeckRecord::Deck(header) => {
+                self.deck = Some(header.info);
+                self.note = None;
+            }
+            DeckRecord::Note(note) => self.note = Some(note),
+            DeckRecord::Card(card) => {
+                let Some(deck) = &self.deck else {
+                    self.skipped_lines += 1;
+                    return;
+                };
+                let note = self.note.as_ref().filter(|n| n.id == card.note_id);
+                if self.query.matches(deck, note, &card, context) {
+                    on_match(deck, note, &card);
+                }
+            }
+        }
+    }
+
+    pub fn skipped_lines(&self) -> usize {
+        self.skipped_lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone, Utc};
+
+    use super::*;
+    use crate::logic::{
+        deck::{deck_file::write_deck, model::Deck},
+        search::history::ReviewHistory,
+        study_day::StudyDay,
+    };
+
+    /// Two deck files one after another, the note of the second card of the first deck is lost.
+    fn deck_files() -> String {
+        let now = Utc.with_ymd_and_hms(2024, 5, 20, 12, 0, 0).unwrap();
+        let mut first = Deck::new(DeckInfo {
+            id: 1,
+            name: "Animals".into(),
+        });
+        first.add_note(vec!["кошка".into(), "cat".into()], now);
+        first.add_note(vec!["собака".into(), "dog".into()], now);
+        let mut second = Deck::new(DeckInfo {
+            id: 2,
+            name: "Food".into(),
+        });
+        second.add_note(vec!["молоко".into(), "milk".into()], now);
+
+        let first = write_deck(&first).unwrap();
+        let lost_note = first.lines().nth(3).unwrap();
+        assert!(lost_note.contains("собака"));
+        let first = first.replace(&format!("{}\n", lost_note), "");
+        format!("{}{{\"card\":\n{}", first, write_deck(&second).unwrap())
+    }
+
+    /// Fronts of the matching cards by deck, `?` for a card without its note.
+    fn search(query: &str) -> (Vec<(String, String)>, usize) {
+        let query = Query::parse(query).unwrap();
+        let history = ReviewHistory::default();
+        let context = SearchContext {
+            day: StudyDay::new(FixedOffset::east_opt(0).unwrap(), 4),
+            now: Utc.with_ymd_and_hms(2024, 5, 20, 12, 0, 0).unwrap(),
+            history: &history,
+        };
+        let mut search = DeckSearch::new(&query);
+        let mut found = Vec::new();
+        for line in deck_files().lines() {
+            search.push_line(line, &context, |deck, note, _| {
+                let front = note.map_or("?", |n| n.field(0));
+                found.push((deck.name.clone(), front.to_string()));
+            });
+        }
+        (found, search.skipped_lines())
+    }
+
+    fn found(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(deck, front)| (deck.to_string(), front.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn searches_every_deck_of_the_stream() {
+        let (cards, skipped) = search("is:new");
+        assert_eq!(
+            cards,
+            found(&[("Animals", "кошка"), ("Animals", "?"), ("Food", "молоко")])
+        );
+        assert_eq!(skipped, 1);
+
+        let (cards, _) = search("deck:food or cat");
+        assert_eq!(cards, found(&[("Animals", "кошка"), ("Food", "молоко")]));
+    }
+
+    #[test]
+    fn card_without_its_note_matches_no_note_terms() {
+        assert_eq!(search("собака").0, found(&[]));
+        assert_eq!(search("dog or tag:x").0, found(&[]));
+        assert_eq!(search("-cat deck:animals").0, found(&[("Animals", "?")]));
+    }
+}
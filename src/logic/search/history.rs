@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use rs_fsrs::Rating;
+
+use crate::logic::{
+    deck::{card::CardId, model::DeckId},
+    review::review_log::ReviewLogEntry,
+    study_day::{DayNumber, StudyDay},
+};
+
+/// Grades by card and study day, what `rated:` terms look up.
+#[derive(Debug, Clone, Default)]
+pub struct ReviewHistory {
+    grades: HashMap<(DeckId, CardId), Vec<(DayNumber, Rating)>>,
+}
+
+impl ReviewHistory {
+    /// Keeps the grades given on `since` or later.
+    pub fn new(entries: &[ReviewLogEntry], day: &StudyDay, since: DayNumber) -> Self {
+        let mut grades: HashMap<_, Vec<_>> = HashMap::new();
+        for entry in entries {
+            let graded_on = day.day_number(entry.time);
+            if graded_on >= since {
+                grades
+                    .entry((entry.deck_id, entry.card_id))
+                    .or_default()
+                    .push((graded_on, entry.rating));
+            }
+        }
+        Self { grades }
+    }
+
+    /// Whether the card was graded on `since` or later, with `rating` if one is given.
+    pub fn is_rated(
+        &self,
+        deck_id: DeckId,
+        card_id: CardId,
+        since: DayNumber,
+        rating: Option<Rating>,
+    ) -> bool {
+        self.grades.get(&(deck_id, card_id)).is_some_and(|grades| {
+            grades
+                .iter()
+                .any(|(day, r)| *day >= since && rating.map_or(true, |wanted| *r == wanted))
+        })
+    }
+}
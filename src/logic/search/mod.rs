@@ -0,0 +1,4 @@
+pub mod browser;
+pub mod deck_search;
+pub mod history;
+pub mod query;
\ No newline at end of file
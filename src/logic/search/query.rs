@@ -0,0 +1,761 @@
+//! Card search, shared by filtered decks, the browser and stats: `tag:verbs (is:due or is:new) -is:suspended`.
+//!
+//! Terms separated by spaces must all match, `or` between them makes either one enough and
+//! binds weaker, so `a b or c` is `(a b) or c`. Parentheses group, `-` in front of a term or
+//! a group negates it, quotes keep spaces in a term: `"to be"`, `tag:"phrasal verbs"`.
+//! In text `*` stands for any number of characters and `_` for one, `\*` and `\_` are literal.
+//!
+//! | term | matches |
+//! |---|---|
+//! | `word`, `wo*d` | a field of the note contains the text, case is ignored |
+//! | `front:кот`, `back:cat*`, `extra:_` | the whole field (extra: any field after the back) |
+//! | `tag:verbs` | the note has the tag or one nested in it, like `verbs::motion` |
+//! | `deck:Russian` | cards of the deck or of a deck nested in it, `deck:Rus*` matches the full name |
+//! | `is:new`, `is:learn`, `is:review` | cards in that state |
+//! | `is:due` | reviews and learning cards due today |
+//! | `is:suspended`, `is:buried`, `is:leech` | |
+//! | `rated:3`, `rated:3:1` | graded in the last 3 days, optionally only with Again (1) .. Easy (4) |
+//! | `prop:ivl>10` | a property compared with `<`, `<=`, `=`, `!=`, `>=` or `>`: `ivl` (days), `due` (days from today, negative when overdue), `lapses`, `reps`, `s` (stability), `d` (difficulty) |
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rs_fsrs::{Rating, State};
+
+use crate::logic::{
+    deck::{
+        card::Card,
+        deck_tree,
+        model::{Deck, DeckInfo, LEECH_TAG},
+        note::{Note, NoteId},
+    },
+    search::history::ReviewHistory,
+    study_day::StudyDay,
+};
+
+/// Longest look back of `rated:`, the review history is kept for that long.
+pub const MAX_RATED_DAYS: u32 = 365;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardIs {
+    New,
+    Learn,
+    Review,
+    Due,
+    Suspended,
+    Buried,
+    Leech,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteField {
+    Front,
+    Back,
+    /// Any field after the back.
+    Extra,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    /// Days between the last review and the due date.
+    Interval,
+    /// Days from today to the due date, new cards have none.
+    Due,
+    Lapses,
+    Reps,
+    Stability,
+    Difficulty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Text(Pattern),
+    Field(NoteField, Pattern),
+    Tag(Pattern),
+    Deck(Pattern),
+    Is(CardIs),
+    Rated { days: u32, rating: Option<Rating> },
+    Prop(Property, Comparison, f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// Matches when all match, an empty one matches every card.
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatternChar {
+    Char(char),
+    /// `*`
+    Any,
+    /// `_`
+    One,
+}
+
+/// Lowercased text to look for with its wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    chars: Vec<PatternChar>,
+}
+
+impl Pattern {
+    pub fn new(text: &str) -> Self {
+        let mut chars = Vec::new();
+        let mut escaped = false;
+        for c in text.to_lowercase().chars() {
+            chars.push(match c {
+                '\\' if !escaped => {
+                    escaped = true;
+                    continue;
+                }
+                '*' if !escaped => PatternChar::Any,
+                '_' if !escaped => PatternChar::One,
+                c => PatternChar::Char(c),
+            });
+            escaped = false;
+        }
+        Self { chars }
+    }
+
+    /// The text when there are no wildcards.
+    fn literal(&self) -> Option<String> {
+        self.chars
+            .iter()
+            .map(|c| match c {
+                PatternChar::Char(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The whole text matches.
+    pub fn matches(&self, text: &str) -> bool {
+        let text: Vec<char> = text.to_lowercase().chars().collect();
+        glob(&self.chars, &text)
+    }
+
+    /// Some part of the text matches.
+    pub fn is_found_in(&self, text: &str) -> bool {
+        match self.literal() {
+            Some(literal) => text.to_lowercase().contains(&literal),
+            None => {
+                let text: Vec<char> = text.to_lowercase().chars().collect();
+                let mut chars = Vec::with_capacity(self.chars.len() + 2);
+                chars.push(PatternChar::Any);
+                chars.extend_from_slice(&self.chars);
+                chars.push(PatternChar::Any);
+                glob(&chars, &text)
+            }
+        }
+    }
+}
+
+/// Wildcard match with backtracking to the last `*`.
+fn glob(pattern: &[PatternChar], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut last_any: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(PatternChar::Any) => {
+                last_any = Some((p, t));
+                p += 1;
+            }
+            Some(PatternChar::One) => {
+                p += 1;
+                t += 1;
+            }
+            Some(PatternChar::Char(c)) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match last_any {
+                // let the `*` take one more character
+                Some((any, taken)) => {
+                    last_any = Some((any, taken + 1));
+                    p = any + 1;
+                    t = taken + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == PatternChar::Any)
+}
+
+/// What terms need beyond the card itself.
+pub struct SearchContext<'a> {
+    pub day: StudyDay,
+    pub now: DateTime<Utc>,
+    pub history: &'a ReviewHistory,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    root: Node,
+}
+
+impl Default for Query {
+    /// Matches every card.
+    fn default() -> Self {
+        Self {
+            root: Node::And(Vec::new()),
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        if parser.tokens.is_empty() {
+            return Ok(Self::default());
+        }
+        let root = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(Self { root }),
+            Some(Token::Close) => Err(anyhow!("unmatched )")),
+            Some(token) => Err(anyhow!("unexpected {:?}", token)),
+        }
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// Whether the review log has to be loaded for `rated:` terms.
+    pub fn needs_history(&self) -> bool {
+        self.root.needs_history()
+    }
+
+    /// `note` is the note of the card, `None` if it is gone.
+    pub fn matches(
+        &self,
+        deck: &DeckInfo,
+        note: Option<&Note>,
+        card: &Card,
+        context: &SearchContext,
+    ) -> bool {
+        self.root.matches(deck, note, card, context)
+    }
+
+    /// Matching cards of a deck in memory, in deck order. `DeckSearch` does the same
+    /// for a deck file read line by line.
+    pub fn search<'d>(&self, deck: &'d Deck, context: &SearchContext) -> Vec<&'d Card> {
+        let notes: HashMap<NoteId, &Note> = deck.notes().iter().map(|n| (n.id, n)).collect();
+        deck.cards()
+            .iter()
+            .filter(|card| {
+                let note = notes.get(&card.note_id).copied();
+                self.matches(&deck.info, note, card, context)
+            })
+            .collect()
+    }
+}
+
+impl Node {
+    fn needs_history(&self) -> bool {
+        match self {
+            Node::And(nodes) | Node::Or(nodes) => nodes.iter().any(Node::needs_history),
+            Node::Not(node) => node.needs_history(),
+            Node::Term(term) => matches!(term, Term::Rated { .. }),
+        }
+    }
+
+    fn matches(
+        &self,
+        deck: &DeckInfo,
+        note: Option<&Note>,
+        card: &Card,
+        context: &SearchContext,
+    ) -> bool {
+        match self {
+            Node::And(nodes) => nodes.iter().all(|n| n.matches(deck, note, card, context)),
+            Node::Or(nodes) => nodes.iter().any(|n| n.matches(deck, note, card, context)),
+            Node::Not(node) => !node.matches(deck, note, card, context),
+            Node::Term(term) => term.matches(deck, note, card, context),
+        }
+    }
+}
+
+impl Term {
+    fn matches(
+        &self,
+        deck: &DeckInfo,
+        note: Option<&Note>,
+        card: &Card,
+        context: &SearchContext,
+    ) -> bool {
+        match self {
+            Term::Text(pattern) => {
+                note.is_some_and(|n| n.fields.iter().any(|f| pattern.is_found_in(f)))
+            }
+            Term::Field(field, pattern) => note.is_some_and(|n| match field {
+                NoteField::Front => pattern.matches(n.field(0)),
+                NoteField::Back => pattern.matches(n.field(1)),
+                NoteField::Extra => n.fields.iter().skip(2).any(|f| pattern.matches(f)),
+            }),
+            Term::Tag(pattern) => {
+                note.is_some_and(|n| n.tags.iter().any(|t| is_tag_in(t, pattern)))
+            }
+            Term::Deck(pattern) => match pattern.literal() {
+                Some(name) => deck_tree::is_in_subtree(&deck.name.to_lowercase(), &name),
+                None => pattern.matches(&deck.name),
+            },
+            Term::Is(is) => {
+                let today = context.day.day_number(context.now);
+                match is {
+                    CardIs::New => card.is_new(),
+                    CardIs::Learn => matches!(card.state(), State::Learning | State::Relearning),
+                    CardIs::Review => card.state() == State::Review,
+                    CardIs::Due => !card.is_new() && card.fsrs.due < context.day.end(context.now),
+                    CardIs::Suspended => card.suspended,
+                    CardIs::Buried => card.is_buried(today),
+                    CardIs::Leech => note.is_some_and(|n| n.has_tag(LEECH_TAG)),
+                }
+            }
+            Term::Rated { days, rating } => {
+                let since = context.day.day_number(context.now) - (*days as i64 - 1);
+                context.history.is_rated(deck.id, card.id, since, *rating)
+            }
+            Term::Prop(property, comparison, value) => property
+                .value(card, context)
+                .is_some_and(|actual| comparison.holds(actual, *value)),
+        }
+    }
+}
+
+impl Property {
+    fn value(&self, card: &Card, context: &SearchContext) -> Option<f64> {
+        let fsrs = &card.fsrs;
+        match self {
+            Property::Interval => Some(fsrs.scheduled_days as f64),
+            Property::Due if card.is_new() => None,
+            Property::Due => {
+                let today = context.day.day_number(context.now);
+                Some((context.day.day_number(fsrs.due) - today) as f64)
+            }
+            Property::Lapses => Some(fsrs.lapses as f64),
+            Property::Reps => Some(fsrs.reps as f64),
+            Property::Stability => Some(fsrs.stability),
+            Property::Difficulty => Some(fsrs.difficulty),
+        }
+    }
+}
+
+impl Comparison {
+    fn holds(&self, actual: f64, value: f64) -> bool {
+        match self {
+            Comparison::Less => actual < value,
+            Comparison::LessOrEqual => actual <= value,
+            Comparison::Equal => actual == value,
+            Comparison::NotEqual => actual != value,
+            Comparison::GreaterOrEqual => actual >= value,
+            Comparison::Greater => actual > value,
+        }
+    }
+}
+
+/// `verbs` takes in `Verbs` and `verbs::motion`, with wildcards the whole tag has to match.
+fn is_tag_in(tag: &str, pattern: &Pattern) -> bool {
+    match pattern.literal() {
+        Some(wanted) => deck_tree::is_in_subtree(&tag.to_lowercase(), &wanted),
+        None => pattern.matches(tag),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Or,
+    And,
+    Not,
+    Word(String),
+}
+
+/// Splits on whitespace and parentheses outside of quotes, the quotes are dropped.
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut is_quoted = false;
+    let mut in_quotes = false;
+    let finish_word = |tokens: &mut Vec<Token>, word: &mut String, is_quoted: &mut bool| {
+        let keyword = match word.to_lowercase().as_str() {
+            _ if *is_quoted => None,
+            "or" => Some(Token::Or),
+            "and" => Some(Token::And),
+            _ => None,
+        };
+        match keyword {
+            Some(keyword) => tokens.push(keyword),
+            None if !word.is_empty() || *is_quoted => {
+                tokens.push(Token::Word(std::mem::take(word)))
+            }
+            None => {}
+        }
+        word.clear();
+        *is_quoted = false;
+    };
+
+    for c in text.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                is_quoted = true;
+            }
+            _ if in_quotes => word.push(c),
+            '(' | ')' => {
+                finish_word(&mut tokens, &mut word, &mut is_quoted);
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            c if c.is_whitespace() => finish_word(&mut tokens, &mut word, &mut is_quoted),
+            '-' if word.is_empty() && !is_quoted => tokens.push(Token::Not),
+            c => word.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(anyhow!("unclosed quote"));
+    }
+    finish_word(&mut tokens, &mut word, &mut is_quoted);
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Node> {
+        let mut alternatives = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            alternatives.push(self.parse_and()?);
+        }
+        Ok(match alternatives.len() {
+            1 => alternatives.remove(0),
+            _ => Node::Or(alternatives),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Node> {
+        let mut all = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) | Some(Token::Or) => break,
+                Some(Token::And) if !all.is_empty() => self.pos += 1,
+                _ => all.push(self.parse_unary()?),
+            }
+        }
+        Ok(match all.len() {
+            0 => return Err(anyhow!("a search term is missing")),
+            1 => all.remove(0),
+            _ => Node::And(all),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Node> {
+        match self.next() {
+            Some(Token::Not) => Ok(Node::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let node = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(node),
+                    _ => Err(anyhow!("unclosed (")),
+                }
+            }
+            Some(Token::Word(word)) => parse_term(&word).map(Node::Term),
+            Some(token) => Err(anyhow!("unexpected {:?}", token)),
+            None => Err(anyhow!("a search term is missing")),
+        }
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term> {
+    let Some((key, value)) = word.split_once(':') else {
+        return Ok(Term::Text(Pattern::new(word)));
+    };
+    let needs_value = |term: Term| match value.is_empty() {
+        true => Err(anyhow!("{}: needs a value", key)),
+        false => Ok(term),
+    };
+    match key.to_lowercase().as_str() {
+        "tag" => needs_value(Term::Tag(Pattern::new(value))),
+        "deck" => needs_value(Term::Deck(Pattern::new(value))),
+        "front" => Ok(Term::Field(NoteField::Front, Pattern::new(value))),
+        "back" => Ok(Term::Field(NoteField::Back, Pattern::new(value))),
+        "extra" => Ok(Term::Field(NoteField::Extra, Pattern::new(value))),
+        "is" => parse_is(value).map(Term::Is),
+        "rated" => parse_rated(value),
+        "prop" => parse_prop(value),
+        // a colon in plain text, e.g. "note: ..."
+        _ => Ok(Term::Text(Pattern::new(word))),
+    }
+}
+
+fn parse_is(value: &str) -> Result<CardIs> {
+    match value.to_lowercase().as_str() {
+        "new" => Ok(CardIs::New),
+        "learn" => Ok(CardIs::Learn),
+        "review" => Ok(CardIs::Review),
+        "due" => Ok(CardIs::Due),
+        "suspended" => Ok(CardIs::Suspended),
+        "buried" => Ok(CardIs::Buried),
+        "leech" => Ok(CardIs::Leech),
+        _ => Err(anyhow!("unknown is:{}", value)),
+    }
+}
+
+fn parse_rated(value: &str) -> Result<Term> {
+    let (days, rating) = match value.split_once(':') {
+        Some((days, rating)) => (days, Some(rating)),
+        None => (value, None),
+    };
+    let days: u32 = days
+        .parse()
+        .ok()
+        .filter(|d| (1..=MAX_RATED_DAYS).contains(d))
+        .ok_or_else(|| anyhow!("rated: takes 1..{} days", MAX_RATED_DAYS))?;
+    let rating = match rating {
+        None => None,
+        Some("1") => Some(Rating::Again),
+        Some("2") => Some(Rating::Hard),
+        Some("3") => Some(Rating::Good),
+        Some("4") => Some(Rating::Easy),
+        Some(other) => return Err(anyhow!("rated: grade {} is not 1..4", other)),
+    };
+    Ok(Term::Rated { days, rating })
+}
+
+fn parse_prop(value: &str) -> Result<Term> {
+    let split = value
+        .find(['<', '>', '=', '!'])
+        .ok_or_else(|| anyhow!("prop:{} has no comparison", value))?;
+    let (name, rest) = value.split_at(split);
+    let property = match name.to_lowercase().as_str() {
+        "ivl" => Property::Interval,
+        "due" => Property::Due,
+        "lapses" => Property::Lapses,
+        "reps" => Property::Reps,
+        "s" => Property::Stability,
+        "d" => Property::Difficulty,
+        _ => return Err(anyhow!("unknown prop:{}", name)),
+    };
+    // two character operators first
+    let comparisons = [
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("!=", Comparison::NotEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+        ("=", Comparison::Equal),
+    ];
+    let (number, comparison) = comparisons
+        .iter()
+        .find_map(|(op, comparison)| Some((rest.strip_prefix(op)?, *comparison)))
+        .ok_or_else(|| anyhow!("prop:{} has no comparison", value))?;
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("prop:{} needs a number", value))?;
+    Ok(Term::Prop(property, comparison, number))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, FixedOffset, TimeZone};
+
+    use super::*;
+    use crate::logic::review::review_log::ReviewLogEntry;
+
+    fn text(word: &str) -> Node {
+        Node::Term(Term::Text(Pattern::new(word)))
+    }
+
+    fn root(query: &str) -> Node {
+        Query::parse(query).unwrap().root().clone()
+    }
+
+    #[test]
+    fn or_binds_weaker_than_and() {
+        assert_eq!(
+            root("a b or c"),
+            Node::Or(vec![Node::And(vec![text("a"), text("b")]), text("c")])
+        );
+        assert_eq!(
+            root("a and b OR c d"),
+            Node::Or(vec![
+                Node::And(vec![text("a"), text("b")]),
+                Node::And(vec![text("c"), text("d")]),
+            ])
+        );
+        assert_eq!(root("\"or\""), text("or"));
+    }
+
+    #[test]
+    fn parentheses_group_and_minus_negates() {
+        assert_eq!(
+            root("a (b or c)"),
+            Node::And(vec![text("a"), Node::Or(vec![text("b"), text("c")])])
+        );
+        assert_eq!(
+            root("-(a or b) -c"),
+            Node::And(vec![
+                Node::Not(Box::new(Node::Or(vec![text("a"), text("b")]))),
+                Node::Not(Box::new(text("c"))),
+            ])
+        );
+        assert_eq!(root("((a))"), text("a"));
+        assert_eq!(root("\"to be\""), text("to be"));
+        assert_eq!(root("  "), Node::And(Vec::new()));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let error = |query: &str| Query::parse(query).unwrap_err().to_string();
+        assert_eq!(error("a b)"), "unmatched )");
+        assert_eq!(error("(a or b))"), "unmatched )");
+        assert_eq!(error("tag:\"phrasal verbs"), "unclosed quote");
+        assert_eq!(error("(a b"), "unclosed (");
+        assert_eq!(error("a or"), "a search term is missing");
+        assert_eq!(error("is:old"), "unknown is:old");
+        assert!(Query::parse("rated:0").is_err());
+        assert!(Query::parse("prop:ivl").is_err());
+        assert!(Query::parse("prop:ivl>x").is_err());
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(Pattern::new("k*n").matches("Kitten"));
+        assert!(!Pattern::new("k*t").matches("kitten"));
+        assert!(Pattern::new("_at").matches("cat"));
+        assert!(!Pattern::new("_at").matches("at"));
+        assert!(Pattern::new("к_т").matches("кот"));
+        assert!(Pattern::new("*").matches(""));
+        assert!(Pattern::new("\\*").matches("*"));
+        assert!(!Pattern::new("\\*").matches("a"));
+        assert!(Pattern::new("c_t").is_found_in("a cat here"));
+        assert!(!Pattern::new("c_t").is_found_in("a ct here"));
+    }
+
+    #[test]
+    fn wildcards_backtrack() {
+        // the first `*` has to give back what it took
+        assert!(Pattern::new("*ab").matches("aab"));
+        assert!(Pattern::new("a*b*c").matches("abbbc"));
+        assert!(Pattern::new("*a*b").matches("xaxab"));
+        assert!(Pattern::new("*_b").matches("abb"));
+        assert!(!Pattern::new("a*b*c").matches("abcb"));
+        assert!(!Pattern::new("*a_").matches("xaxa"));
+    }
+
+    struct Fixture {
+        deck: Deck,
+        history: ReviewHistory,
+        day: StudyDay,
+        now: DateTime<Utc>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let now = Utc.with_ymd_and_hms(2024, 5, 20, 12, 0, 0).unwrap();
+            let day = StudyDay::new(FixedOffset::east_opt(0).unwrap(), 4);
+            let mut deck = Deck::new(DeckInfo {
+                id: 1,
+                name: "Words".into(),
+            });
+            for (front, interval) in [("кошка", 3), ("собака", 11), ("мышь", 30)] {
+                let note_id = deck.add_note(vec![front.into(), "".into()], now);
+                let card_id = deck.cards_of_note(note_id).next().unwrap().id;
+                let card = deck.card_mut(card_id).unwrap();
+                card.fsrs.state = State::Review;
+                card.fsrs.scheduled_days = interval;
+            }
+            let graded = |card: &Card, days_ago: i64, rating: Rating| ReviewLogEntry {
+                deck_id: 1,
+                card_id: card.id,
+                note_id: card.note_id,
+                template: card.template,
+                cloze: None,
+                time: now - Duration::days(days_ago),
+                rating,
+                elapsed_days: 0,
+                state_before: State::Review,
+                state_after: State::Review,
+                duration_ms: 0,
+            };
+            let cards = deck.cards();
+            let entries = [
+                graded(&cards[0], 6, Rating::Again),
+                graded(&cards[1], 8, Rating::Good),
+            ];
+            let today = day.day_number(now);
+            let history = ReviewHistory::new(&entries, &day, today - MAX_RATED_DAYS as i64);
+            Self {
+                deck,
+                history,
+                day,
+                now,
+            }
+        }
+
+        fn fronts(&self, query: &str) -> Vec<&str> {
+            let context = SearchContext {
+                day: self.day,
+                now: self.now,
+                history: &self.history,
+            };
+            Query::parse(query)
+                .unwrap()
+                .search(&self.deck, &context)
+                .iter()
+                .map(|card| self.deck.note(card.note_id).unwrap().field(0))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn interval_property() {
+        let fixture = Fixture::new();
+        assert_eq!(fixture.fronts("prop:ivl>10"), vec!["собака", "мышь"]);
+        assert_eq!(fixture.fronts("prop:ivl<=11"), vec!["кошка", "собака"]);
+        assert_eq!(fixture.fronts("prop:ivl=30"), vec!["мышь"]);
+        assert_eq!(fixture.fronts("-prop:ivl!=3"), vec!["кошка"]);
+    }
+
+    #[test]
+    fn rated_in_last_days() {
+        let fixture = Fixture::new();
+        assert!(Query::parse("rated:7").unwrap().needs_history());
+        assert_eq!(fixture.fronts("rated:7"), vec!["кошка"]);
+        assert_eq!(fixture.fronts("rated:9"), vec!["кошка", "собака"]);
+        assert_eq!(fixture.fronts("rated:9:3"), vec!["собака"]);
+        assert_eq!(fixture.fronts("rated:7:1 or мышь"), vec!["кошка", "мышь"]);
+    }
+}
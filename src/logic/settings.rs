@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use crate::logic::study_day::StudyDay;
+
+/// Device wide settings, stored in `settings.jsn` on the SD card.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// POSIX `TZ` value the device clock is set to, e.g. `GMT-3` for UTC+3.
+    pub tz: String,
+    /// Local hour the study day starts at.
+    pub rollover_hour: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tz: "GMT-3".to_string(),
+            rollover_hour: 4,
+        }
+    }
+}
+
+impl Settings {
+    pub const FILE_NAME: &'static str = "settings.jsn";
+
+    /// A `TZ` the day boundary can't be computed from falls back to UTC.
+    pub fn study_day(&self) -> StudyDay {
+        StudyDay::from_tz(&self.tz, self.rollover_hour).unwrap_or_else(|e| {
+            log::warn!("using UTC days: {:?}", e);
+            StudyDay::new(
+                chrono::FixedOffset::east_opt(0).unwrap(),
+                self.rollover_hour,
+            )
+        })
+    }
+}
@@ -0,0 +1,201 @@
+//! Reviews per day for the activity heatmap and the study streaks.
+//!
+//! Days are study days of the device timezone, so reviews after midnight but before
+//! the rollover hour keep a streak of the previous day going.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+
+use crate::logic::{
+    review::review_log::ReviewLogEntry,
+    study_day::{date_day, day_date, DayNumber, StudyDay},
+};
+
+/// Shades of the heatmap, 0 for a day without reviews.
+pub const HEAT_LEVELS: usize = 5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Streaks {
+    /// Days in a row up to today, or up to yesterday while today has no reviews yet.
+    pub current: usize,
+    pub longest: usize,
+}
+
+/// A calendar month of the heatmap: a column per week, a row per weekday from Monday.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeatmapMonth {
+    pub year: i32,
+    pub month: u32,
+    pub weeks: usize,
+    pub days: Vec<HeatmapDay>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeatmapDay {
+    pub day: DayNumber,
+    pub week: usize,
+    pub weekday: usize,
+    pub reviews: usize,
+}
+
+impl HeatmapMonth {
+    pub fn max_reviews(&self) -> usize {
+        self.days.iter().map(|d| d.reviews).max().unwrap_or(0)
+    }
+}
+
+/// Review counts of every day with reviews.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReviewActivity {
+    days: BTreeMap<DayNumber, usize>,
+}
+
+impl ReviewActivity {
+    pub fn push_review(&mut self, entry: &ReviewLogEntry, day: &StudyDay) {
+        self.push_time(entry.time, day);
+    }
+
+    pub fn push_time(&mut self, time: DateTime<Utc>, day: &StudyDay) {
+        *self.days.entry(day.day_number(time)).or_default() += 1;
+    }
+
+    pub fn reviews_on(&self, day: DayNumber) -> usize {
+        self.days.get(&day).copied().unwrap_or(0)
+    }
+
+    pub fn first_day(&self) -> Option<DayNumber> {
+        self.days.keys().next().copied()
+    }
+
+    /// Reviews in the future, e.g. from a clock that was set wrong, don't count.
+    pub fn streaks(&self, today: DayNumber) -> Streaks {
+        let mut longest = 0;
+        let mut run = 0;
+        let mut last = None;
+        for &day in self.days.range(..=today).map(|(day, _)| day) {
+            run = if last == Some(day - 1) { run + 1 } else { 1 };
+            longest = longest.max(run);
+            last = Some(day);
+        }
+        let current = match last {
+            Some(last) if last >= today - 1 => run,
+            _ => 0,
+        };
+        Streaks { current, longest }
+    }
+
+    pub fn month(&self, year: i32, month: u32) -> HeatmapMonth {
+        let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap_or_default();
+        let next = first + Months::new(1);
+        let offset = first.weekday().num_days_from_monday() as usize;
+        let days: Vec<HeatmapDay> = (date_day(first)..date_day(next))
+            .enumerate()
+            .map(|(i, day)| HeatmapDay {
+                day,
+                week: (offset + i) / 7,
+                weekday: (offset + i) % 7,
+                reviews: self.reviews_on(day),
+            })
+            .collect();
+        HeatmapMonth {
+            year,
+            month,
+            weeks: days.last().map_or(0, |d| d.week + 1),
+            days,
+        }
+    }
+}
+
+/// Year and month of a study day.
+pub fn month_of(day: DayNumber) -> (i32, u32) {
+    let date = day_date(day);
+    (date.year(), date.month())
+}
+
+/// The month `by` months after the given one, before it if negative.
+pub fn shift_month((year, month): (i32, u32), by: i32) -> (i32, u32) {
+    let index = year * 12 + month as i32 - 1 + by;
+    (index.div_euclid(12), index.rem_euclid(12) as u32 + 1)
+}
+
+/// Shade of a day: 0 without reviews, then up to `HEAT_LEVELS - 1` relative to the busiest day.
+pub fn heat_level(reviews: usize, max_reviews: usize) -> usize {
+    if reviews == 0 || max_reviews == 0 {
+        return 0;
+    }
+    let levels = HEAT_LEVELS - 1;
+    ((reviews * levels).div_ceil(max_reviews)).clamp(1, levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(days: &[DayNumber]) -> ReviewActivity {
+        ReviewActivity {
+            days: days.iter().map(|day| (*day, 1)).collect(),
+        }
+    }
+
+    #[test]
+    fn current_streak_may_end_yesterday() {
+        let today = 1000;
+        let streaks = activity(&[997, 998, 999]).streaks(today);
+        assert_eq!(
+            streaks,
+            Streaks {
+                current: 3,
+                longest: 3
+            }
+        );
+        // once today has reviews it joins the run
+        assert_eq!(activity(&[997, 998, 999, 1000]).streaks(today).current, 4);
+        // a day without reviews before yesterday ends it
+        assert_eq!(activity(&[997, 998]).streaks(today).current, 0);
+        assert_eq!(activity(&[]).streaks(today), Streaks::default());
+    }
+
+    #[test]
+    fn gap_splits_runs() {
+        let streaks = activity(&[990, 991, 992, 993, 995, 996]).streaks(996);
+        assert_eq!(
+            streaks,
+            Streaks {
+                current: 2,
+                longest: 4
+            }
+        );
+    }
+
+    #[test]
+    fn future_reviews_are_ignored() {
+        let streaks = activity(&[998, 999, 1001, 1002, 1003, 1004]).streaks(1000);
+        assert_eq!(
+            streaks,
+            Streaks {
+                current: 2,
+                longest: 2
+            }
+        );
+    }
+
+    #[test]
+    fn month_starting_on_sunday() {
+        // 1 September 2024 is a Sunday
+        let first = date_day(NaiveDate::from_ymd_opt(2024, 9, 1).unwrap());
+        let month = activity(&[first, first + 1, first + 29]).month(2024, 9);
+        assert_eq!((month.year, month.month), (2024, 9));
+        assert_eq!(month.days.len(), 30);
+        assert_eq!(month.weeks, 6);
+        let day = |i: usize| {
+            let d = month.days[i];
+            (d.week, d.weekday, d.reviews)
+        };
+        assert_eq!(day(0), (0, 6, 1));
+        assert_eq!(day(1), (1, 0, 1));
+        assert_eq!(day(2), (1, 1, 0));
+        assert_eq!(day(29), (5, 0, 1));
+        assert_eq!(month.max_reviews(), 1);
+    }
+}
@@ -0,0 +1,258 @@
+//! Numbers of the stats screen, collected from cards and review log entries fed one at a time
+//! so neither the decks nor the log have to be in memory.
+
+use chrono::{DateTime, Utc};
+use rs_fsrs::{Rating, State};
+
+use crate::logic::{
+    deck::card::Card,
+    review::review_log::ReviewLogEntry,
+    study_day::{DayNumber, StudyDay},
+};
+
+pub const FORECAST_DAYS: usize = 30;
+pub const RETENTION_DAYS: usize = 30;
+/// Upper bounds of the interval buckets in days, the last bucket takes everything longer.
+pub const INTERVAL_BUCKETS: [(i64, &str); 10] = [
+    (1, "1d"),
+    (3, "3d"),
+    (7, "1w"),
+    (14, "2w"),
+    (30, "1m"),
+    (60, "2m"),
+    (90, "3m"),
+    (180, "6m"),
+    (365, "1y"),
+    (i64::MAX, "1y+"),
+];
+/// FSRS difficulty goes from 1 to 10, a bucket per whole number.
+pub const DIFFICULTY_BUCKETS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateCounts {
+    pub new: usize,
+    pub learning: usize,
+    pub review: usize,
+    /// Counted here instead of their state.
+    pub suspended: usize,
+}
+
+impl StateCounts {
+    pub fn total(&self) -> usize {
+        self.new + self.learning + self.review + self.suspended
+    }
+}
+
+/// Reviews of cards in review state on one day, a pass is any grade but Again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionDay {
+    pub reviews: usize,
+    pub passed: usize,
+}
+
+impl RetentionDay {
+    /// Share of passed reviews, `None` for a day without reviews.
+    pub fn rate(&self) -> Option<f32> {
+        (self.reviews > 0).then(|| self.passed as f32 / self.reviews as f32)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionStats {
+    today: DayNumber,
+    pub cards: StateCounts,
+    /// Cards due on each of the next days, today first together with the overdue ones.
+    pub forecast: [usize; FORECAST_DAYS],
+    /// True retention of the last days, today last.
+    pub retention: [RetentionDay; RETENTION_DAYS],
+    /// Review cards by interval, see `INTERVAL_BUCKETS`.
+    pub intervals: [usize; INTERVAL_BUCKETS.len()],
+    /// Studied cards by difficulty, 1 first.
+    pub difficulty: [usize; DIFFICULTY_BUCKETS],
+}
+
+impl CollectionStats {
+    pub fn new(day: &StudyDay, now: DateTime<Utc>) -> Self {
+        Self {
+            today: day.day_number(now),
+            cards: StateCounts::default(),
+            forecast: [0; FORECAST_DAYS],
+            retention: [RetentionDay::default(); RETENTION_DAYS],
+            intervals: [0; INTERVAL_BUCKETS.len()],
+            difficulty: [0; DIFFICULTY_BUCKETS],
+        }
+    }
+
+    pub fn push_card(&mut self, card: &Card, day: &StudyDay) {
+        if card.suspended {
+            self.cards.suspended += 1;
+            return;
+        }
+        match card.state() {
+            State::New => {
+                self.cards.new += 1;
+                return;
+            }
+            State::Learning | State::Relearning => self.cards.learning += 1,
+            State::Review => {
+                self.cards.review += 1;
+                let interval = card.fsrs.scheduled_days;
+                let bucket = INTERVAL_BUCKETS
+                    .iter()
+                    .position(|(bound, _)| interval <= *bound)
+                    .unwrap_or(INTERVAL_BUCKETS.len() - 1);
+                self.intervals[bucket] += 1;
+            }
+        }
+
+        let due_in = (day.day_number(card.fsrs.due) - self.today).max(0) as usize;
+        if due_in < FORECAST_DAYS {
+            self.forecast[due_in] += 1;
+        }
+        let difficulty = (card.fsrs.difficulty.floor() as usize).clamp(1, DIFFICULTY_BUCKETS);
+        self.difficulty[difficulty - 1] += 1;
+    }
+
+    pub fn push_review(&mut self, entry: &ReviewLogEntry, day: &StudyDay) {
+        if entry.state_before != State::Review {
+            return;
+        }
+        let days_ago = self.today - day.day_number(entry.time);
+        if !(0..RETENTION_DAYS as i64).contains(&days_ago) {
+            return;
+        }
+        let retention = &mut self.retention[RETENTION_DAYS - 1 - days_ago as usize];
+        retention.reviews += 1;
+        if entry.rating != Rating::Again {
+            retention.passed += 1;
+        }
+    }
+
+    /// True retention over all of `RETENTION_DAYS`.
+    pub fn total_retention(&self) -> RetentionDay {
+        self.retention
+            .iter()
+            .fold(RetentionDay::default(), |total, day| RetentionDay {
+                reviews: total.reviews + day.reviews,
+                passed: total.passed + day.passed,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, FixedOffset, TimeZone};
+
+    use super::*;
+
+    fn day() -> StudyDay {
+        StudyDay::new(FixedOffset::east_opt(3 * 3600).unwrap(), 4)
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap()
+    }
+
+    fn card(state: State, due_in_days: i64, interval: i64, difficulty: f64) -> Card {
+        let mut card = Card::new(1, 1, 0, now());
+        card.fsrs.state = state;
+        card.fsrs.due = now() + Duration::days(due_in_days);
+        card.fsrs.scheduled_days = interval;
+        card.fsrs.difficulty = difficulty;
+        card
+    }
+
+    fn review(days_ago: i64, state_before: State, rating: Rating) -> ReviewLogEntry {
+        ReviewLogEntry {
+            deck_id: 1,
+            card_id: 1,
+            note_id: 1,
+            template: 0,
+            cloze: None,
+            time: now() - Duration::days(days_ago),
+            rating,
+            elapsed_days: 0,
+            state_before,
+            state_after: State::Review,
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn cards_are_counted_by_state_due_day_and_interval() {
+        let day = day();
+        let mut stats = CollectionStats::new(&day, now());
+        let cards = [
+            card(State::New, 0, 0, 0.0),
+            card(State::Learning, 0, 0, 5.5),
+            card(State::Review, -40, 1, 0.5),
+            card(State::Review, 2, 3, 10.0),
+            card(State::Review, 29, 4, 7.0),
+            card(State::Review, 30, 400, 7.9),
+            Card {
+                suspended: true,
+                ..card(State::Review, 1, 10, 3.0)
+            },
+        ];
+        for card in &cards {
+            stats.push_card(card, &day);
+        }
+
+        assert_eq!(
+            stats.cards,
+            StateCounts {
+                new: 1,
+                learning: 1,
+                review: 4,
+                suspended: 1,
+            }
+        );
+        assert_eq!(stats.cards.total(), cards.len());
+        // overdue cards are due today, the last forecast day is 29 days ahead
+        let mut forecast = [0; FORECAST_DAYS];
+        forecast[0] = 2;
+        forecast[2] = 1;
+        forecast[29] = 1;
+        assert_eq!(stats.forecast, forecast);
+        // 1d, 3d, 1w and 1y+
+        assert_eq!(stats.intervals, [1, 1, 1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(stats.difficulty, [1, 0, 0, 0, 1, 0, 2, 0, 0, 1]);
+    }
+
+    #[test]
+    fn retention_counts_reviews_by_day() {
+        let day = day();
+        let mut stats = CollectionStats::new(&day, now());
+        for entry in [
+            review(0, State::Review, Rating::Good),
+            review(0, State::Review, Rating::Again),
+            review(0, State::Learning, Rating::Again),
+            review(1, State::Review, Rating::Hard),
+            review(29, State::Review, Rating::Easy),
+            review(30, State::Review, Rating::Again),
+            review(-1, State::Review, Rating::Again),
+        ] {
+            stats.push_review(&entry, &day);
+        }
+
+        let today = stats.retention[RETENTION_DAYS - 1];
+        assert_eq!(
+            today,
+            RetentionDay {
+                reviews: 2,
+                passed: 1
+            }
+        );
+        assert_eq!(today.rate(), Some(0.5));
+        assert_eq!(stats.retention[RETENTION_DAYS - 2].rate(), Some(1.0));
+        assert_eq!(stats.retention[0].reviews, 1);
+        assert_eq!(stats.retention[1].rate(), None);
+        assert_eq!(
+            stats.total_retention(),
+            RetentionDay {
+                reviews: 4,
+                passed: 3
+            }
+        );
+    }
+}
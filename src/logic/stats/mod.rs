@@ -0,0 +1,2 @@
+pub mod activity;
+pub mod collection_stats;
\ No newline at end of file
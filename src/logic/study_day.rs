@@ -0,0 +1,167 @@
+//! Study days: the local day a review counts towards for the daily limits.
+//!
+//! A day starts at the rollover hour in local time, so a late session after midnight
+//! still belongs to the previous day.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+
+/// Days since 1970-01-01 in local time shifted by the rollover hour.
+pub type DayNumber = i64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StudyDay {
+    offset: FixedOffset,
+    rollover_hour: u32,
+}
+
+impl StudyDay {
+    pub fn new(offset: FixedOffset, rollover_hour: u32) -> Self {
+        Self {
+            offset,
+            rollover_hour: rollover_hour.min(23),
+        }
+    }
+
+    /// Builds it from a POSIX `TZ` value like the one set on the device, e.g. `GMT-3`.
+    pub fn from_tz(tz: &str, rollover_hour: u32) -> Result<Self> {
+        Ok(Self::new(parse_tz_offset(tz)?, rollover_hour))
+    }
+
+    pub fn offset(&self) -> FixedOffset {
+        self.offset
+    }
+
+    pub fn day_number(&self, now: DateTime<Utc>) -> DayNumber {
+        let local = now.with_timezone(&self.offset).naive_local()
+            - Duration::hours(self.rollover_hour as i64);
+        date_day(local.date())
+    }
+
+    /// When the study day of `now` started.
+    pub fn start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        self.start_of_day(self.day_number(now))
+    }
+
+    /// When the next study day starts, cards due before it are due today.
+    pub fn end(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        self.start_of_day(self.day_number(now) + 1)
+    }
+
+    fn start_of_day(&self, day: DayNumber) -> DateTime<Utc> {
+        let rollover = NaiveTime::from_hms_opt(self.rollover_hour, 0, 0).unwrap_or_default();
+        let local = day_date(day).and_time(rollover);
+        // a fixed offset has exactly one mapping for every local time
+        self.offset
+            .from_local_datetime(&local)
+            .single()
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|| local.and_utc())
+    }
+}
+
+fn epoch_date() -> NaiveDate {
+    DateTime::UNIX_EPOCH.date_naive()
+}
+
+/// Local date of a study day.
+pub fn day_date(day: DayNumber) -> NaiveDate {
+    epoch_date() + Duration::days(day)
+}
+
+pub fn date_day(date: NaiveDate) -> DayNumber {
+    date.signed_duration_since(epoch_date()).num_days()
+}
+
+/// Offset east of UTC of a POSIX `TZ` value: `NAME[+-]hh[:mm[:ss]]`, the sign means
+/// west of Greenwich so `GMT-3` is UTC+3. Daylight saving rules after it are not supported.
+pub fn parse_tz_offset(tz: &str) -> Result<FixedOffset> {
+    let tz = tz.trim();
+    let rest = if let Some(quoted) = tz.strip_prefix('<') {
+        let end = quoted
+            .find('>')
+            .ok_or_else(|| anyhow!("unterminated <name> in TZ {}", tz))?;
+        &quoted[end + 1..]
+    } else {
+        tz.trim_start_matches(|c: char| c.is_ascii_alphabetic())
+    };
+    if rest.len() == tz.len() {
+        return Err(anyhow!("TZ {} has no zone name", tz));
+    }
+
+    let offset_len = rest
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '+' | '-' | ':')))
+        .unwrap_or(rest.len());
+    if offset_len < rest.len() {
+        return Err(anyhow!("daylight saving in TZ {} is not supported", tz));
+    }
+    let (sign, offset) = match rest.strip_prefix('-') {
+        Some(offset) => (-1, offset),
+        None => (1, rest.strip_prefix('+').unwrap_or(rest)),
+    };
+
+    let mut seconds = 0;
+    for (i, part) in offset.split(':').enumerate() {
+        let value: i32 = part
+            .parse()
+            .map_err(|_| anyhow!("bad offset in TZ {}", tz))?;
+        seconds += value
+            * match i {
+                0 => 3600,
+                1 => 60,
+                2 => 1,
+                _ => return Err(anyhow!("bad offset in TZ {}", tz)),
+            };
+    }
+    FixedOffset::west_opt(sign * seconds).ok_or_else(|| anyhow!("offset out of range in TZ {}", tz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 10, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn day_changes_at_rollover_hour() {
+        let moscow = StudyDay::new(FixedOffset::east_opt(3 * 3600).unwrap(), 4);
+        let day = date_day(NaiveDate::from_ymd_opt(2024, 3, 10).unwrap());
+        // 03:59 and 04:00 in Moscow
+        assert_eq!(moscow.day_number(utc(0, 59)), day - 1);
+        assert_eq!(moscow.day_number(utc(1, 0)), day);
+        assert_eq!(moscow.start(utc(12, 0)), utc(1, 0));
+        assert_eq!(moscow.end(utc(0, 59)), utc(1, 0));
+    }
+
+    #[test]
+    fn negative_offset_keeps_previous_utc_date() {
+        let new_york = StudyDay::new(FixedOffset::west_opt(5 * 3600).unwrap(), 0);
+        let day = date_day(NaiveDate::from_ymd_opt(2024, 3, 10).unwrap());
+        // 23:30 on the 9th in New York is already the 10th in UTC
+        assert_eq!(new_york.day_number(utc(4, 30)), day - 1);
+        assert_eq!(new_york.day_number(utc(5, 0)), day);
+        assert_eq!(new_york.end(utc(4, 30)), utc(5, 0));
+        assert_eq!(day_date(day), NaiveDate::from_ymd_opt(2024, 3, 10).unwrap());
+    }
+
+    #[test]
+    fn parses_posix_tz() {
+        let east = |seconds| FixedOffset::east_opt(seconds).unwrap();
+        assert_eq!(parse_tz_offset("MSK-3").unwrap(), east(3 * 3600));
+        assert_eq!(
+            parse_tz_offset("<+0330>-3:30").unwrap(),
+            east(3 * 3600 + 30 * 60)
+        );
+        assert_eq!(parse_tz_offset("EST5").unwrap(), east(-5 * 3600));
+        assert_eq!(parse_tz_offset("UTC0").unwrap(), east(0));
+
+        assert!(parse_tz_offset("-3").is_err());
+        assert!(parse_tz_offset("<+03-3").is_err());
+        assert!(parse_tz_offset("GMTx").is_err());
+        assert!(parse_tz_offset("CET-1CEST,M3.5.0,M10.5.0/3").is_err());
+        assert!(parse_tz_offset("XYZ-30").is_err());
+        assert!(StudyDay::from_tz("MSK-3", 4).is_ok());
+    }
+}
@@ -16,6 +16,9 @@ pub trait CardputerView {
     fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>);
     fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>>;
     fn draw(&mut self, ui: &mut CardworderUi<'_>);
+
+    /// Called after `update` to write pending changes to (or read more data from) the SD card.
+    fn sync_storage(&mut self, _hal: &mut CardputerHal<'_>) {}
 }
 
 impl <'a> ViewManager<'a> {
@@ -32,6 +35,8 @@ impl <'a> ViewManager<'a> {
         }
 
         let next_view = self.current_view.update(&self.hal.keyboard_state);
+        self.current_view.sync_storage(&mut self.hal);
+
         if let Some(next_view) = next_view {
             self.current_view = next_view;
             self.view_need_init = true;
@@ -45,7 +50,7 @@ impl <'a> ViewManager<'a> {
 
         self.current_view.draw(&mut self.ui);
         if self.current_view.is_need_top_line() {
-            self.ui.draw_top_line(&self.hal.keyboard_state.input_state, &self.hal.keyboard_state.pressed);
+            self.ui.draw_top_line(&self.hal.keyboard_state.input_state, &self.hal.keyboard_state.pressed, self.hal.local_now());
         }
         self.ui.flip_buffer();
     }
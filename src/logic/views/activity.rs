@@ -0,0 +1,122 @@
+use chrono::Month;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{RgbColor, WebColors}};
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::KeyEvent}}, logic::{settings::Settings, stats::activity::{heat_level, month_of, shift_month, ReviewActivity, Streaks, HEAT_LEVELS}, study_day::DayNumber, view_manager::CardputerView, views::main_menu::MainMenuView}, ui::cardworder_ui::CardworderUi};
+
+/// Months side by side, the newest on the right.
+const MONTHS_PER_PAGE: i32 = 3;
+const CELL_SIZE: u32 = 10;
+const CELL_STEP: i32 = 11;
+const MONTH_WIDTH: i32 = 6 * CELL_STEP + 8;
+const GRID_LEFT: i32 = 8;
+const GRID_TOP: i32 = 40;
+const HEAT_COLORS: [Rgb565; HEAT_LEVELS] = [Rgb565::CSS_DARK_SLATE_GRAY, Rgb565::CSS_DARK_GREEN, Rgb565::CSS_GREEN, Rgb565::CSS_LIME_GREEN, Rgb565::CSS_LIME];
+
+/// Calendar heatmap of reviews per day with the study streaks, paged by month.
+#[derive(Default)]
+pub struct ActivityView {
+    activity: ReviewActivity,
+    today: DayNumber,
+    streaks: Streaks,
+    /// Months back from the current one shown on the right.
+    months_back: i32,
+    error: Option<String>,
+}
+
+impl ActivityView {
+    fn load(&mut self, hal: &mut CardputerHal<'_>) -> anyhow::Result<()> {
+        let settings = hal.load_settings().unwrap_or_else(|e| {
+            log::error!("error load settings: {:?}", e);
+            Settings::default()
+        });
+        let day = settings.study_day();
+        self.today = day.day_number(hal.now());
+        let activity = &mut self.activity;
+        hal.read_review_log(|entry| activity.push_review(entry, &day))?;
+        self.streaks = self.activity.streaks(self.today);
+        Ok(())
+    }
+
+    /// Paging back stops at the page with the month of the first review on the left.
+    fn max_months_back(&self) -> i32 {
+        let Some(first_day) = self.activity.first_day() else {
+            return 0;
+        };
+        let (first_year, first_month) = month_of(first_day.min(self.today));
+        let (year, month) = month_of(self.today);
+        let months = (year - first_year) * 12 + month as i32 - first_month as i32;
+        (months - MONTHS_PER_PAGE + 1).max(0)
+    }
+
+    fn draw_month(&self, ui: &mut CardworderUi<'_>, (year, month): (i32, u32), left: i32) {
+        let heatmap = self.activity.month(year, month);
+        let name = Month::try_from(month as u8).map_or("?", |m| m.name());
+        ui.draw_text_small(&format!("{} {}", &name[..3], year), left, GRID_TOP - 8, Rgb565::CSS_GRAY);
+
+        let max_reviews = heatmap.max_reviews();
+        for day in heatmap.days.iter().filter(|d| d.day <= self.today) {
+            let color = HEAT_COLORS[heat_level(day.reviews, max_reviews)];
+            let x = left + CELL_STEP * day.week as i32;
+            let y = GRID_TOP + CELL_STEP * day.weekday as i32;
+            ui.fill_rect(x, y, CELL_SIZE, CELL_SIZE, color);
+            if day.day == self.today {
+                ui.fill_rect(x + 3, y + 3, CELL_SIZE - 6, CELL_SIZE - 6, Rgb565::WHITE);
+            }
+        }
+    }
+}
+
+impl CardputerView for ActivityView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Reading review log...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        if let Err(e) = self.load(hal) {
+            log::error!("error load activity: {:?}", e);
+            self.error = Some("Can't read review log".to_string());
+        }
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        let Some((KeyEvent::Pressed, symbol)) = keyboard_state.pressed else {
+            return None;
+        };
+
+        match symbol {
+            PressedSymbol::Esc => return Some(Box::new(MainMenuView::default())),
+            PressedSymbol::ArrowLeft => self.months_back = (self.months_back + 1).min(self.max_months_back()),
+            PressedSymbol::ArrowRight => self.months_back = (self.months_back - 1).max(0),
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        if let Some(error) = &self.error {
+            ui.draw_text_centered(error, 50, Rgb565::CSS_TOMATO);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        }
+
+        let streak_color = if self.activity.reviews_on(self.today) > 0 { Rgb565::CSS_LIME } else { Rgb565::WHITE };
+        ui.draw_text_huge(&format!("Streak: {} days", self.streaks.current), 2, 14, streak_color);
+        ui.draw_text_huge(&format!("Longest: {}", self.streaks.longest), 140, 14, Rgb565::CSS_GRAY);
+
+        let newest = shift_month(month_of(self.today), -self.months_back);
+        for i in 0..MONTHS_PER_PAGE {
+            let month = shift_month(newest, i - MONTHS_PER_PAGE + 1);
+            self.draw_month(ui, month, GRID_LEFT + MONTH_WIDTH * i);
+        }
+
+        ui.draw_text_huge("<>: month  Esc: back", 2, 123, Rgb565::CSS_GRAY);
+    }
+}
@@ -0,0 +1,305 @@
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{Point, RgbColor, Size, WebColors}, primitives::Rectangle};
+use rs_fsrs::State;
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::{KeyEvent, Scancode}}}, logic::{deck::{deck_index::DeckIndex, deck_tree::leaf_name, filtered_deck::SortKey, model::DeckId}, review::{interval::format_interval, undo::pop_undo}, search::{browser::{BrowserPage, BrowserRow, BrowserSort}, deck_search::DeckSearch, history::ReviewHistory, query::{Query, SearchContext, MAX_RATED_DAYS}}, settings::Settings, study_day::{day_date, StudyDay}, view_manager::CardputerView, views::{editor::EditorView, main_menu::MainMenuView}}, ui::{cardworder_ui::CardworderUi, text_field::{TextField, TextMode}}};
+
+/// Rows read from the SD card at a time, a 10k card collection never fits in RAM.
+const PAGE_SIZE: usize = 40;
+const VISIBLE_ROWS: usize = 7;
+const ROW_HEIGHT: i32 = 13;
+const SORT_FIELD_WIDTH: usize = 17;
+const DECK_WIDTH: usize = 8;
+/// Right of the `/` in front of the search.
+const SEARCH_AREA: Rectangle = Rectangle::new(Point::new(8, 12), Size::new(230, 12));
+
+/// Which page to read on the next storage sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageLoad {
+    First,
+    Next,
+    Previous,
+    /// The shown page again, after a change of its cards.
+    Current,
+}
+
+/// Cards of every deck matching a search, in a list read from the SD card a page at a time.
+pub struct BrowserView {
+    index: DeckIndex,
+    day: StudyDay,
+    search: TextField,
+    query: Query,
+    is_editing_search: bool,
+    sort: BrowserSort,
+    descending: bool,
+    /// Key after which each page up to the current one starts.
+    page_starts: Vec<Option<SortKey>>,
+    rows: Vec<BrowserRow>,
+    matches: usize,
+    selected: usize,
+    pending: Option<PageLoad>,
+    /// Ctrl+Z was pressed, the latest change is undone on the next storage sync.
+    is_undoing: bool,
+    error: Option<String>,
+}
+
+impl Default for BrowserView {
+    fn default() -> Self {
+        Self {
+            index: DeckIndex::default(),
+            day: Settings::default().study_day(),
+            search: TextField::new("", TextMode::SingleLine, SEARCH_AREA),
+            query: Query::default(),
+            is_editing_search: false,
+            sort: BrowserSort::default(),
+            descending: false,
+            page_starts: Vec::new(),
+            rows: Vec::new(),
+            matches: 0,
+            selected: 0,
+            pending: Some(PageLoad::First),
+            is_undoing: false,
+            error: None,
+        }
+    }
+}
+
+impl BrowserView {
+    fn load_page(&mut self, hal: &mut CardputerHal<'_>, load: PageLoad) -> anyhow::Result<()> {
+        let after = match load {
+            PageLoad::First => {
+                self.page_starts.clear();
+                None
+            }
+            PageLoad::Next => self.rows.last().map(|row| row.key),
+            PageLoad::Previous => {
+                self.page_starts.pop();
+                self.page_starts.pop().flatten()
+            }
+            PageLoad::Current => self.page_starts.pop().flatten(),
+        };
+
+        let now = hal.now();
+        let history = if self.query.needs_history() {
+            let deck_ids: Vec<DeckId> = self.index.decks.iter().map(|d| d.id).collect();
+            let entries = hal.load_review_log(&deck_ids)?;
+            ReviewHistory::new(&entries, &self.day, self.day.day_number(now) - MAX_RATED_DAYS as i64)
+        } else {
+            ReviewHistory::default()
+        };
+        let context = SearchContext { day: self.day, now, history: &history };
+
+        let mut search = DeckSearch::new(&self.query);
+        let mut page = BrowserPage::new(self.sort, self.descending, after, PAGE_SIZE);
+        for info in &self.index.decks {
+            hal.read_lines(&info.file_name(), |line| search.push_line(line, &context, |deck, note, card| page.push_card(deck, note, card)))?;
+        }
+        if search.skipped_lines() > 0 {
+            log::warn!("browser skipped {} damaged deck lines", search.skipped_lines());
+        }
+        (self.rows, self.matches) = page.finish();
+        self.page_starts.push(after);
+        self.selected = match load {
+            PageLoad::Previous => self.rows.len().saturating_sub(1),
+            PageLoad::Current => self.selected.min(self.rows.len().saturating_sub(1)),
+            _ => 0,
+        };
+        Ok(())
+    }
+
+    fn apply_search(&mut self) {
+        match Query::parse(self.search.text()) {
+            Ok(query) => {
+                self.query = query;
+                self.is_editing_search = false;
+                self.pending = Some(PageLoad::First);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Takes back the latest change and reloads the page to show it.
+    fn undo(&mut self, hal: &mut CardputerHal<'_>) {
+        let Some(step) = pop_undo() else {
+            self.error = Some("Nothing to undo".to_string());
+            return;
+        };
+        match hal.undo_in_deck(&step) {
+            Ok(()) => {
+                self.error = None;
+                self.pending = Some(PageLoad::Current);
+            }
+            Err(e) => {
+                log::error!("error undo {}: {:?}", step.action.name(), e);
+                self.error = Some(format!("Undo failed: {}", e));
+            }
+        }
+    }
+
+    fn change_sort(&mut self, sort: BrowserSort, descending: bool) {
+        self.sort = sort;
+        self.descending = descending;
+        self.pending = Some(PageLoad::First);
+    }
+
+    fn first_row_number(&self) -> usize {
+        self.page_starts.len().saturating_sub(1) * PAGE_SIZE
+    }
+
+    fn deck_name(&self, deck_id: DeckId) -> &str {
+        self.index.decks.iter().find(|d| d.id == deck_id).map_or("?", |d| leaf_name(&d.name))
+    }
+
+    fn draw_row(&self, ui: &mut CardworderUi<'_>, row: &BrowserRow, y: i32, is_selected: bool) {
+        let due = match row.state {
+            State::New => "new".to_string(),
+            _ => day_date(self.day.day_number(row.due)).format("%m-%d").to_string(),
+        };
+        let interval = match row.state {
+            State::New => "-".to_string(),
+            _ => format_interval(row.last_review, row.due),
+        };
+        let color = match (is_selected, row.suspended) {
+            (true, _) => Rgb565::CSS_LIGHT_BLUE,
+            (false, true) => Rgb565::CSS_GRAY,
+            (false, false) => Rgb565::WHITE,
+        };
+        let cursor = if is_selected { ">" } else { " " };
+        let sort_field = fit(&row.sort_field, SORT_FIELD_WIDTH);
+        let deck = fit(self.deck_name(row.deck_id), DECK_WIDTH);
+        ui.draw_text_huge(&format!("{}{:<w$} {:<d$}", cursor, sort_field, deck, w = SORT_FIELD_WIDTH, d = DECK_WIDTH), 0, y, color);
+        ui.draw_text_huge(&format!("{:>5}", due), 162, y, Rgb565::CSS_LIME_GREEN);
+        ui.draw_text_huge(&format!("{:>5}", interval), 198, y, Rgb565::CSS_DEEP_SKY_BLUE);
+    }
+}
+
+/// Cuts `text` to `width` chars, marking the cut with `~`.
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut fitted: String = text.chars().take(width.saturating_sub(1)).collect();
+    fitted.push('~');
+    fitted
+}
+
+impl CardputerView for BrowserView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Loading cards...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        match hal.load_deck_index() {
+            Ok(index) => self.index = index,
+            Err(e) => {
+                log::error!("error load deck index: {:?}", e);
+                self.error = Some(format!("Can't load {}", DeckIndex::FILE_NAME));
+                self.pending = None;
+                return;
+            }
+        }
+        self.day = hal.load_settings().unwrap_or_else(|e| {
+            log::error!("error load settings: {:?}", e);
+            Settings::default()
+        }).study_day();
+        self.sync_storage(hal);
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        let Some((KeyEvent::Pressed, symbol)) = keyboard_state.pressed else {
+            return None;
+        };
+
+        if self.is_editing_search {
+            match symbol {
+                PressedSymbol::Esc | PressedSymbol::Tab => self.is_editing_search = false,
+                PressedSymbol::Enter => self.apply_search(),
+                _ => {
+                    self.search.handle_key(keyboard_state);
+                }
+            }
+            return None;
+        }
+
+        if keyboard_state.is_ctrl_with(Scancode::Z) {
+            self.is_undoing = true;
+            return None;
+        }
+        match symbol {
+            PressedSymbol::Esc => return Some(Box::new(MainMenuView::default())),
+            PressedSymbol::Tab | PressedSymbol::Char('/') => self.is_editing_search = true,
+            PressedSymbol::ArrowDown => {
+                if self.selected + 1 < self.rows.len() {
+                    self.selected += 1;
+                } else if self.first_row_number() + self.rows.len() < self.matches {
+                    self.pending = Some(PageLoad::Next);
+                }
+            }
+            PressedSymbol::ArrowUp => {
+                if self.selected > 0 {
+                    self.selected -= 1;
+                } else if self.page_starts.len() > 1 {
+                    self.pending = Some(PageLoad::Previous);
+                }
+            }
+            PressedSymbol::ArrowLeft | PressedSymbol::ArrowRight => {
+                let i = BrowserSort::ALL.iter().position(|s| *s == self.sort).unwrap_or(0);
+                let len = BrowserSort::ALL.len();
+                let next = if symbol == PressedSymbol::ArrowRight { (i + 1) % len } else { (i + len - 1) % len };
+                self.change_sort(BrowserSort::ALL[next], self.descending);
+            }
+            PressedSymbol::Char('r') => self.change_sort(self.sort, !self.descending),
+            PressedSymbol::Char('a') => return Some(Box::new(EditorView::add_from_browser())),
+            PressedSymbol::Enter => {
+                let row = self.rows.get(self.selected)?;
+                return Some(Box::new(EditorView::edit(row.deck_id, row.note_id)));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        let search_color = if self.is_editing_search { Rgb565::CSS_LIGHT_BLUE } else { Rgb565::CSS_GRAY };
+        ui.draw_text_huge("/", 2, 12, search_color);
+        ui.draw_text_field(&self.search, self.is_editing_search);
+
+        if self.rows.is_empty() && self.error.is_none() {
+            ui.draw_text_centered("No cards match", 60, Rgb565::WHITE);
+        }
+        let first = self.selected.saturating_sub(VISIBLE_ROWS - 1);
+        for (i, row) in self.rows.iter().enumerate().skip(first).take(VISIBLE_ROWS) {
+            let y = 32 + ROW_HEIGHT * (i - first) as i32;
+            self.draw_row(ui, row, y, i == self.selected);
+        }
+
+        let order = if self.descending { "desc" } else { "asc" };
+        let position = if self.rows.is_empty() { 0 } else { self.first_row_number() + self.selected + 1 };
+        match &self.error {
+            Some(error) => ui.draw_text_small(error, 2, 24, Rgb565::CSS_TOMATO),
+            None => ui.draw_text_small(&format!("sort: < {} > {} (r)   {}/{}", self.sort.name(), order, position, self.matches), 2, 24, Rgb565::CSS_GRAY),
+        }
+        let hints = if self.is_editing_search { "Enter: search  Tab: list" } else { "Enter: edit  a: add  Tab: search" };
+        ui.draw_text_huge(hints, 2, 123, Rgb565::CSS_GRAY);
+    }
+
+    fn sync_storage(&mut self, hal: &mut CardputerHal<'_>) {
+        if self.is_undoing {
+            self.is_undoing = false;
+            self.undo(hal);
+        }
+        let Some(load) = self.pending.take() else { return };
+        if let Err(e) = self.load_page(hal, load) {
+            log::error!("error load browser page: {:?}", e);
+            self.error = Some(format!("Failed: {}", e));
+        }
+    }
+}
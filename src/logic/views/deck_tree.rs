@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{RgbColor, WebColors}};
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::KeyEvent}}, logic::{deck::{deck_index::DeckIndex, deck_tree::{leaf_name, DeckTree, DeckTreeRow}}, review::session::queue_counts, settings::Settings, view_manager::CardputerView, views::{main_menu::MainMenuView, review::ReviewView}}, ui::cardworder_ui::CardworderUi};
+
+/// Rows that fit between the top line and the key hints.
+const VISIBLE_ROWS: usize = 8;
+const ROW_HEIGHT: i32 = 13;
+
+/// Decks as a tree with the cards to study today, a parent counts (and studies) everything nested in it.
+#[derive(Default)]
+pub struct DeckTreeView {
+    tree: DeckTree,
+    selected: usize,
+    error: Option<String>,
+}
+
+impl DeckTreeView {
+    fn load(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) -> anyhow::Result<()> {
+        let index = hal.load_deck_index()?;
+        let settings = hal.load_settings().unwrap_or_else(|e| {
+            log::error!("error load settings: {:?}", e);
+            Settings::default()
+        });
+        let day = settings.study_day();
+        let now = hal.now();
+
+        // decks are loaded one at a time, only their counts stay in memory
+        let mut counts = HashMap::new();
+        for info in &index.decks {
+            ui.draw_starting_line(&format!("Counting {}...", info.name), Rgb565::BLACK, Rgb565::WHITE);
+            ui.flip_buffer();
+            match hal.load_deck(info) {
+                Ok(deck) => {
+                    counts.insert(info.id, queue_counts(&deck, &day, now));
+                }
+                Err(e) => log::error!("error load deck {}: {:?}", info.name, e),
+            }
+        }
+        self.tree = DeckTree::new(&index.decks, &counts);
+        Ok(())
+    }
+
+    fn selected_row(&self) -> Option<&DeckTreeRow> {
+        self.tree.visible_rows().get(self.selected).copied()
+    }
+}
+
+impl CardputerView for DeckTreeView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Loading decks...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        if let Err(e) = self.load(hal, ui) {
+            log::error!("error load decks: {:?}", e);
+            self.error = Some(format!("Can't load {}", DeckIndex::FILE_NAME));
+        }
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        let Some((KeyEvent::Pressed, symbol)) = keyboard_state.pressed else {
+            return None;
+        };
+        let row_count = self.tree.visible_rows().len();
+
+        match symbol {
+            PressedSymbol::Esc => return Some(Box::new(MainMenuView::default())),
+            PressedSymbol::ArrowDown => self.selected = (self.selected + 1).min(row_count.saturating_sub(1)),
+            PressedSymbol::ArrowUp => self.selected = self.selected.saturating_sub(1),
+            PressedSymbol::ArrowLeft | PressedSymbol::ArrowRight => {
+                let collapse = symbol == PressedSymbol::ArrowLeft;
+                if let Some(name) = self.selected_row().filter(|r| r.has_children).map(|r| r.name.clone()) {
+                    self.tree.set_collapsed(&name, collapse);
+                }
+            }
+            PressedSymbol::Enter => {
+                let row = self.selected_row()?;
+                let decks = self.tree.subtree_decks(&row.name);
+                if !decks.is_empty() {
+                    return Some(Box::new(ReviewView::new(row.name.clone(), decks)));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        if let Some(error) = &self.error {
+            ui.draw_text_centered(error, 50, Rgb565::CSS_TOMATO);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        }
+
+        let rows = self.tree.visible_rows();
+        if rows.is_empty() {
+            ui.draw_text_centered("No decks yet", 45, Rgb565::WHITE);
+            ui.draw_text_centered("Import CSV/TSV first", 65, Rgb565::CSS_GRAY);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        }
+
+        // keep the selected row on screen
+        let first = self.selected.saturating_sub(VISIBLE_ROWS - 1);
+        for (i, row) in rows.iter().enumerate().skip(first).take(VISIBLE_ROWS) {
+            let y = 12 + ROW_HEIGHT * (i - first) as i32;
+            let is_selected = i == self.selected;
+            let fold = match (row.has_children, self.tree.is_collapsed(&row.name)) {
+                (false, _) => " ",
+                (true, true) => "+",
+                (true, false) => "-",
+            };
+            let color = if is_selected { Rgb565::CSS_LIGHT_BLUE } else { Rgb565::WHITE };
+            let cursor = if is_selected { ">" } else { " " };
+            ui.draw_text_huge(&format!("{}{}{}", cursor, fold, leaf_name(&row.name)), 6 * row.depth as i32, y, color);
+
+            let counts = row.counts;
+            ui.draw_text_huge(&format!("{:>3}", counts.new), 168, y, Rgb565::CSS_DEEP_SKY_BLUE);
+            ui.draw_text_huge(&format!("{:>3}", counts.learning), 192, y, Rgb565::CSS_TOMATO);
+            ui.draw_text_huge(&format!("{:>3}", counts.review), 216, y, Rgb565::CSS_LIME_GREEN);
+        }
+
+        ui.draw_text_huge("Enter: study  <>: fold  Esc: back", 2, 123, Rgb565::CSS_GRAY);
+    }
+}
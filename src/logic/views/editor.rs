@@ -0,0 +1,283 @@
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{Point, RgbColor, Size, WebColors}, primitives::Rectangle};
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::KeyEvent}}, logic::{deck::{deck_index::DeckIndex, model::{Deck, DeckId, DeckInfo}, note::NoteId, note_draft::NoteDraft}, review::undo::{push_undo, UndoAction, UndoStep}, view_manager::CardputerView, views::{browser::BrowserView, main_menu::MainMenuView}}, ui::{cardworder_ui::CardworderUi, text_field::{TextField, TextMode, CHAR_WIDTH}}};
+
+const VISIBLE_ROWS: usize = 7;
+const ROW_HEIGHT: i32 = 13;
+const SCREEN_WIDTH: u32 = 240;
+/// Deck a note goes to when there are no decks yet.
+const FIRST_DECK_NAME: &str = "Default";
+
+/// Rows of the form after the note fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditorRow {
+    Field(usize),
+    Deck,
+    Tags,
+}
+
+/// Where Esc goes back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EditorOrigin {
+    MainMenu,
+    Browser,
+}
+
+/// Adds notes on the device or edits one picked in the browser. Tab goes through the fields,
+/// the deck and the tags, Ctrl+Space switches between the En and Ru layouts.
+pub struct EditorView {
+    index: DeckIndex,
+    deck: usize,
+    /// The note being edited, `None` when adding new ones.
+    editing: Option<(DeckId, NoteId)>,
+    fields: Vec<TextField>,
+    tags: TextField,
+    row: usize,
+    origin: EditorOrigin,
+    is_saving: bool,
+    /// Set once the user was warned that the front is already in the deck.
+    allow_duplicate: bool,
+    message: Option<(String, Rgb565)>,
+    error: Option<String>,
+}
+
+impl Default for EditorView {
+    fn default() -> Self {
+        Self {
+            index: DeckIndex::default(),
+            deck: 0,
+            editing: None,
+            fields: Vec::new(),
+            tags: text_input("Tags", ""),
+            row: 0,
+            origin: EditorOrigin::MainMenu,
+            is_saving: false,
+            allow_duplicate: false,
+            message: None,
+            error: None,
+        }
+        .with_draft(NoteDraft::default())
+    }
+}
+
+impl EditorView {
+    pub fn edit(deck_id: DeckId, note_id: NoteId) -> Self {
+        Self { editing: Some((deck_id, note_id)), origin: EditorOrigin::Browser, ..Default::default() }
+    }
+
+    /// A new note from the browser, Esc goes back there.
+    pub fn add_from_browser() -> Self {
+        Self { origin: EditorOrigin::Browser, ..Default::default() }
+    }
+
+    fn with_draft(mut self, draft: NoteDraft) -> Self {
+        self.set_draft(draft);
+        self
+    }
+
+    fn set_draft(&mut self, draft: NoteDraft) {
+        self.fields = draft.fields.iter().enumerate().map(|(i, text)| text_input(&NoteDraft::field_name(i), text)).collect();
+        self.tags = text_input("Tags", &draft.tags);
+    }
+
+    fn draft(&self) -> NoteDraft {
+        NoteDraft { fields: self.fields.iter().map(|f| f.text().to_string()).collect(), tags: self.tags.text().to_string() }
+    }
+
+    fn rows(&self) -> Vec<EditorRow> {
+        let mut rows: Vec<EditorRow> = (0..self.fields.len()).map(EditorRow::Field).collect();
+        rows.push(EditorRow::Deck);
+        rows.push(EditorRow::Tags);
+        rows
+    }
+
+    fn load(&mut self, hal: &mut CardputerHal<'_>) -> anyhow::Result<()> {
+        self.index = hal.load_deck_index()?;
+        let Some((deck_id, note_id)) = self.editing else { return Ok(()) };
+        self.deck = self.index.decks.iter().position(|d| d.id == deck_id).ok_or_else(|| anyhow::anyhow!("no deck {}", deck_id))?;
+        let deck = hal.load_deck(&self.index.decks[self.deck])?;
+        let note = deck.note(note_id).ok_or_else(|| anyhow::anyhow!("no note {} in {}", note_id, deck.info.name))?;
+        self.set_draft(NoteDraft::from_note(note));
+        Ok(())
+    }
+
+    /// Writes the draft to its deck, returns the text to show.
+    fn save(&mut self, hal: &mut CardputerHal<'_>) -> anyhow::Result<String> {
+        let now = hal.now();
+        let (mut deck, is_new_deck) = match self.index.decks.get(self.deck) {
+            Some(info) => (hal.load_deck(info)?, false),
+            None => (Deck::new(DeckInfo { id: self.index.next_id(), name: FIRST_DECK_NAME.to_string() }), true),
+        };
+        let draft = self.draft();
+        let except = self.editing.map(|(_, note_id)| note_id);
+        if !self.allow_duplicate && draft.find_duplicate(&deck, except).is_some() {
+            self.allow_duplicate = true;
+            return Err(anyhow::anyhow!("already in {}, Enter to save anyway", deck.info.name));
+        }
+
+        let mut undo = None;
+        let message = match self.editing {
+            Some((_, note_id)) => {
+                undo = UndoStep::before(UndoAction::EditNote, &deck, note_id, None);
+                draft.apply_to(&mut deck, note_id, now)?;
+                "Saved".to_string()
+            }
+            None => {
+                draft.add_to(&mut deck, now)?;
+                format!("Added to {}", deck.info.name)
+            }
+        };
+        hal.save_deck(&deck)?;
+        if let Some(step) = undo {
+            push_undo(step);
+        }
+        if is_new_deck {
+            self.index.decks.push(deck.info.clone());
+            hal.save_deck_index(&self.index)?;
+        }
+
+        // a new note keeps the deck and tags for the next one
+        if self.editing.is_none() {
+            self.fields.iter_mut().for_each(|f| f.set_text(""));
+            self.row = 0;
+        }
+        self.allow_duplicate = false;
+        Ok(message)
+    }
+
+    fn back(&self) -> Box<dyn CardputerView> {
+        match self.origin {
+            EditorOrigin::MainMenu => Box::new(MainMenuView::default()),
+            EditorOrigin::Browser => Box::new(BrowserView::default()),
+        }
+    }
+
+    fn deck_name(&self) -> String {
+        match self.index.decks.get(self.deck) {
+            Some(info) => info.name.clone(),
+            None => format!("{} (new)", FIRST_DECK_NAME),
+        }
+    }
+}
+
+/// A one line input right of its label, moved to its row when drawn.
+fn text_input(label: &str, text: &str) -> TextField {
+    let left = label_width(label);
+    TextField::new(text, TextMode::SingleLine, Rectangle::new(Point::new(left, 0), Size::new(SCREEN_WIDTH - 2 - left as u32, ROW_HEIGHT as u32)))
+}
+
+fn label_width(label: &str) -> i32 {
+    2 + CHAR_WIDTH as i32 * (label.chars().count() as i32 + 2)
+}
+
+impl CardputerView for EditorView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Loading note...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        if let Err(e) = self.load(hal) {
+            log::error!("error load note: {:?}", e);
+            self.error = Some("Can't load the note".to_string());
+        }
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        let Some((KeyEvent::Pressed, symbol)) = keyboard_state.pressed else {
+            return None;
+        };
+        if self.error.is_some() {
+            return (symbol == PressedSymbol::Esc).then(|| self.back());
+        }
+
+        if symbol != PressedSymbol::Enter {
+            self.message = None;
+        }
+
+        let rows = self.rows();
+        let row = rows[self.row.min(rows.len() - 1)];
+        match (symbol, row) {
+            (PressedSymbol::Esc, _) => return Some(self.back()),
+            (PressedSymbol::Enter, _) => self.is_saving = true,
+            (PressedSymbol::Tab, _) if keyboard_state.input_state.shift_pressed => self.row = (self.row + rows.len() - 1) % rows.len(),
+            (PressedSymbol::Tab, _) => self.row = (self.row + 1) % rows.len(),
+            (PressedSymbol::ArrowDown, _) => self.row = (self.row + 1).min(rows.len() - 1),
+            (PressedSymbol::ArrowUp, _) => self.row = self.row.saturating_sub(1),
+            (PressedSymbol::ArrowLeft | PressedSymbol::ArrowRight, EditorRow::Deck) if self.editing.is_none() && !self.index.decks.is_empty() => {
+                let len = self.index.decks.len();
+                self.deck = if symbol == PressedSymbol::ArrowRight { (self.deck + 1) % len } else { (self.deck + len - 1) % len };
+                self.allow_duplicate = false;
+            }
+            (_, EditorRow::Field(i)) => {
+                let is_edited = self.fields[i].handle_key(keyboard_state);
+                self.allow_duplicate &= !is_edited;
+            }
+            (_, EditorRow::Tags) => {
+                self.tags.handle_key(keyboard_state);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        if let Some(error) = &self.error {
+            ui.draw_text_centered(error, 50, Rgb565::CSS_TOMATO);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        }
+
+        // notes with many fields scroll to keep the selected row on screen
+        let first = self.row.saturating_sub(VISIBLE_ROWS - 1);
+        for (i, row) in self.rows().into_iter().enumerate().skip(first).take(VISIBLE_ROWS) {
+            let is_selected = i == self.row;
+            let color = if is_selected { Rgb565::CSS_LIGHT_BLUE } else { Rgb565::WHITE };
+            let y = 14 + ROW_HEIGHT * (i - first) as i32;
+            let (label, input) = match row {
+                EditorRow::Field(field) => (NoteDraft::field_name(field), Some(&mut self.fields[field])),
+                EditorRow::Deck => ("Deck".to_string(), None),
+                EditorRow::Tags => ("Tags".to_string(), Some(&mut self.tags)),
+            };
+            ui.draw_text_huge(&format!("{}:", label), 2, y, color);
+            match input {
+                Some(input) => {
+                    let area = input.area();
+                    input.set_area(Rectangle::new(Point::new(area.top_left.x, y), area.size));
+                    ui.draw_text_field(input, is_selected);
+                }
+                None => {
+                    let deck = if self.editing.is_some() { self.deck_name() } else { format!("< {} >", self.deck_name()) };
+                    ui.draw_text_huge(&deck, label_width(&label), y, color);
+                }
+            }
+        }
+
+        if let Some((message, color)) = &self.message {
+            ui.draw_text_small(message, 2, 112, *color);
+        }
+        let hints = if self.editing.is_some() { "Tab: next  Enter: save  Esc" } else { "Tab: next  Enter: add  Esc" };
+        ui.draw_text_huge(hints, 2, 123, Rgb565::CSS_GRAY);
+    }
+
+    fn sync_storage(&mut self, hal: &mut CardputerHal<'_>) {
+        if !self.is_saving {
+            return;
+        }
+        self.is_saving = false;
+        self.message = Some(match self.save(hal) {
+            Ok(message) => (message, Rgb565::CSS_LIME_GREEN),
+            Err(e) => {
+                log::warn!("note not saved: {:?}", e);
+                (format!("Not saved: {}", e), Rgb565::CSS_TOMATO)
+            }
+        });
+    }
+}
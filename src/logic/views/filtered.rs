@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{RgbColor, WebColors}};
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::KeyEvent}}, logic::{deck::{card::CardId, deck_index::DeckIndex, filtered_deck::{CardPicker, FilterOrder, FilteredDeck, FilteredDeckId, FilteredDecks}, model::DeckId}, review::session::{filtered_counts, QueueCounts}, search::{deck_search::DeckSearch, history::ReviewHistory, query::{Query, SearchContext, MAX_RATED_DAYS}}, settings::Settings, view_manager::CardputerView, views::{main_menu::MainMenuView, review::ReviewView}}, ui::cardworder_ui::CardworderUi};
+
+const VISIBLE_ROWS: usize = 8;
+const ROW_HEIGHT: i32 = 13;
+const MAX_LIMIT_DIGITS: usize = 4;
+
+/// Ready-made searches, Tab goes through them in the form.
+const PRESETS: [(&str, &str, usize, FilterOrder, bool); 4] = [
+    ("Custom", "", 100, FilterOrder::Due, true),
+    ("Forgotten in 3 days", "rated:3:1", 100, FilterOrder::Due, true),
+    ("Random 50 new", "is:new", 50, FilterOrder::Random, true),
+    ("Preview new", "is:new", 50, FilterOrder::Due, false),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormField {
+    Search,
+    Limit,
+    Order,
+    Reschedule,
+}
+
+const FORM_FIELDS: [FormField; 4] = [FormField::Search, FormField::Limit, FormField::Order, FormField::Reschedule];
+
+/// A new filtered deck being set up.
+struct FilteredDeckForm {
+    preset: usize,
+    search: String,
+    limit: String,
+    order: FilterOrder,
+    reschedule: bool,
+    field: usize,
+    error: Option<String>,
+}
+
+impl FilteredDeckForm {
+    fn new() -> Self {
+        let mut form = Self { preset: 0, search: String::new(), limit: String::new(), order: FilterOrder::Due, reschedule: true, field: 0, error: None };
+        form.apply_preset(0);
+        form
+    }
+
+    fn apply_preset(&mut self, preset: usize) {
+        let (_, search, limit, order, reschedule) = PRESETS[preset];
+        self.preset = preset;
+        self.search = search.to_string();
+        self.limit = limit.to_string();
+        self.order = order;
+        self.reschedule = reschedule;
+        self.error = None;
+    }
+
+    /// The preset name while its search is kept, the search itself once it is edited.
+    fn name(&self) -> String {
+        let (name, search, ..) = PRESETS[self.preset];
+        if self.preset != 0 && self.search == search { name.to_string() } else { self.search.trim().to_string() }
+    }
+
+    fn to_filtered_deck(&self, id: FilteredDeckId) -> anyhow::Result<FilteredDeck> {
+        if self.search.trim().is_empty() {
+            return Err(anyhow::anyhow!("type a search"));
+        }
+        Query::parse(&self.search)?;
+        let limit = self.limit.parse::<usize>().ok().filter(|l| *l > 0).ok_or_else(|| anyhow::anyhow!("limit must be 1 or more"))?;
+        Ok(FilteredDeck { id, name: self.name(), search: self.search.trim().to_string(), limit, order: self.order, reschedule: self.reschedule, home_decks: Vec::new(), created: DateTime::default() })
+    }
+
+    fn update(&mut self, symbol: PressedSymbol) {
+        let field = FORM_FIELDS[self.field];
+        match (symbol, field) {
+            (PressedSymbol::ArrowDown, _) => self.field = (self.field + 1).min(FORM_FIELDS.len() - 1),
+            (PressedSymbol::ArrowUp, _) => self.field = self.field.saturating_sub(1),
+            (PressedSymbol::Tab, _) => self.apply_preset((self.preset + 1) % PRESETS.len()),
+            (PressedSymbol::Char(c), FormField::Search) => self.search.push(c),
+            (PressedSymbol::Backspace, FormField::Search) => {
+                self.search.pop();
+            }
+            (PressedSymbol::Char(c), FormField::Limit) if c.is_ascii_digit() && self.limit.len() < MAX_LIMIT_DIGITS => self.limit.push(c),
+            (PressedSymbol::Backspace, FormField::Limit) => {
+                self.limit.pop();
+            }
+            (PressedSymbol::ArrowLeft | PressedSymbol::ArrowRight, FormField::Order) => {
+                let i = FilterOrder::ALL.iter().position(|o| *o == self.order).unwrap_or(0);
+                let len = FilterOrder::ALL.len();
+                let next = if symbol == PressedSymbol::ArrowRight { (i + 1) % len } else { (i + len - 1) % len };
+                self.order = FilterOrder::ALL[next];
+            }
+            (PressedSymbol::ArrowLeft | PressedSymbol::ArrowRight | PressedSymbol::Char(' '), FormField::Reschedule) => self.reschedule = !self.reschedule,
+            _ => {}
+        }
+    }
+
+    fn draw(&self, ui: &mut CardworderUi<'_>) {
+        ui.draw_text_huge(&format!("New: {}", PRESETS[self.preset].0), 2, 12, Rgb565::CSS_GRAY);
+        let reschedule = if self.reschedule { "yes" } else { "no, preview" };
+        let values = [
+            format!("Search: {}", self.search),
+            format!("Limit: {}", self.limit),
+            format!("Order: < {} >", self.order.name()),
+            format!("Reschedule: {}", reschedule),
+        ];
+        for (i, value) in values.iter().enumerate() {
+            let is_selected = i == self.field;
+            let color = if is_selected { Rgb565::CSS_LIGHT_BLUE } else { Rgb565::WHITE };
+            let cursor = if is_selected && matches!(FORM_FIELDS[i], FormField::Search | FormField::Limit) { "_" } else { "" };
+            ui.draw_text_huge(&format!("{}{}", value, cursor), 2, 32 + ROW_HEIGHT * i as i32, color);
+        }
+        if let Some(error) = &self.error {
+            ui.draw_text_small(error, 2, 95, Rgb565::CSS_TOMATO);
+        }
+        ui.draw_text_huge("Tab: preset  Enter: build  Esc", 2, 123, Rgb565::CSS_GRAY);
+    }
+}
+
+/// Something to do on the SD card once the keys are handled.
+enum PendingAction {
+    Build(FilteredDeck),
+    Empty(FilteredDeckId),
+}
+
+/// Filtered decks for custom study: build one from a search, study it, empty it to give
+/// the cards back to their home decks.
+#[derive(Default)]
+pub struct FilteredDecksView {
+    index: DeckIndex,
+    decks: FilteredDecks,
+    counts: HashMap<FilteredDeckId, QueueCounts>,
+    selected: usize,
+    form: Option<FilteredDeckForm>,
+    pending: Option<PendingAction>,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+impl FilteredDecksView {
+    fn load(&mut self, hal: &mut CardputerHal<'_>) -> anyhow::Result<()> {
+        self.index = hal.load_deck_index()?;
+        self.decks = hal.load_filtered_decks()?;
+        let day = Self::settings(hal).study_day();
+        let now = hal.now();
+
+        // every home deck is loaded once, whatever number of filtered decks borrow from it
+        self.counts.clear();
+        for info in self.index.decks.iter().filter(|info| self.decks.decks.iter().any(|d| d.home_decks.contains(&info.id))) {
+            let deck = hal.load_deck(info)?;
+            for filtered in self.decks.decks.iter().filter(|d| d.home_decks.contains(&info.id)) {
+                *self.counts.entry(filtered.id).or_default() += filtered_counts(&deck, filtered.id, &day, now);
+            }
+        }
+        self.selected = self.selected.min(self.decks.decks.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn settings(hal: &mut CardputerHal<'_>) -> Settings {
+        hal.load_settings().unwrap_or_else(|e| {
+            log::error!("error load settings: {:?}", e);
+            Settings::default()
+        })
+    }
+
+    /// Lends the matching cards of every deck to a new filtered deck, returns how many.
+    fn build(&mut self, hal: &mut CardputerHal<'_>, mut filtered: FilteredDeck) -> anyhow::Result<usize> {
+        let query = Query::parse(&filtered.search)?;
+        let day = Self::settings(hal).study_day();
+        let now = hal.now();
+        let history = if query.needs_history() {
+            let deck_ids: Vec<DeckId> = self.index.decks.iter().map(|d| d.id).collect();
+            let entries = hal.load_review_log(&deck_ids)?;
+            ReviewHistory::new(&entries, &day, day.day_number(now) - MAX_RATED_DAYS as i64)
+        } else {
+            ReviewHistory::default()
+        };
+        let context = SearchContext { day, now, history: &history };
+        // seeds the random order, the id may have been used by an emptied deck
+        filtered.created = now;
+
+        // the deck files are searched line by line, a deck is only loaded if cards are lent from it
+        let today = day.day_number(now);
+        let mut search = DeckSearch::new(&query);
+        let mut picker = CardPicker::new(&filtered);
+        for info in &self.index.decks {
+            hal.read_lines(&info.file_name(), |line| search.push_line(line, &context, |deck, _, card| picker.push_card(deck.id, card, today)))?;
+        }
+        if search.skipped_lines() > 0 {
+            log::warn!("search skipped {} damaged deck lines", search.skipped_lines());
+        }
+        let picked = picker.finish();
+        if picked.is_empty() {
+            return Ok(0);
+        }
+
+        for info in &self.index.decks {
+            let card_ids: Vec<CardId> = picked.iter().filter(|k| k.deck_id == info.id).map(|k| k.card_id).collect();
+            if card_ids.is_empty() {
+                continue;
+            }
+            let mut deck = hal.load_deck(info)?;
+            deck.move_to_filtered(&card_ids, filtered.id);
+            hal.save_deck(&deck)?;
+            filtered.home_decks.push(info.id);
+        }
+        self.decks.decks.push(filtered);
+        hal.save_filtered_decks(&self.decks)?;
+        Ok(picked.len())
+    }
+
+    /// Gives the cards back to their home decks and deletes the filtered deck.
+    fn empty(&mut self, hal: &mut CardputerHal<'_>, id: FilteredDeckId) -> anyhow::Result<usize> {
+        let Some(filtered) = self.decks.find(id).cloned() else { return Ok(0) };
+        let mut returned = 0;
+        for info in self.index.decks.iter().filter(|d| filtered.home_decks.contains(&d.id)) {
+            let mut deck = hal.load_deck(info)?;
+            let count = deck.return_from_filtered(id);
+            if count > 0 {
+                hal.save_deck(&deck)?;
+            }
+            returned += count;
+        }
+        // removed only once every card is back, otherwise emptying it again finishes the job
+        self.decks.remove(id);
+        hal.save_filtered_decks(&self.decks)?;
+        Ok(returned)
+    }
+}
+
+impl CardputerView for FilteredDecksView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Loading filtered decks...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        if let Err(e) = self.load(hal) {
+            log::error!("error load filtered decks: {:?}", e);
+            self.error = Some(format!("Can't load {}", FilteredDecks::FILE_NAME));
+        }
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        let Some((KeyEvent::Pressed, symbol)) = keyboard_state.pressed else {
+            return None;
+        };
+
+        if let Some(form) = &mut self.form {
+            match symbol {
+                PressedSymbol::Esc => self.form = None,
+                PressedSymbol::Enter => match form.to_filtered_deck(self.decks.next_id()) {
+                    Ok(filtered) => {
+                        self.pending = Some(PendingAction::Build(filtered));
+                        self.form = None;
+                    }
+                    Err(e) => form.error = Some(e.to_string()),
+                },
+                _ => form.update(symbol),
+            }
+            return None;
+        }
+
+        match symbol {
+            PressedSymbol::Esc => return Some(Box::new(MainMenuView::default())),
+            PressedSymbol::ArrowDown => self.selected = (self.selected + 1).min(self.decks.decks.len().saturating_sub(1)),
+            PressedSymbol::ArrowUp => self.selected = self.selected.saturating_sub(1),
+            PressedSymbol::Char('n') if self.error.is_none() => {
+                self.form = Some(FilteredDeckForm::new());
+                self.message = None;
+            }
+            PressedSymbol::Char('x') => {
+                if let Some(filtered) = self.decks.decks.get(self.selected) {
+                    self.pending = Some(PendingAction::Empty(filtered.id));
+                }
+            }
+            PressedSymbol::Enter => {
+                let filtered = self.decks.decks.get(self.selected)?;
+                let decks = self.index.decks.iter().filter(|d| filtered.home_decks.contains(&d.id)).cloned().collect();
+                return Some(Box::new(ReviewView::filtered(filtered.clone(), decks)));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        if let Some(error) = &self.error {
+            ui.draw_text_centered(error, 50, Rgb565::CSS_TOMATO);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        }
+        if let Some(form) = &self.form {
+            form.draw(ui);
+            return;
+        }
+
+        if self.decks.decks.is_empty() {
+            ui.draw_text_centered("No filtered decks", 45, Rgb565::WHITE);
+            ui.draw_text_centered("n: build one from a search", 65, Rgb565::CSS_GRAY);
+        }
+        let first = self.selected.saturating_sub(VISIBLE_ROWS - 1);
+        for (i, filtered) in self.decks.decks.iter().enumerate().skip(first).take(VISIBLE_ROWS) {
+            let y = 12 + ROW_HEIGHT * (i - first) as i32;
+            let is_selected = i == self.selected;
+            let color = if is_selected { Rgb565::CSS_LIGHT_BLUE } else { Rgb565::WHITE };
+            let cursor = if is_selected { ">" } else { " " };
+            let preview = if filtered.reschedule { "" } else { " (preview)" };
+            ui.draw_text_huge(&format!("{}{}{}", cursor, filtered.name, preview), 0, y, color);
+
+            let counts = self.counts.get(&filtered.id).copied().unwrap_or_default();
+            ui.draw_text_huge(&format!("{:>3}", counts.new), 168, y, Rgb565::CSS_DEEP_SKY_BLUE);
+            ui.draw_text_huge(&format!("{:>3}", counts.learning), 192, y, Rgb565::CSS_TOMATO);
+            ui.draw_text_huge(&format!("{:>3}", counts.review), 216, y, Rgb565::CSS_LIME_GREEN);
+        }
+
+        if let Some(message) = &self.message {
+            ui.draw_text_small(message, 2, 112, Rgb565::CSS_YELLOW);
+        }
+        ui.draw_text_huge("Enter:study n:new x:empty Esc", 2, 123, Rgb565::CSS_GRAY);
+    }
+
+    fn sync_storage(&mut self, hal: &mut CardputerHal<'_>) {
+        let Some(action) = self.pending.take() else { return };
+        let result = match action {
+            PendingAction::Build(filtered) => self.build(hal, filtered).map(|count| match count {
+                0 => "No cards match the search".to_string(),
+                count => format!("Borrowed {} cards", count),
+            }),
+            PendingAction::Empty(id) => self.empty(hal, id).map(|count| format!("Returned {} cards home", count)),
+        };
+        self.message = Some(match result {
+            Ok(message) => message,
+            Err(e) => {
+                log::error!("error update filtered decks: {:?}", e);
+                format!("Failed: {}", e)
+            }
+        });
+        if let Err(e) = self.load(hal) {
+            log::error!("error load filtered decks: {:?}", e);
+            self.error = Some(format!("Can't load {}", FilteredDecks::FILE_NAME));
+        }
+    }
+}
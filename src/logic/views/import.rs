@@ -0,0 +1,180 @@
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{RgbColor, WebColors}};
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::KeyEvent}}, logic::{deck::{deck_index::DeckIndex, deck_tree::SEPARATOR, model::{Deck, DeckInfo}}, import::{csv_import::{CsvImporter, ImportReport}, csv_parser::CsvParser}, view_manager::CardputerView, views::main_menu::MainMenuView}, ui::cardworder_ui::CardworderUi};
+
+/// Directories deeper than this are not searched for files to import.
+const MAX_DIR_DEPTH: usize = 3;
+
+/// A `.csv`/`.tsv` file on the SD card and the deck it goes to.
+struct ImportSource {
+    /// 8.3 path, the one to open the file with.
+    path: String,
+    size: u32,
+    file_name: String,
+    deck_name: String,
+}
+
+/// Imports every `.csv`/`.tsv` file from the SD card into the deck named after the file,
+/// a file in a directory goes to a nested deck: `Russian/Verbs.csv` -> `Russian::Verbs`.
+/// Imported files are recorded in the deck index and skipped until they change.
+#[derive(Default)]
+pub struct ImportView {
+    reports: Vec<ImportReport>,
+    unchanged_count: usize,
+    error: Option<String>,
+}
+
+impl ImportView {
+    /// Collects the files to import from `dir` and its subdirectories.
+    fn find_sources(hal: &mut CardputerHal<'_>, dir: &str, parent_deck: Option<&str>, depth: usize, sources: &mut Vec<ImportSource>) -> anyhow::Result<()> {
+        let entries = hal.list_dir(dir)?;
+        for entry in &entries {
+            let path = if dir.is_empty() { entry.name.clone() } else { format!("{}/{}", dir, entry.name) };
+            let name = entry.display_name();
+            let stem = if entry.is_dir { name } else { name.rsplit_once('.').map_or(name, |(stem, _)| stem) };
+            let deck_name = match parent_deck {
+                Some(parent) => format!("{}{}{}", parent, SEPARATOR, stem),
+                None => stem.to_string(),
+            };
+            if entry.is_dir {
+                if depth < MAX_DIR_DEPTH {
+                    Self::find_sources(hal, &path, Some(&deck_name), depth + 1, sources)?;
+                }
+            } else if CsvParser::for_file_name(name).is_some() {
+                sources.push(ImportSource { path, size: entry.size, file_name: name.to_string(), deck_name });
+            }
+        }
+        Ok(())
+    }
+
+    fn import_file(hal: &mut CardputerHal<'_>, index: &mut DeckIndex, source: &ImportSource) -> ImportReport {
+        let file_name = source.file_name.as_str();
+        let deck_name = source.deck_name.clone();
+        let error_report = |error: String| ImportReport {
+            file_name: file_name.to_string(),
+            deck_name: deck_name.clone(),
+            error_count: 1,
+            errors: vec![error],
+            ..Default::default()
+        };
+
+        let existing = index.find_by_name(&deck_name).cloned();
+        let deck = match &existing {
+            Some(info) => match hal.load_deck(info) {
+                Ok(deck) => deck,
+                Err(e) => {
+                    log::error!("error load deck {}: {:?}", info.name, e);
+                    return error_report(format!("can't load {}", info.file_name()));
+                }
+            },
+            None => Deck::new(DeckInfo { id: index.next_id(), name: deck_name.clone() }),
+        };
+
+        let mut importer = match CsvImporter::new(file_name, deck, hal.now()) {
+            Ok(importer) => importer,
+            Err(e) => return error_report(e.to_string()),
+        };
+        if let Err(e) = hal.read_lines(&source.path, |line| importer.push_line(line)) {
+            log::error!("error read {}: {:?}", file_name, e);
+            return error_report(format!("can't read {}", file_name));
+        }
+
+        let (deck, report) = importer.finish();
+        if report.imported == 0 {
+            index.mark_imported(&source.path, source.size);
+            return report;
+        }
+        if let Err(e) = hal.save_deck(&deck) {
+            log::error!("error save deck {}: {:?}", deck.info.name, e);
+            return error_report(format!("can't write {}", deck.info.file_name()));
+        }
+        if existing.is_none() {
+            index.decks.push(deck.info.clone());
+        }
+        index.mark_imported(&source.path, source.size);
+        report
+    }
+}
+
+impl CardputerView for ImportView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Looking for CSV/TSV...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        let mut sources = Vec::new();
+        if let Err(e) = Self::find_sources(hal, "", None, 0, &mut sources) {
+            log::error!("error list SD card: {:?}", e);
+            self.error = Some("Can't read SD card".to_string());
+            return;
+        }
+        let mut index = match hal.load_deck_index() {
+            Ok(index) => index,
+            Err(e) => {
+                log::error!("error load deck index: {:?}", e);
+                self.error = Some(format!("Can't load {}", DeckIndex::FILE_NAME));
+                return;
+            }
+        };
+        let loaded_index = index.clone();
+
+        for source in &sources {
+            if index.is_imported(&source.path, source.size) {
+                self.unchanged_count += 1;
+                continue;
+            }
+            ui.draw_starting_line(&format!("Importing {}...", source.file_name), Rgb565::BLACK, Rgb565::WHITE);
+            ui.flip_buffer();
+
+            let report = Self::import_file(hal, &mut index, source);
+            log::info!("imported {}: {}", report.file_name, report.summary());
+            self.reports.push(report);
+        }
+
+        if index != loaded_index {
+            if let Err(e) = hal.save_deck_index(&index) {
+                log::error!("error save deck index: {:?}", e);
+                self.error = Some(format!("Can't write {}", DeckIndex::FILE_NAME));
+            }
+        }
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        match keyboard_state.pressed {
+            Some((KeyEvent::Pressed, PressedSymbol::Esc)) => Some(Box::new(MainMenuView::default())),
+            _ => None,
+        }
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        if let Some(error) = &self.error {
+            ui.draw_text_centered(error, 50, Rgb565::CSS_TOMATO);
+        } else if self.reports.is_empty() && self.unchanged_count > 0 {
+            ui.draw_text_centered("No new .csv/.tsv files", 50, Rgb565::WHITE);
+        } else if self.reports.is_empty() {
+            ui.draw_text_centered("No .csv/.tsv files", 50, Rgb565::WHITE);
+        }
+
+        let mut y = 12;
+        for report in &self.reports {
+            let color = if report.error_count == 0 { Rgb565::CSS_LIME_GREEN } else { Rgb565::CSS_ORANGE };
+            ui.draw_text_huge(&report.deck_name, 2, y, Rgb565::WHITE);
+            ui.draw_text_huge(&report.summary(), 120, y, color);
+            y += 13;
+            if let Some(error) = report.errors.first() {
+                ui.draw_text_small(error, 8, y - 3, Rgb565::CSS_TOMATO);
+                y += 7;
+            }
+        }
+
+        ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+    }
+}
@@ -1,20 +1,52 @@
 use embedded_graphics::{pixelcolor::Rgb565, prelude::{RgbColor, WebColors}};
 
-use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::{KeyEvent, Scancode}}}, logic::{view_manager::CardputerView, views::start::StartView}, ui::cardworder_ui::CardworderUi};
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::{KeyEvent, Scancode}}}, logic::{view_manager::CardputerView, views::{activity::ActivityView, browser::BrowserView, deck_tree::DeckTreeView, editor::EditorView, filtered::FilteredDecksView, import::ImportView, optimize::OptimizeView, start::StartView, stats::StatsView}}, ui::cardworder_ui::CardworderUi};
+
+const VISIBLE_OPTIONS: usize = 9;
 
 enum MainMenuOption {
     Nothing,
+    Decks,
+    Browse,
+    AddNote,
+    CustomStudy,
+    Stats,
+    Activity,
+    ImportDecks,
+    OptimizeFsrs,
     ConnectWifiAndUpdateNtp,
 }
 
+impl MainMenuOption {
+    fn title(&self) -> String {
+        match self {
+            MainMenuOption::Nothing => "Nothing".to_string(),
+            MainMenuOption::Decks => "Decks".to_string(),
+            MainMenuOption::Browse => "Browse cards".to_string(),
+            MainMenuOption::AddNote => "Add note".to_string(),
+            MainMenuOption::CustomStudy => "Custom study".to_string(),
+            MainMenuOption::Stats => "Stats".to_string(),
+            MainMenuOption::Activity => "Activity".to_string(),
+            MainMenuOption::ImportDecks => "Import CSV/TSV".to_string(),
+            MainMenuOption::OptimizeFsrs => "Optimize FSRS".to_string(),
+            MainMenuOption::ConnectWifiAndUpdateNtp => "Connect Wifi and Update Ntp".to_string(),
+        }
+    }
+}
+
 pub struct MainMenuView {
     show_fps: bool,
-    current_option: Option<MainMenuOption>,    
+    options: Vec<MainMenuOption>,
+    current_option: Option<usize>,
 }
 
 impl Default for MainMenuView {
     fn default() -> Self {
-        Self { show_fps: false, current_option: None }
+        Self {
+            show_fps: false,
+            options: vec![MainMenuOption::Nothing, MainMenuOption::Decks, MainMenuOption::Browse, MainMenuOption::AddNote, MainMenuOption::CustomStudy, MainMenuOption::Stats, MainMenuOption::Activity, MainMenuOption::ImportDecks, MainMenuOption::OptimizeFsrs, MainMenuOption::ConnectWifiAndUpdateNtp],
+            current_option: None,
+        }
     }
 }
 
@@ -27,7 +59,7 @@ impl CardputerView for MainMenuView {
         true
     }
 
-    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+    fn init(&mut self, _hal: &mut CardputerHal<'_>, _ui: &mut CardworderUi<'_>) {
     }
 
     fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
@@ -41,13 +73,37 @@ impl CardputerView for MainMenuView {
 
         match keyboard_state.pressed {
             Some((KeyEvent::Pressed, PressedSymbol::ArrowDown)) => {
-                self.current_option = Some(MainMenuOption::ConnectWifiAndUpdateNtp);
+                self.current_option = Some(self.current_option.map_or(0, |i| (i + 1).min(self.options.len() - 1)));
             }
             Some((KeyEvent::Pressed, PressedSymbol::ArrowUp)) => {
-                self.current_option = Some(MainMenuOption::Nothing);
+                self.current_option = Some(self.current_option.map_or(0, |i| i.saturating_sub(1)));
             }
             Some((KeyEvent::Pressed, PressedSymbol::Enter)) => {
-                match self.current_option { 
+                match self.current_option.and_then(|i| self.options.get(i)) {
+                    Some(MainMenuOption::Decks) => {
+                        return Some(Box::new(DeckTreeView::default()));
+                    }
+                    Some(MainMenuOption::Browse) => {
+                        return Some(Box::new(BrowserView::default()));
+                    }
+                    Some(MainMenuOption::AddNote) => {
+                        return Some(Box::new(EditorView::default()));
+                    }
+                    Some(MainMenuOption::CustomStudy) => {
+                        return Some(Box::new(FilteredDecksView::default()));
+                    }
+                    Some(MainMenuOption::Stats) => {
+                        return Some(Box::new(StatsView::default()));
+                    }
+                    Some(MainMenuOption::Activity) => {
+                        return Some(Box::new(ActivityView::default()));
+                    }
+                    Some(MainMenuOption::ImportDecks) => {
+                        return Some(Box::new(ImportView::default()));
+                    }
+                    Some(MainMenuOption::OptimizeFsrs) => {
+                        return Some(Box::new(OptimizeView::default()));
+                    }
                     Some(MainMenuOption::ConnectWifiAndUpdateNtp) => {
                         return Some(Box::new(StartView{}));
                     }
@@ -61,18 +117,17 @@ impl CardputerView for MainMenuView {
     }
 
     fn draw(&mut self, ui: &mut CardworderUi<'_>) {
-        if matches!(self.current_option, Some(MainMenuOption::Nothing)) {
-            ui.draw_text_huge("> Nothing", 0, 8, Rgb565::CSS_LIGHT_BLUE);
-        } else {
-            ui.draw_text_huge("  Nothing", 0, 8, Rgb565::CSS_WHITE);
-        }
-        
-        if matches!(self.current_option, Some(MainMenuOption::ConnectWifiAndUpdateNtp)) {
-            ui.draw_text_huge("> Connect Wifi and Update Ntp", 0, 21, Rgb565::CSS_LIGHT_BLUE);
-        } else {
-            ui.draw_text_huge("  Connect Wifi and Update Ntp", 0, 21, Rgb565::WHITE);
+        // the menu scrolls once it is longer than the screen
+        let first = self.current_option.unwrap_or(0).saturating_sub(VISIBLE_OPTIONS - 1);
+        for (i, option) in self.options.iter().enumerate().skip(first).take(VISIBLE_OPTIONS) {
+            let y = 8 + 13 * (i - first) as i32;
+            if self.current_option == Some(i) {
+                ui.draw_text_huge(&format!("> {}", option.title()), 0, y, Rgb565::CSS_LIGHT_BLUE);
+            } else {
+                ui.draw_text_huge(&format!("  {}", option.title()), 0, y, Rgb565::WHITE);
+            }
         }
 
         ui.show_fps = self.show_fps;
     }
-}
\ No newline at end of file
+}
@@ -1,2 +1,11 @@
 pub mod start;
-pub mod main_menu;
\ No newline at end of file
+pub mod main_menu;
+pub mod review;
+pub mod import;
+pub mod optimize;
+pub mod deck_tree;
+pub mod filtered;
+pub mod stats;
+pub mod activity;
+pub mod browser;
+pub mod editor;
\ No newline at end of file
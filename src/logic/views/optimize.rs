@@ -0,0 +1,119 @@
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{RgbColor, WebColors}};
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::KeyEvent}}, logic::{optimizer::trainer::{optimize, OptimizeReport, TrainerConfig, TrainingSet}, view_manager::CardputerView, views::main_menu::MainMenuView}, ui::cardworder_ui::CardworderUi};
+
+/// Soft float makes training slow on the ESP32-S3, bigger logs go to `fsrsopt` on a PC.
+const MAX_DEVICE_REVIEWS: usize = 2000;
+
+enum PresetResult {
+    NoData,
+    TooBig(usize),
+    Fitted(OptimizeReport),
+}
+
+/// Fits the FSRS weights of every preset to the review log on the device.
+#[derive(Default)]
+pub struct OptimizeView {
+    results: Vec<(String, PresetResult)>,
+    error: Option<String>,
+}
+
+impl OptimizeView {
+    fn run(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) -> anyhow::Result<()> {
+        let index = hal.load_deck_index()?;
+        let mut presets = hal.load_presets()?;
+
+        let mut deck_presets = Vec::new();
+        for info in &index.decks {
+            let header = hal.load_deck_header(info)?;
+            deck_presets.push((info.id, header.options.preset_id));
+        }
+
+        let mut is_changed = false;
+        for preset in presets.presets.iter_mut() {
+            let deck_ids: Vec<_> = deck_presets.iter().filter(|(_, p)| *p == preset.id).map(|(d, _)| *d).collect();
+            let entries = hal.load_review_log(&deck_ids)?;
+            let result = if entries.len() > MAX_DEVICE_REVIEWS {
+                PresetResult::TooBig(entries.len())
+            } else {
+                let set = TrainingSet::from_log(&entries);
+                drop(entries);
+                if set.card_count() == 0 {
+                    PresetResult::NoData
+                } else {
+                    let config = TrainerConfig::default();
+                    let report = optimize(&set, preset.weights, config, |epoch| {
+                        ui.draw_starting_line(&format!("{}: epoch {}/{}", preset.name, epoch, config.epochs), Rgb565::BLACK, Rgb565::WHITE);
+                        ui.flip_buffer();
+                    });
+                    if report.is_improved() {
+                        preset.weights = report.weights;
+                        is_changed = true;
+                    }
+                    PresetResult::Fitted(report)
+                }
+            };
+            self.results.push((preset.name.clone(), result));
+        }
+
+        if is_changed {
+            hal.save_presets(&presets)?;
+        }
+        Ok(())
+    }
+}
+
+impl CardputerView for OptimizeView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Loading review log...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        if let Err(e) = self.run(hal, ui) {
+            log::error!("error optimize presets: {:?}", e);
+            self.error = Some("Can't optimize, see log".to_string());
+        }
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        match keyboard_state.pressed {
+            Some((KeyEvent::Pressed, PressedSymbol::Esc)) => Some(Box::new(MainMenuView::default())),
+            _ => None,
+        }
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        if let Some(error) = &self.error {
+            ui.draw_text_centered(error, 50, Rgb565::CSS_TOMATO);
+        }
+
+        let mut y = 12;
+        for (name, result) in &self.results {
+            ui.draw_text_huge(name, 2, y, Rgb565::WHITE);
+            match result {
+                PresetResult::NoData => ui.draw_text_huge("no reviews yet", 100, y, Rgb565::CSS_GRAY),
+                PresetResult::TooBig(reviews) => ui.draw_text_huge(&format!("{} reviews, use fsrsopt", reviews), 100, y, Rgb565::CSS_ORANGE),
+                PresetResult::Fitted(report) => {
+                    let color = if report.is_improved() { Rgb565::CSS_LIME_GREEN } else { Rgb565::CSS_GRAY };
+                    let status = if report.is_improved() { "saved" } else { "kept" };
+                    ui.draw_text_huge(&format!("{} cards, {}", report.cards, status), 100, y, color);
+                    y += 11;
+                    ui.draw_text_small(&format!("log-loss {:.3} -> {:.3}", report.before.log_loss, report.after.log_loss), 8, y, Rgb565::WHITE);
+                    y += 7;
+                    ui.draw_text_small(&format!("RMSE {:.3} -> {:.3}", report.before.rmse, report.after.rmse), 8, y, Rgb565::WHITE);
+                }
+            }
+            y += 13;
+        }
+
+        ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+    }
+}
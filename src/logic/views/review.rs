@@ -0,0 +1,633 @@
+use chrono::{DateTime, Utc};
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{RgbColor, WebColors}};
+use rs_fsrs::Rating;
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::{KeyEvent, Scancode}}}, logic::{deck::{cloze::{Span, SpanStyle}, deck_preset::DeckPresets, filtered_deck::FilteredDeck, model::{DeckId, DeckInfo}}, review::{answer_check::{AnswerCheck, AnswerChecker}, diff::DiffOp, interval::format_interval, review_log::ReviewLogEntry, session::ReviewSession, undo::{pop_undo, push_undo, UndoStep}}, settings::Settings, view_manager::CardputerView, views::{deck_tree::DeckTreeView, filtered::FilteredDecksView}}, ui::cardworder_ui::{CardworderUi, CellMark}};
+
+/// Decks studied together are all kept in RAM, a loaded deck takes about as much as its file.
+const MAX_STUDY_FILES_SIZE: u32 = 160 * 1024;
+
+const GRADES: [(char, Rating, &str, Rgb565); 4] = [
+    ('1', Rating::Again, "Again", Rgb565::CSS_TOMATO),
+    ('2', Rating::Hard, "Hard", Rgb565::CSS_ORANGE),
+    ('3', Rating::Good, "Good", Rgb565::CSS_LIME_GREEN),
+    ('4', Rating::Easy, "Easy", Rgb565::CSS_DEEP_SKY_BLUE),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnswerMode {
+    /// Think of the answer and flip the card.
+    Flip,
+    /// Type the answer and compare it with the expected one.
+    Typed,
+}
+
+pub struct ReviewView {
+    /// The chosen deck or parent, its nested decks are studied with it.
+    name: String,
+    decks: Vec<DeckInfo>,
+    /// Set when studying a filtered deck, `decks` are the homes of its cards.
+    filtered: Option<FilteredDeck>,
+    session: Option<ReviewSession>,
+    load_error: Option<String>,
+    mode: AnswerMode,
+    typed_answer: String,
+    answer_check: Option<AnswerCheck>,
+    answer_shown: bool,
+    /// New back of the leech's note while it is being rewritten.
+    leech_edit: Option<String>,
+    /// Grades not yet appended to the review log.
+    pending_log: Vec<ReviewLogEntry>,
+    /// Decks changed since they were last written to the SD card.
+    unsaved_decks: Vec<DeckId>,
+    /// Undone grades still to be taken out of the review log.
+    undone_log: Vec<ReviewLogEntry>,
+    /// Undo steps of decks outside this session, restored on the SD card.
+    undo_elsewhere: Vec<UndoStep>,
+    /// What Ctrl+Z did, until the next key.
+    notice: Option<String>,
+    /// Time of the HAL clock, taken in `init` and `sync_storage` since `update` has no HAL.
+    now: DateTime<Utc>,
+}
+
+impl ReviewView {
+    pub fn new(name: String, decks: Vec<DeckInfo>) -> Self {
+        Self {
+            name,
+            decks,
+            filtered: None,
+            session: None,
+            load_error: None,
+            mode: AnswerMode::Flip,
+            typed_answer: String::new(),
+            answer_check: None,
+            answer_shown: false,
+            leech_edit: None,
+            pending_log: Vec::new(),
+            unsaved_decks: Vec::new(),
+            undone_log: Vec::new(),
+            undo_elsewhere: Vec::new(),
+            notice: None,
+            now: DateTime::UNIX_EPOCH,
+        }
+    }
+
+    pub fn filtered(filtered: FilteredDeck, decks: Vec<DeckInfo>) -> Self {
+        let name = filtered.name.clone();
+        Self { filtered: Some(filtered), ..Self::new(name, decks) }
+    }
+
+    /// Where Esc leads: the list the session was started from.
+    fn back(&self) -> Box<dyn CardputerView> {
+        match self.filtered {
+            Some(_) => Box::new(FilteredDecksView::default()),
+            None => Box::new(DeckTreeView::default()),
+        }
+    }
+
+    fn mark_unsaved(&mut self, deck_id: DeckId) {
+        if !self.unsaved_decks.contains(&deck_id) {
+            self.unsaved_decks.push(deck_id);
+        }
+    }
+
+    fn grade(&mut self, rating: Rating) {
+        let Some(session) = &mut self.session else { return };
+        if let Some(graded) = session.grade(rating, self.now) {
+            if let Some(step) = graded.undo {
+                push_undo(step);
+            }
+            self.mark_unsaved(graded.key.deck_id);
+            self.pending_log.extend(graded.log);
+            self.answer_shown = false;
+            self.typed_answer.clear();
+            self.answer_check = None;
+        }
+    }
+
+    /// Bury or suspend the shown card.
+    fn put_aside(&mut self, suspend: bool) {
+        let Some(session) = &mut self.session else { return };
+        let Some(key) = session.current_key() else { return };
+        let now = self.now;
+        let undo = if suspend { session.set_suspended(key, true, now) } else { session.bury(key, now) };
+        if let Some(step) = undo {
+            push_undo(step);
+        }
+        self.mark_unsaved(key.deck_id);
+        self.answer_shown = false;
+        self.typed_answer.clear();
+        self.answer_check = None;
+    }
+
+    /// Takes back the latest grade, bury, suspend or note edit, the card is shown again
+    /// with the state it had.
+    fn undo(&mut self) {
+        let Some(step) = pop_undo() else {
+            self.notice = Some("Nothing to undo".to_string());
+            return;
+        };
+        self.notice = Some(format!("Undone: {}", step.action.name()));
+        let is_in_session = self.session.as_mut().is_some_and(|session| session.undo(&step, self.now));
+        if !is_in_session {
+            self.undo_elsewhere.push(step);
+            return;
+        }
+        self.mark_unsaved(step.deck_id);
+        self.undone_log.extend(step.log);
+        self.answer_shown = false;
+        self.typed_answer.clear();
+        self.answer_check = None;
+        self.leech_edit = None;
+    }
+
+    fn show_answer(&mut self) {
+        let Some(session) = &self.session else { return };
+        let Some(sides) = session.current_sides() else { return };
+        let Some(deck) = session.current_deck() else { return };
+        if self.mode == AnswerMode::Typed {
+            let checker = AnswerChecker::new(deck.options.answer_check.clone());
+            self.answer_check = Some(checker.check(&self.typed_answer, &sides.expected));
+        }
+        self.answer_shown = true;
+    }
+
+    /// Keys of the leech notice: edit the answer, (un)suspend, reset or go on with Enter.
+    fn update_leech(&mut self, symbol: PressedSymbol) {
+        let Some(session) = &mut self.session else { return };
+        let Some(key) = session.leech() else { return };
+        let Some(deck) = session.deck(key.deck_id) else { return };
+        let Some(card) = deck.card(key.card_id) else { return };
+        let (note_id, suspended) = (card.note_id, card.suspended);
+        let template = deck.template_of(card);
+        // the deletions of a cloze card are in its question field
+        let answer_field = if card.cloze.is_some() { template.question_field } else { template.answer_field };
+        let now = self.now;
+
+        if let Some(text) = &mut self.leech_edit {
+            match symbol {
+                PressedSymbol::Char(c) => text.push(c),
+                PressedSymbol::Backspace => {
+                    text.pop();
+                }
+                PressedSymbol::Enter => {
+                    if let Some(step) = session.set_note_field(key.deck_id, note_id, answer_field, text.trim().to_string(), now) {
+                        push_undo(step);
+                    }
+                    self.leech_edit = None;
+                    self.mark_unsaved(key.deck_id);
+                }
+                PressedSymbol::Esc => self.leech_edit = None,
+                _ => {}
+            }
+            return;
+        }
+
+        match symbol {
+            PressedSymbol::Char('e') => {
+                self.leech_edit = deck.note(note_id).map(|n| n.field(answer_field).to_string());
+            }
+            PressedSymbol::Char('s') => {
+                if let Some(step) = session.set_suspended(key, !suspended, now) {
+                    push_undo(step);
+                }
+                self.mark_unsaved(key.deck_id);
+            }
+            PressedSymbol::Char('r') => {
+                if let Some(step) = session.reset(key, now) {
+                    push_undo(step);
+                }
+                session.dismiss_leech();
+                self.mark_unsaved(key.deck_id);
+            }
+            PressedSymbol::Enter => session.dismiss_leech(),
+            _ => {}
+        }
+    }
+
+    fn draw_leech(&self, ui: &mut CardworderUi<'_>) {
+        let Some(session) = &self.session else { return };
+        let Some(key) = session.leech() else { return };
+        let Some(deck) = session.deck(key.deck_id) else { return };
+        let Some(card) = deck.card(key.card_id) else { return };
+        let Some(sides) = deck.sides(card) else { return };
+
+        ui.draw_text_centered(&format!("Leech: {} lapses", card.fsrs.lapses), 28, Rgb565::CSS_TOMATO);
+        draw_spans(ui, &sides.question, 45);
+        match &self.leech_edit {
+            Some(text) => {
+                ui.draw_text_centered(&format!("{}_", text), 65, Rgb565::CSS_YELLOW);
+                ui.draw_text_huge("Enter: save  Esc: cancel", 2, 123, Rgb565::CSS_GRAY);
+            }
+            None => {
+                draw_spans(ui, &sides.answer, 65);
+                let status = if card.suspended { "suspended" } else { "still in study" };
+                ui.draw_text_centered(status, 85, Rgb565::CSS_GRAY);
+                let suspend = if card.suspended { "unsuspend" } else { "suspend" };
+                ui.draw_text_huge(&format!("e:edit s:{} r:reset Enter", suspend), 2, 123, Rgb565::CSS_GRAY);
+            }
+        }
+    }
+
+    fn update_typing(&mut self, symbol: PressedSymbol) {
+        match symbol {
+            PressedSymbol::Char(c) => self.typed_answer.push(c),
+            PressedSymbol::Backspace => {
+                self.typed_answer.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn draw_answer_check(&mut self, ui: &mut CardworderUi<'_>, expected: &str) {
+        let Some(check) = &self.answer_check else { return };
+        let cells: Vec<(char, Rgb565, CellMark)> = check
+            .diff
+            .ops
+            .iter()
+            .map(|op| match op {
+                DiffOp::Correct(c) => (*c, Rgb565::CSS_LIME_GREEN, CellMark::None),
+                DiffOp::Missing(c) => (*c, Rgb565::CSS_GRAY, CellMark::Underline),
+                DiffOp::Extra(c) => (*c, Rgb565::CSS_TOMATO, CellMark::StrikeThrough),
+            })
+            .collect();
+        let height = ui.draw_char_cells(&cells, 58);
+        let rule_color = if check.is_accepted() { Rgb565::CSS_LIME_GREEN } else { Rgb565::CSS_TOMATO };
+        ui.draw_text_small(check.rule.description(), 2, 50, rule_color);
+        if !check.diff.is_exact() || check.expected != expected {
+            ui.draw_text_centered(expected, 62 + height, Rgb565::CSS_LIGHT_BLUE);
+        }
+    }
+
+    fn draw_counts(&mut self, ui: &mut CardworderUi<'_>) {
+        let Some(session) = &self.session else { return };
+        let counts = session.counts();
+        ui.draw_text_huge(&format!("{}", counts.new), 2, 12, Rgb565::CSS_DEEP_SKY_BLUE);
+        ui.draw_text_huge(&format!("{}", counts.learning), 32, 12, Rgb565::CSS_TOMATO);
+        ui.draw_text_huge(&format!("{}", counts.review), 62, 12, Rgb565::CSS_LIME_GREEN);
+        ui.draw_text_small(&self.name, 100, 15, Rgb565::CSS_GRAY);
+    }
+
+    fn draw_grades(&mut self, ui: &mut CardworderUi<'_>) {
+        let suggested = self.answer_check.as_ref().map(|c| c.suggested_rating());
+        let now = self.now;
+        let Some(session) = &mut self.session else { return };
+        // a preview doesn't schedule, Again shows the card again and the rest send it home
+        let preview = session.preview(now);
+        if preview.is_none() {
+            ui.draw_text_huge("Preview: 1 again, 2-4 done", 2, 123, Rgb565::CSS_GRAY);
+        }
+
+        for (i, (key, rating, label, color)) in GRADES.iter().enumerate() {
+            let x = i as i32 * 60;
+            if suggested == Some(*rating) {
+                ui.fill_rect(x + 2, 107, 56, 2, *color);
+            }
+            ui.draw_text_huge(&format!("{} {}", key, label), x + 2, 110, *color);
+            if let Some(scheduled) = preview.and_then(|p| p.get(rating)) {
+                ui.draw_text_huge(&format_interval(now, scheduled.due()), x + 2, 123, Rgb565::WHITE);
+            }
+        }
+    }
+}
+
+/// Draws card text with cloze deletions highlighted.
+fn draw_spans(ui: &mut CardworderUi<'_>, spans: &[Span], y: i32) {
+    let colored: Vec<(&str, Rgb565)> = spans
+        .iter()
+        .map(|span| {
+            let color = match span.style {
+                SpanStyle::Plain => Rgb565::WHITE,
+                SpanStyle::Hidden => Rgb565::CSS_YELLOW,
+                SpanStyle::Revealed => Rgb565::CSS_LIGHT_BLUE,
+            };
+            (span.text.as_str(), color)
+        })
+        .collect();
+    ui.draw_spans_centered(&colored, y);
+}
+
+impl CardputerView for ReviewView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Loading deck...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        let presets = hal.load_presets().unwrap_or_else(|e| {
+            log::error!("error load presets: {:?}", e);
+            DeckPresets::default()
+        });
+        let settings = hal.load_settings().unwrap_or_else(|e| {
+            log::error!("error load settings: {:?}", e);
+            Settings::default()
+        });
+        self.now = hal.now();
+        match hal.deck_files_size(&self.decks) {
+            Ok(size) if size > MAX_STUDY_FILES_SIZE => {
+                log::error!("{} decks of {} take {} bytes", self.decks.len(), self.name, size);
+                self.load_error = Some(format!("Too big to study: {} KB", size.div_ceil(1024)));
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("error check deck sizes: {:?}", e),
+        }
+        let mut decks = Vec::new();
+        for info in &self.decks {
+            match hal.load_deck(info) {
+                Ok(deck) => {
+                    let scheduler = presets.preset_or_default(deck.options.preset_id).scheduler();
+                    decks.push((deck, scheduler));
+                }
+                Err(e) => {
+                    log::error!("error load deck {}: {:?}", info.name, e);
+                    self.load_error = Some(format!("Can't load {}", info.file_name()));
+                    return;
+                }
+            }
+        }
+        self.session = Some(match &self.filtered {
+            Some(filtered) => ReviewSession::filtered(decks, filtered, settings.study_day(), self.now),
+            None => ReviewSession::new(decks, settings.study_day(), self.now),
+        });
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        let Some(session) = &mut self.session else {
+            return match keyboard_state.pressed {
+                Some((KeyEvent::Pressed, PressedSymbol::Esc)) if self.load_error.is_some() => Some(self.back()),
+                _ => None,
+            };
+        };
+
+        // same clock the top line shows, a learning card comes back when its step is over
+        session.update(self.now);
+        let has_card = session.current().is_some();
+        let has_leech = session.leech().is_some();
+        let Some((KeyEvent::Pressed, symbol)) = keyboard_state.pressed else {
+            return None;
+        };
+        self.notice = None;
+        if keyboard_state.is_ctrl_with(Scancode::Z) {
+            self.undo();
+            return None;
+        }
+
+        if has_leech {
+            if symbol == PressedSymbol::Esc && self.leech_edit.is_none() {
+                return Some(self.back());
+            }
+            self.update_leech(symbol);
+            return None;
+        }
+
+        match (symbol, self.answer_shown, self.mode) {
+            (PressedSymbol::Esc, _, _) => {
+                return Some(self.back());
+            }
+            (PressedSymbol::Tab, false, _) => {
+                self.mode = match self.mode {
+                    AnswerMode::Flip => AnswerMode::Typed,
+                    AnswerMode::Typed => AnswerMode::Flip,
+                };
+                self.typed_answer.clear();
+            }
+            (PressedSymbol::Enter, false, _) if has_card => self.show_answer(),
+            (_, false, AnswerMode::Typed) if has_card => self.update_typing(symbol),
+            (PressedSymbol::Char('-'), _, _) if has_card => self.put_aside(false),
+            (PressedSymbol::Char('@'), _, _) if has_card => self.put_aside(true),
+            (PressedSymbol::Enter, true, AnswerMode::Typed) => {
+                if let Some(rating) = self.answer_check.as_ref().map(|c| c.suggested_rating()) {
+                    self.grade(rating);
+                }
+            }
+            (PressedSymbol::Char(c), true, _) => {
+                if let Some((_, rating, _, _)) = GRADES.iter().find(|(key, _, _, _)| *key == c) {
+                    self.grade(*rating);
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        if let Some(error) = &self.load_error {
+            ui.draw_text_centered(error, 50, Rgb565::CSS_TOMATO);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        }
+
+        self.draw_counts(ui);
+        if let Some(notice) = &self.notice {
+            ui.draw_text_small(notice, 100, 23, Rgb565::CSS_YELLOW);
+        }
+
+        let Some(session) = &self.session else { return };
+        if session.leech().is_some() {
+            self.draw_leech(ui);
+            return;
+        }
+        if let Some(due) = session.waiting_until() {
+            ui.draw_text_centered("Learning cards are resting", 45, Rgb565::WHITE);
+            ui.draw_text_centered(&format!("next one in {}", format_interval(self.now, due)), 65, Rgb565::CSS_GRAY);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        }
+        let is_cloze = session.current().is_some_and(|c| c.cloze.is_some());
+        let Some(sides) = session.current_sides() else {
+            ui.draw_text_centered("Congratulations!", 45, Rgb565::CSS_LIME_GREEN);
+            ui.draw_text_centered("No more cards due", 65, Rgb565::WHITE);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        };
+
+        // a cloze card reveals the deletions in place and shows the extra field below
+        let front = if is_cloze && self.answer_shown { &sides.answer } else { &sides.question };
+        draw_spans(ui, front, 35);
+
+        match (self.answer_shown, self.mode) {
+            (true, AnswerMode::Flip) => {
+                ui.fill_rect(20, 57, 200, 1, Rgb565::CSS_GRAY);
+                if is_cloze {
+                    ui.draw_text_centered(&sides.extra, 65, Rgb565::CSS_GRAY);
+                } else {
+                    draw_spans(ui, &sides.answer, 65);
+                }
+                self.draw_grades(ui);
+            }
+            (true, AnswerMode::Typed) => {
+                self.draw_answer_check(ui, &sides.expected);
+                self.draw_grades(ui);
+            }
+            (false, AnswerMode::Flip) => {
+                ui.draw_text_huge("Enter: show answer  Tab: type it", 2, 123, Rgb565::CSS_GRAY);
+            }
+            (false, AnswerMode::Typed) => {
+                ui.fill_rect(20, 57, 200, 1, Rgb565::CSS_GRAY);
+                ui.draw_text_centered(&format!("{}_", self.typed_answer), 65, Rgb565::CSS_YELLOW);
+                ui.draw_text_huge("Enter: check  Tab: flip mode", 2, 123, Rgb565::CSS_GRAY);
+            }
+        }
+    }
+
+    fn sync_storage(&mut self, hal: &mut CardputerHal<'_>) {
+        self.now = hal.now();
+        for step in self.undo_elsewhere.drain(..) {
+            if let Err(e) = hal.undo_in_deck(&step) {
+                log::error!("error undo {} in deck {}: {:?}", step.action.name(), step.deck_id, e);
+            }
+        }
+        if self.unsaved_decks.is_empty() {
+            return;
+        }
+        // the journal goes first, a deck torn while saving is rebuilt from it
+        for entry in self.undone_log.drain(..) {
+            if let Err(e) = hal.remove_review_log_entry(&entry) {
+                log::warn!("undone grade left in the review log: {:?}", e);
+            }
+        }
+        for entry in self.pending_log.drain(..) {
+            if let Err(e) = hal.append_review_log(&entry) {
+                log::error!("error append review log: {:?}", e);
+            }
+        }
+        let Some(session) = &self.session else { return };
+        for deck in self.unsaved_decks.drain(..).filter_map(|id| session.deck(id)) {
+            if let Err(e) = hal.save_deck(deck) {
+                log::error!("error save deck {}: {:?}", deck.info.name, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, sync::Mutex};
+
+    use chrono::{Duration, TimeZone};
+    use rs_fsrs::State;
+
+    use super::*;
+    use crate::{cardputer_hal::memory::{FixedClock, MemoryDisplay, MemoryStorage, OfflineNetwork, ScriptedKeys}, logic::{deck::{deck_file::{read_deck, write_deck}, model::Deck}, review::review_log::{ReviewLogLoader, FILE_NAME}}};
+
+    const KEY_EVENTS: usize = 8;
+
+    /// The undo history is shared, a test undoing must not take a step of another one.
+    static VIEW_RUN: Mutex<()> = Mutex::new(());
+
+    /// Time of the HAL clock, far from the system clock so grading with the latter shows.
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap()
+    }
+
+    /// A deck of one note with the back `back`.
+    fn deck(back: String) -> Deck {
+        let mut deck = Deck::new(DeckInfo { id: 1, name: "Words".into() });
+        deck.add_note(vec!["кошка".into(), back], now() - Duration::hours(1));
+        deck
+    }
+
+    /// The deck saved on a memory SD card, the clock tells `now()`.
+    fn hal(deck: &Deck, keys: ScriptedKeys) -> (CardputerHal<'static>, Rc<RefCell<MemoryStorage>>) {
+        let storage = MemoryStorage::default().with_file(&deck.info.file_name(), &write_deck(deck).unwrap());
+        let storage = Rc::new(RefCell::new(storage));
+        let hal = CardputerHal::build(Box::new(MemoryDisplay::default()), Box::new(storage.clone()), Box::new(keys), Box::new(OfflineNetwork), Box::new(FixedClock { now: now() }));
+        (hal, storage)
+    }
+
+    fn run(keys: ScriptedKeys) -> (Rc<RefCell<MemoryStorage>>, DeckInfo) {
+        let _running = VIEW_RUN.lock().unwrap_or_else(|e| e.into_inner());
+        let deck = deck("cat".into());
+        let info = deck.info.clone();
+        let (mut hal, storage) = hal(&deck, keys);
+        let mut ui = CardworderUi::build(hal.take_screen());
+        let mut view = ReviewView::new(info.name.clone(), vec![info.clone()]);
+        view.init(&mut hal, &mut ui);
+        for _ in 0..KEY_EVENTS {
+            hal.update_keyboard_state();
+            assert!(view.update(&hal.keyboard_state).is_none());
+            view.draw(&mut ui);
+            view.sync_storage(&mut hal);
+        }
+        (storage, info)
+    }
+
+    fn saved_deck(storage: &Rc<RefCell<MemoryStorage>>, info: &DeckInfo) -> Deck {
+        read_deck(storage.borrow().file(&info.file_name()).unwrap()).unwrap()
+    }
+
+    fn review_log(storage: &Rc<RefCell<MemoryStorage>>) -> Vec<ReviewLogEntry> {
+        let mut loader = ReviewLogLoader::default();
+        storage.borrow().file(FILE_NAME).unwrap_or_default().lines().for_each(|line| loader.push_line(line));
+        loader.finish()
+    }
+
+    #[test]
+    fn grade_is_logged_and_saved() {
+        let mut keys = ScriptedKeys::default();
+        keys.tap(Scancode::Enter);
+        keys.tap(Scancode::_3);
+
+        let (storage, info) = run(keys);
+        let log = review_log(&storage);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].rating, Rating::Good);
+        assert_eq!(log[0].state_before, State::New);
+        assert_eq!(log[0].time, now());
+        let deck = saved_deck(&storage, &info);
+        let card = &deck.cards()[0];
+        assert_eq!(card.id, log[0].card_id);
+        assert_eq!(card.state(), State::Learning);
+        assert_eq!(card.fsrs.reps, 1);
+        assert_eq!(card.fsrs.last_review, now());
+        assert!(card.fsrs.due > now() && card.fsrs.due < now() + Duration::hours(1));
+    }
+
+    #[test]
+    fn undone_grade_is_taken_back_from_storage() {
+        let mut keys = ScriptedKeys::default();
+        keys.tap(Scancode::Enter);
+        keys.tap(Scancode::_1);
+        keys.push(KeyEvent::Pressed, Scancode::Ctrl);
+        keys.tap(Scancode::Z);
+        keys.push(KeyEvent::Released, Scancode::Ctrl);
+
+        let (storage, info) = run(keys);
+        assert!(review_log(&storage).is_empty());
+        assert!(storage.borrow().file(FILE_NAME).unwrap().trim().is_empty());
+        assert!(saved_deck(&storage, &info).cards()[0].is_new());
+    }
+
+    #[test]
+    fn too_big_decks_are_not_loaded() {
+        let deck = deck("cat ".repeat(MAX_STUDY_FILES_SIZE as usize / 4));
+        let mut keys = ScriptedKeys::default();
+        keys.push(KeyEvent::Pressed, Scancode::Fn);
+        keys.tap(Scancode::Tilde);
+        let (mut hal, storage) = hal(&deck, keys);
+        let mut ui = CardworderUi::build(hal.take_screen());
+        let mut view = ReviewView::new(deck.info.name.clone(), vec![deck.info.clone()]);
+        view.init(&mut hal, &mut ui);
+        assert!(view.session.is_none());
+        assert!(view.load_error.as_deref().is_some_and(|e| e.starts_with("Too big to study: ")));
+
+        // Esc leads back
+        let mut next = None;
+        for _ in 0..2 {
+            hal.update_keyboard_state();
+            next = view.update(&hal.keyboard_state);
+            view.sync_storage(&mut hal);
+        }
+        assert!(next.is_some());
+        assert_eq!(saved_deck(&storage, &deck.info), deck);
+    }
+}
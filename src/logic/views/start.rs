@@ -1,8 +1,6 @@
 use embedded_graphics::{pixelcolor::Rgb565, prelude::RgbColor};
-use esp_idf_svc::sntp::{EspSntp, SyncStatus};
-use esp_idf_sys::{setenv, tzset};
 
-use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, wifi::wifi::WifiConfig}, logic::{view_manager::CardputerView, views::main_menu::MainMenuView}, ui::cardworder_ui::CardworderUi, ResultExt};
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, platform::WifiConfig}, logic::{settings::Settings, view_manager::CardputerView, views::main_menu::MainMenuView}, ui::cardworder_ui::CardworderUi, ResultExt};
 
 pub struct StartView {
 }
@@ -21,16 +19,11 @@ impl CardputerView for StartView {
         ui.draw_starting_line("Starting...", Rgb565::BLACK, Rgb565::WHITE);
         ui.flip_buffer();
 
-        unsafe {
-            let env_tz = b"TZ\0";
-            let tz = b"GMT-3\0";
-            // TODO: move to a separate file
-            setenv(env_tz.as_ptr() as *const i8, tz.as_ptr() as *const i8, 1);
-            tzset();
-            // let tz = getenv(env_tz.as_ptr() as *const i8);
-            // let tz_str = CStr::from_ptr(tz).to_str().unwrap();
-            // log::info!("tz: {:?}", tz_str);
-        }
+        let settings = hal.load_settings().unwrap_or_else(|e| {
+            log::error!("error load settings: {:?}", e);
+            Settings::default()
+        });
+        hal.set_time_zone(&settings.tz);
 
 
         hal.create_wifi_file_if_non_exists(
@@ -52,15 +45,10 @@ impl CardputerView for StartView {
 
         hal.connect_wifi(wifi_config).unwrap_or_log("error connecting to wifi");
 
-        ui.draw_starting_line("Starting NTP...", Rgb565::BLACK, Rgb565::WHITE);
-        ui.flip_buffer();
-    
-        let ntp = EspSntp::new_default().unwrap();
-    
         ui.draw_starting_line("Awaiting NTP...", Rgb565::BLACK, Rgb565::WHITE);
         ui.flip_buffer();
     
-        while ntp.get_sync_status() != SyncStatus::Completed {}
+        hal.sync_time().unwrap_or_log("error syncing time");
     
         ui.draw_starting_line("Got NTP!", Rgb565::BLACK, Rgb565::WHITE);
         ui.flip_buffer();
@@ -0,0 +1,173 @@
+use embedded_graphics::{pixelcolor::Rgb565, prelude::{Point, RgbColor, Size, WebColors}, primitives::Rectangle};
+
+use crate::{cardputer_hal::{cardputer_hal::{CardputerHal, KeyboardState}, input::{keyboard::PressedSymbol, keyboard_io::KeyEvent}}, logic::{search::{deck_search::DeckSearch, history::ReviewHistory, query::{Query, SearchContext}}, settings::Settings, stats::collection_stats::{CollectionStats, DIFFICULTY_BUCKETS, FORECAST_DAYS, INTERVAL_BUCKETS, RETENTION_DAYS}, view_manager::CardputerView, views::main_menu::MainMenuView}, ui::{cardworder_ui::CardworderUi, chart::BarChart}};
+
+/// Area of the charts between the top line and the key hints.
+const CHART_TOP: i32 = 14;
+const CHART_HEIGHT: u32 = 104;
+const CHART_WIDTH: u32 = 236;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatsPage {
+    Forecast,
+    Retention,
+    Cards,
+    Histograms,
+}
+
+impl StatsPage {
+    const ALL: [StatsPage; 4] = [StatsPage::Forecast, StatsPage::Retention, StatsPage::Cards, StatsPage::Histograms];
+}
+
+/// Stats of all decks, a page at a time. Decks and the review log are read line by line,
+/// only the numbers stay in memory.
+#[derive(Default)]
+pub struct StatsView {
+    stats: Option<CollectionStats>,
+    page: usize,
+    error: Option<String>,
+}
+
+impl StatsView {
+    fn load(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) -> anyhow::Result<()> {
+        let index = hal.load_deck_index()?;
+        let settings = hal.load_settings().unwrap_or_else(|e| {
+            log::error!("error load settings: {:?}", e);
+            Settings::default()
+        });
+        let day = settings.study_day();
+        let now = hal.now();
+        let mut stats = CollectionStats::new(&day, now);
+
+        let query = Query::default();
+        let history = ReviewHistory::default();
+        let context = SearchContext { day, now, history: &history };
+        let mut search = DeckSearch::new(&query);
+        for info in &index.decks {
+            ui.draw_starting_line(&format!("Counting {}...", info.name), Rgb565::BLACK, Rgb565::WHITE);
+            ui.flip_buffer();
+            if let Err(e) = hal.read_lines(&info.file_name(), |line| search.push_line(line, &context, |_, _, card| stats.push_card(card, &day))) {
+                log::error!("error read deck {}: {:?}", info.name, e);
+            }
+        }
+        if search.skipped_lines() > 0 {
+            log::warn!("stats skipped {} damaged deck lines", search.skipped_lines());
+        }
+
+        ui.draw_starting_line("Reading review log...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+        hal.read_review_log(|entry| stats.push_review(entry, &day))?;
+
+        self.stats = Some(stats);
+        Ok(())
+    }
+
+    fn chart_area(top: i32, height: u32) -> Rectangle {
+        Rectangle::new(Point::new(2, top), Size::new(CHART_WIDTH, height))
+    }
+
+    fn draw_forecast(stats: &CollectionStats, ui: &mut CardworderUi<'_>) {
+        let values = stats.forecast.iter().map(|n| Some(*n as f32)).collect();
+        let total: usize = stats.forecast.iter().sum();
+        let mut chart = BarChart::new(&format!("Due in {} days: {}", FORECAST_DAYS, total), values, Rgb565::CSS_LIME_GREEN);
+        chart.labels = vec![(0, "today".to_string()), (FORECAST_DAYS / 2, format!("{}d", FORECAST_DAYS / 2)), (FORECAST_DAYS - 1, format!("{}d", FORECAST_DAYS - 1))];
+        ui.draw_chart(&chart, Self::chart_area(CHART_TOP, CHART_HEIGHT));
+    }
+
+    fn draw_retention(stats: &CollectionStats, ui: &mut CardworderUi<'_>) {
+        let values = stats.retention.iter().map(|day| day.rate().map(|r| r * 100.0)).collect();
+        let total = stats.total_retention();
+        let title = match total.rate() {
+            Some(rate) => format!("True retention: {:.0}% of {}", rate * 100.0, total.reviews),
+            None => "True retention: no reviews".to_string(),
+        };
+        let mut chart = BarChart::new(&title, values, Rgb565::CSS_DEEP_SKY_BLUE);
+        chart.max = Some(100.0);
+        chart.unit = "%";
+        chart.labels = vec![(0, format!("-{}d", RETENTION_DAYS - 1)), (RETENTION_DAYS - 1, "today".to_string())];
+        ui.draw_chart(&chart, Self::chart_area(CHART_TOP, CHART_HEIGHT));
+    }
+
+    fn draw_cards(stats: &CollectionStats, ui: &mut CardworderUi<'_>) {
+        let counts = stats.cards;
+        let rows = [
+            ("New", counts.new, Rgb565::CSS_DEEP_SKY_BLUE),
+            ("Learning", counts.learning, Rgb565::CSS_TOMATO),
+            ("Review", counts.review, Rgb565::CSS_LIME_GREEN),
+            ("Suspended", counts.suspended, Rgb565::CSS_GRAY),
+            ("Total", counts.total(), Rgb565::WHITE),
+        ];
+        for (i, (name, count, color)) in rows.iter().enumerate() {
+            let y = 20 + 16 * i as i32;
+            ui.draw_text_huge(name, 40, y, *color);
+            ui.draw_text_huge(&format!("{:>6}", count), 150, y, *color);
+        }
+    }
+
+    fn draw_histograms(stats: &CollectionStats, ui: &mut CardworderUi<'_>) {
+        let half = CHART_HEIGHT / 2;
+
+        let values = stats.intervals.iter().map(|n| Some(*n as f32)).collect();
+        let mut intervals = BarChart::new("Review intervals", values, Rgb565::CSS_LIME_GREEN);
+        intervals.labels = INTERVAL_BUCKETS.iter().enumerate().map(|(i, (_, label))| (i, label.to_string())).collect();
+        ui.draw_chart(&intervals, Self::chart_area(CHART_TOP, half - 2));
+
+        let values = stats.difficulty.iter().map(|n| Some(*n as f32)).collect();
+        let mut difficulty = BarChart::new("Difficulty", values, Rgb565::CSS_ORANGE);
+        difficulty.labels = (0..DIFFICULTY_BUCKETS).map(|i| (i, (i + 1).to_string())).collect();
+        ui.draw_chart(&difficulty, Self::chart_area(CHART_TOP + half as i32, half));
+    }
+}
+
+impl CardputerView for StatsView {
+    fn is_need_top_line(&self) -> bool {
+        true
+    }
+
+    fn is_need_clear_on_update(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, hal: &mut CardputerHal<'_>, ui: &mut CardworderUi<'_>) {
+        ui.clear(Rgb565::BLACK);
+        ui.draw_starting_line("Loading stats...", Rgb565::BLACK, Rgb565::WHITE);
+        ui.flip_buffer();
+
+        if let Err(e) = self.load(hal, ui) {
+            log::error!("error load stats: {:?}", e);
+            self.error = Some("Can't load stats".to_string());
+        }
+    }
+
+    fn update(&mut self, keyboard_state: &KeyboardState) -> Option<Box<dyn CardputerView>> {
+        let Some((KeyEvent::Pressed, symbol)) = keyboard_state.pressed else {
+            return None;
+        };
+
+        match symbol {
+            PressedSymbol::Esc => return Some(Box::new(MainMenuView::default())),
+            PressedSymbol::ArrowRight => self.page = (self.page + 1) % StatsPage::ALL.len(),
+            PressedSymbol::ArrowLeft => self.page = (self.page + StatsPage::ALL.len() - 1) % StatsPage::ALL.len(),
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, ui: &mut CardworderUi<'_>) {
+        let Some(stats) = &self.stats else {
+            let error = self.error.as_deref().unwrap_or("No stats");
+            ui.draw_text_centered(error, 50, Rgb565::CSS_TOMATO);
+            ui.draw_text_huge("Esc: back", 2, 123, Rgb565::CSS_GRAY);
+            return;
+        };
+
+        match StatsPage::ALL[self.page] {
+            StatsPage::Forecast => Self::draw_forecast(stats, ui),
+            StatsPage::Retention => Self::draw_retention(stats, ui),
+            StatsPage::Cards => Self::draw_cards(stats, ui),
+            StatsPage::Histograms => Self::draw_histograms(stats, ui),
+        }
+
+        ui.draw_text_huge(&format!("<>: page {}/{}  Esc: back", self.page + 1, StatsPage::ALL.len()), 2, 123, Rgb565::CSS_GRAY);
+    }
+}
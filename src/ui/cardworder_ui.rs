@@ -1,4 +1,7 @@
-use embedded_fps::FPS;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDateTime, Timelike};
 use embedded_graphics::geometry::AnchorPoint;
 use embedded_graphics::mono_font::iso_8859_5::FONT_6X13;
 use embedded_graphics::mono_font::iso_8859_5::FONT_6X13_BOLD;
@@ -17,79 +20,70 @@ use embedded_text::TextBox;
 
 use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
 
-use embedded_time::rate::Fraction;
-use esp_idf_sys::{localtime_r, time, time_t, tm};
-use u8g2_fonts::types::{FontColor, VerticalPosition};
+use embedded_graphics_framebuf::FrameBuf;
+use u8g2_fonts::types::{FontColor, HorizontalAlignment as FontAlignment, VerticalPosition};
 use u8g2_fonts::{fonts, FontRenderer};
 
 use crate::cardputer_hal::input::keyboard::InputLanguage;
 use crate::cardputer_hal::input::keyboard::InputState;
 use crate::cardputer_hal::input::keyboard::PressedSymbol;
 use crate::cardputer_hal::input::keyboard_io::KeyEvent;
-use crate::cardputer_hal::screen::cardputer_screen::CardputerScreen;
+use crate::cardputer_hal::platform::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::ui::chart::BarChart;
+use crate::ui::framebuffer::CardputerFramebuffer;
+use crate::ui::text_field::TextField;
+
+/// Decoration of a single character drawn by `CardworderUi::draw_char_cells`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellMark {
+    None,
+    Underline,
+    StrikeThrough,
+}
 
-pub struct CardworderClock {}
 pub struct CardworderUi<'a> {
-    screen: CardputerScreen<'a>,
-    fps_counter: FPS<45, CardworderClock>,
+    screen: FrameBuf<Rgb565, CardputerFramebuffer>,
+    display: Box<dyn Display + 'a>,
+    /// When the frames of the last second were shown.
+    frame_times: VecDeque<Instant>,
     debug_small_text_style: MonoTextStyle<'a, Rgb565>,
     pub show_fps: bool,
 }
 
-impl Default for CardworderClock {
-    fn default() -> Self {
-        CardworderClock {}
-    }
-}
-
-impl embedded_time::clock::Clock for CardworderClock {
-    type T = u64;
-    const SCALING_FACTOR: Fraction = Fraction::new(1, 1000000);
-
-    fn try_now(&self) -> Result<embedded_time::Instant<Self>, embedded_time::clock::Error> {
-        let now = unsafe { esp_idf_svc::sys::esp_timer_get_time() };
-        Ok(embedded_time::Instant::<Self>::new(now as u64))
-    }
-
-    fn new_timer<Dur: embedded_time::duration::Duration>(
-        &self,
-        duration: Dur,
-    ) -> embedded_time::Timer<
-        embedded_time::timer::param::OneShot,
-        embedded_time::timer::param::Armed,
-        Self,
-        Dur,
-    >
-    where
-        Dur: embedded_time::fixed_point::FixedPoint,
-    {
-        embedded_time::Timer::<
-            embedded_time::timer::param::None,
-            embedded_time::timer::param::None,
-            Self,
-            Dur,
-        >::new(&self, duration)
-    }
-}
-
 impl CardworderUi<'_> {
-    pub fn build<'b>(screen: CardputerScreen<'b>) -> CardworderUi<'b> {
-        let fps_counter = FPS::<45, _>::new(CardworderClock::default());
+    pub fn build<'b>(display: Box<dyn Display + 'b>) -> CardworderUi<'b> {
+        let framebuffer = CardputerFramebuffer::new(Rgb565::CSS_BLACK);
+        let screen = FrameBuf::new(framebuffer, SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize);
         let debug_small_text_style = MonoTextStyle::new(&FONT_4X6, Rgb565::WHITE);
         CardworderUi {
-            screen: screen,
-            fps_counter: fps_counter,
+            screen,
+            display,
+            frame_times: VecDeque::new(),
             debug_small_text_style,
             show_fps: false,
         }
     }
 
     pub fn clear(&mut self, color: Rgb565) {
-        self.screen.clear(color);
+        self.screen.clear(color).unwrap();
+    }
+
+    /// The frame being drawn, row by row.
+    pub fn frame(&self) -> &[Rgb565] {
+        &self.screen.data.data
+    }
+
+    fn tick_fps(&mut self) -> usize {
+        let now = Instant::now();
+        while self.frame_times.front().is_some_and(|t| now.duration_since(*t) > Duration::from_secs(1)) {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(now);
+        self.frame_times.len()
     }
 
     pub fn flip_buffer(&mut self) {
-        let fps = self.fps_counter.tick();
+        let fps = self.tick_fps();
         if self.show_fps {
             let fps_text = format!("FPS: {}", fps);
             let text_style = TextStyleBuilder::new()
@@ -115,15 +109,21 @@ impl CardworderUi<'_> {
 
             text.draw(&mut self.screen).unwrap();
         }
-        self.screen.flush_framebuffer();
+        if let Err(e) = self.display.show_frame(&self.screen.data.data) {
+            log::error!("error show frame: {:?}", e);
+        }
     }
 
     pub fn backlight_off(&mut self) {
-        self.screen.backlight_off();
+        if let Err(e) = self.display.set_backlight(false) {
+            log::error!("error switch backlight: {:?}", e);
+        }
     }
 
     pub fn backlight_on(&mut self) {
-        self.screen.backlight_on();
+        if let Err(e) = self.display.set_backlight(true) {
+            log::error!("error switch backlight: {:?}", e);
+        }
     }
 
     pub fn draw_starting_line(&mut self, text: &str, bg_color: Rgb565, font_color: Rgb565) {
@@ -157,6 +157,7 @@ impl CardworderUi<'_> {
         &mut self,
         input_state: &InputState,
         key_event: &Option<(KeyEvent, PressedSymbol)>,
+        local_time: NaiveDateTime,
     ) {
         let top_line_area = Rectangle {
             top_left: Point { x: 0, y: 0 },
@@ -274,25 +275,7 @@ impl CardworderUi<'_> {
 
         let time_x = 240 - 1 - 4 * 8;
 
-        let mut tm = tm {
-            tm_sec: 0,
-            tm_min: 0,
-            tm_hour: 0,
-            tm_mday: 0,
-            tm_mon: 0,
-            tm_year: 0,
-            tm_wday: 0,
-            tm_yday: 0,
-            tm_isdst: 0,
-        };
-        let mut now_time: time_t = 0;
-        unsafe {
-            // TODO: move to a separate file
-            time(&mut now_time);
-            localtime_r(&now_time, &mut tm);
-        }
-
-        let formatted = format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec);
+        let formatted = format!("{:02}:{:02}:{:02}", local_time.hour(), local_time.minute(), local_time.second());
 
         font1
             .render(
@@ -311,6 +294,100 @@ impl CardworderUi<'_> {
         font1.render(text, Point::new(x, y), VerticalPosition::Top, FontColor::Transparent(font_color), &mut self.screen).unwrap();
     }
 
+    pub fn draw_text_small(&mut self, text: &str, x: i32, y: i32, font_color: Rgb565) {
+        let font = FontRenderer::new::<fonts::u8g2_font_4x6_t_cyrillic>().with_ignore_unknown_chars(true);
+        font.render(text, Point::new(x, y), VerticalPosition::Top, FontColor::Transparent(font_color), &mut self.screen).unwrap();
+    }
+
+    /// Draws a line of card text centered on the screen, falls back to a smaller font if it doesn't fit.
+    pub fn draw_text_centered(&mut self, text: &str, y: i32, font_color: Rgb565) {
+        let width = self.screen.width() as i32;
+        let position = Point::new(width / 2, y);
+
+        let big_font = FontRenderer::new::<fonts::u8g2_font_9x15_t_cyrillic>().with_ignore_unknown_chars(true);
+        let big_fits = big_font
+            .get_rendered_dimensions_aligned(text, position, VerticalPosition::Top, FontAlignment::Center)
+            .ok()
+            .flatten()
+            .map(|bb| bb.size.width as i32 <= width)
+            .unwrap_or(true);
+
+        let font = if big_fits {
+            big_font
+        } else {
+            FontRenderer::new::<fonts::u8g2_font_6x12_t_cyrillic>().with_ignore_unknown_chars(true)
+        };
+        font.render_aligned(text, position, VerticalPosition::Top, FontAlignment::Center, FontColor::Transparent(font_color), &mut self.screen).unwrap();
+    }
+
+    /// Draws differently colored parts of one line centered on the screen, falls back to a smaller font
+    /// if they don't fit, like `draw_text_centered`.
+    pub fn draw_spans_centered(&mut self, spans: &[(&str, Rgb565)], y: i32) {
+        let width = self.screen.width() as i32;
+        let line_width = |font: &FontRenderer| -> i32 {
+            spans
+                .iter()
+                .filter_map(|(text, _)| font.get_rendered_dimensions(*text, Point::zero(), VerticalPosition::Top).ok())
+                .map(|dimensions| dimensions.advance.x)
+                .sum()
+        };
+
+        let big_font = FontRenderer::new::<fonts::u8g2_font_9x15_t_cyrillic>().with_ignore_unknown_chars(true);
+        let big_width = line_width(&big_font);
+        let (font, text_width) = if big_width <= width {
+            (big_font, big_width)
+        } else {
+            let font = FontRenderer::new::<fonts::u8g2_font_6x12_t_cyrillic>().with_ignore_unknown_chars(true);
+            let text_width = line_width(&font);
+            (font, text_width)
+        };
+
+        let mut x = (width - text_width).max(0) / 2;
+        for (text, color) in spans {
+            let rendered = font.render(*text, Point::new(x, y), VerticalPosition::Top, FontColor::Transparent(*color), &mut self.screen).unwrap();
+            x += rendered.advance.x;
+        }
+    }
+
+    /// Draws characters in fixed 6px cells centered on the screen, wrapping when a line is full.
+    /// Returns the height of the drawn block.
+    pub fn draw_char_cells(&mut self, cells: &[(char, Rgb565, CellMark)], y: i32) -> i32 {
+        const CELL_WIDTH: i32 = 6;
+        const LINE_HEIGHT: i32 = 13;
+        let font = FontRenderer::new::<fonts::u8g2_font_6x12_t_cyrillic>().with_ignore_unknown_chars(true);
+        let width = self.screen.width() as i32;
+        let cells_per_line = (width / CELL_WIDTH) as usize;
+
+        let mut line_y = y;
+        for line in cells.chunks(cells_per_line) {
+            let mut x = (width - line.len() as i32 * CELL_WIDTH) / 2;
+            for (c, color, mark) in line {
+                font.render(*c, Point::new(x, line_y), VerticalPosition::Top, FontColor::Transparent(*color), &mut self.screen).unwrap();
+                match mark {
+                    CellMark::Underline => self.fill_rect(x, line_y + 11, CELL_WIDTH as u32, 1, *color),
+                    CellMark::StrikeThrough => self.fill_rect(x, line_y + 6, CELL_WIDTH as u32, 1, *color),
+                    CellMark::None => {}
+                }
+                x += CELL_WIDTH;
+            }
+            line_y += LINE_HEIGHT;
+        }
+        line_y - y
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Rgb565) {
+        let area = Rectangle::new(Point::new(x, y), Size::new(width, height));
+        self.screen.fill_solid(&area, color).unwrap();
+    }
+
+    pub fn draw_chart(&mut self, chart: &BarChart, area: Rectangle) {
+        chart.draw(area, &mut self.screen).unwrap();
+    }
+
+    pub fn draw_text_field(&mut self, field: &TextField, is_focused: bool) {
+        field.draw(&mut self.screen, is_focused).unwrap();
+    }
+
     pub fn draw_long_text(&mut self, is_bold: bool) {
         let text = "- В мои 27 меня уже ничем не удивить!\n- Тебе 35.\n- Что, блин?!";
         let font1 = FontRenderer::new::<fonts::u8g2_font_4x6_t_cyrillic>();
@@ -353,7 +430,7 @@ impl CardworderUi<'_> {
                     .unwrap()
                     .anchor_y(embedded_graphics::geometry::AnchorY::Bottom) as i32,
             ),
-            Size::new(self.screen.framebuffer.width() as u32 - 16, 0),
+            Size::new(self.screen.width() as u32 - 16, 0),
         );
 
         let text_box = TextBox::with_textbox_style(text, bounds, character_style, textbox_style);
@@ -0,0 +1,250 @@
+//! Bar chart drawn with embedded-graphics primitives into whatever area a view gives it:
+//!
+//! ```text
+//! Due in 30 days                  42
+//! █ ▆ ▇   ▃ ▅ ▂ ▁ ...
+//! ───────────────────────────────────
+//! today        15d                29d
+//! ```
+//! The layout is computed apart from the drawing, so it works for any area and draw target.
+
+use embedded_graphics::{
+    pixelcolor::Rgb565,
+    prelude::{DrawTarget, Point, Size, WebColors},
+    primitives::Rectangle,
+};
+use u8g2_fonts::{
+    fonts,
+    types::{FontColor, VerticalPosition},
+    Error as FontError, FontRenderer,
+};
+
+/// Title and label rows fit the 4x6 font.
+const TEXT_ROW_HEIGHT: u32 = 7;
+const AXIS_COLOR: Rgb565 = Rgb565::CSS_DIM_GRAY;
+const TEXT_COLOR: Rgb565 = Rgb565::CSS_GRAY;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarChart {
+    pub title: String,
+    /// `None` leaves a gap, e.g. a day without reviews.
+    pub values: Vec<Option<f32>>,
+    /// Texts under bars, by bar index.
+    pub labels: Vec<(usize, String)>,
+    pub color: Rgb565,
+    /// Top of the scale, the largest value when `None`.
+    pub max: Option<f32>,
+    /// Appended to the top of the scale shown next to the title, e.g. `%`.
+    pub unit: &'static str,
+}
+
+/// Where the parts of a chart go in its area.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartLayout {
+    pub title: Point,
+    /// Right end of the top of the scale.
+    pub scale: Point,
+    pub plot: Rectangle,
+    /// A bar per value, `None` for gaps and zeros.
+    pub bars: Vec<Option<Rectangle>>,
+    /// Center of the text under a bar, by index into `BarChart::labels`.
+    pub labels: Vec<Point>,
+    pub max: f32,
+}
+
+impl BarChart {
+    pub fn new(title: &str, values: Vec<Option<f32>>, color: Rgb565) -> Self {
+        Self {
+            title: title.to_string(),
+            values,
+            labels: Vec::new(),
+            color,
+            max: None,
+            unit: "",
+        }
+    }
+
+    /// Bars share the plot width evenly with a pixel between them when there is room,
+    /// the pixels left over are split to both sides.
+    pub fn layout(&self, area: Rectangle) -> ChartLayout {
+        let top = area.top_left.y;
+        let plot_height = area.size.height.saturating_sub(2 * TEXT_ROW_HEIGHT + 1);
+        let plot = Rectangle::new(
+            Point::new(area.top_left.x, top + TEXT_ROW_HEIGHT as i32),
+            Size::new(area.size.width, plot_height),
+        );
+
+        let count = self.values.len().max(1) as u32;
+        let slot = (plot.size.width / count).max(1);
+        let gap = if slot >= 3 { 1 } else { 0 };
+        let left = plot.top_left.x + (plot.size.width.saturating_sub(slot * count) / 2) as i32;
+        let bottom = plot.top_left.y + plot_height as i32;
+
+        let max = self.max.unwrap_or_else(|| {
+            self.values
+                .iter()
+                .flatten()
+                .fold(0.0, |max: f32, v| max.max(*v))
+        });
+        let bars = self
+            .values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let value = value.filter(|v| *v > 0.0 && max > 0.0)?;
+                let height = ((value / max).min(1.0) * plot_height as f32)
+                    .round()
+                    .max(1.0) as u32;
+                Some(Rectangle::new(
+                    Point::new(left + (slot * i as u32) as i32, bottom - height as i32),
+                    Size::new(slot - gap, height),
+                ))
+            })
+            .collect();
+        let labels = self
+            .labels
+            .iter()
+            .map(|(i, _)| Point::new(left + (slot * *i as u32 + slot / 2) as i32, bottom + 2))
+            .collect();
+
+        ChartLayout {
+            title: area.top_left,
+            scale: Point::new(area.top_left.x + area.size.width as i32, top),
+            plot,
+            bars,
+            labels,
+            max,
+        }
+    }
+
+    pub fn draw<D>(&self, area: Rectangle, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let layout = self.layout(area);
+        let font =
+            FontRenderer::new::<fonts::u8g2_font_4x6_t_cyrillic>().with_ignore_unknown_chars(true);
+        let text_width = |text: &str| {
+            font.get_rendered_dimensions(text, Point::zero(), VerticalPosition::Top)
+                .map_or(0, |d| d.advance.x)
+        };
+        let area_right = area.top_left.x + area.size.width as i32;
+        let draw_text = |text: &str, x: i32, y: i32, target: &mut D| {
+            // keep the text inside the area
+            let x = x.min(area_right - text_width(text)).max(area.top_left.x);
+            font.render(
+                text,
+                Point::new(x, y),
+                VerticalPosition::Top,
+                FontColor::Transparent(TEXT_COLOR),
+                target,
+            )
+            .map(|_| ())
+            .or_else(|e| match e {
+                FontError::DisplayError(e) => Err(e),
+                _ => Ok(()),
+            })
+        };
+
+        draw_text(&self.title, layout.title.x, layout.title.y, target)?;
+        let scale = format!("{}{}", layout.max.round(), self.unit);
+        draw_text(
+            &scale,
+            layout.scale.x - text_width(&scale),
+            layout.scale.y,
+            target,
+        )?;
+
+        for bar in layout.bars.iter().flatten() {
+            target.fill_solid(bar, self.color)?;
+        }
+        let axis = Rectangle::new(
+            Point::new(
+                layout.plot.top_left.x,
+                layout.plot.top_left.y + layout.plot.size.height as i32,
+            ),
+            Size::new(layout.plot.size.width, 1),
+        );
+        target.fill_solid(&axis, AXIS_COLOR)?;
+
+        for ((_, label), center) in self.labels.iter().zip(&layout.labels) {
+            draw_text(label, center.x - text_width(label) / 2, center.y, target)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chart(values: &[Option<f32>]) -> BarChart {
+        BarChart::new("Due", values.to_vec(), Rgb565::CSS_GREEN)
+    }
+
+    fn area(width: u32, height: u32) -> Rectangle {
+        Rectangle::new(Point::new(10, 20), Size::new(width, height))
+    }
+
+    #[test]
+    fn bars_fill_the_plot() {
+        let mut chart = chart(&[Some(10.0), None, Some(5.0), Some(0.0)]);
+        chart.labels = vec![(0, "today".into()), (3, "3d".into())];
+        let layout = chart.layout(area(100, 50));
+
+        assert_eq!(layout.title, Point::new(10, 20));
+        assert_eq!(layout.scale, Point::new(110, 20));
+        assert_eq!(
+            layout.plot,
+            Rectangle::new(Point::new(10, 27), Size::new(100, 35))
+        );
+        assert_eq!(layout.max, 10.0);
+        // 25 pixels a bar with a gap, they stand on the bottom of the plot
+        assert_eq!(
+            layout.bars,
+            [
+                Some(Rectangle::new(Point::new(10, 27), Size::new(24, 35))),
+                None,
+                Some(Rectangle::new(Point::new(60, 44), Size::new(24, 18))),
+                None,
+            ]
+        );
+        assert_eq!(layout.labels, [Point::new(22, 64), Point::new(97, 64)]);
+    }
+
+    #[test]
+    fn leftover_pixels_go_to_both_sides() {
+        let layout = chart(&[Some(1.0); 7]).layout(area(10, 30));
+        // a pixel per bar leaves no room for gaps
+        let bars: Vec<Rectangle> = layout.bars.into_iter().flatten().collect();
+        assert_eq!(bars.len(), 7);
+        assert_eq!(bars[0].top_left.x, 11);
+        assert_eq!(bars[6].top_left.x, 17);
+        assert!(bars.iter().all(|bar| bar.size.width == 1));
+
+        let layout = chart(&[Some(1.0); 3]).layout(area(100, 30));
+        assert_eq!(layout.bars[0].unwrap().top_left.x, 10);
+        assert_eq!(layout.bars[2].unwrap().size.width, 32);
+    }
+
+    #[test]
+    fn scale_can_be_fixed() {
+        let mut chart = chart(&[Some(0.5), Some(2.0), Some(0.001)]);
+        chart.max = Some(1.0);
+        let layout = chart.layout(area(30, 35));
+        let heights: Vec<u32> = layout
+            .bars
+            .iter()
+            .map(|bar| bar.unwrap().size.height)
+            .collect();
+        // over the top is cut, a tiny value still shows
+        assert_eq!(heights, [10, 20, 1]);
+        assert_eq!(layout.max, 1.0);
+
+        // nothing to draw in a chart of zeros or a too small area
+        let layout = BarChart::new("", vec![Some(0.0), None], Rgb565::CSS_RED).layout(area(20, 40));
+        assert_eq!(layout.bars, [None, None]);
+        assert_eq!(layout.max, 0.0);
+        assert_eq!(chart.layout(area(30, 10)).plot.size.height, 0);
+    }
+}
@@ -3,10 +3,10 @@ use core::iter;
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics_framebuf::backends::FrameBufferBackend;
 
-use super::display::{DISPLAY_SIZE_HEIGHT, DISPLAY_SIZE_WIDTH};
+use crate::cardputer_hal::platform::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
-const DISPLAY_SIZE_WIDTH_U: usize = DISPLAY_SIZE_WIDTH as usize;
-const DISPLAY_SIZE_HEIGHT_U: usize = DISPLAY_SIZE_HEIGHT as usize;
+const DISPLAY_SIZE_WIDTH_U: usize = SCREEN_WIDTH as usize;
+const DISPLAY_SIZE_HEIGHT_U: usize = SCREEN_HEIGHT as usize;
 
 pub struct CardputerFramebuffer {
     pub data: Vec<Rgb565>,
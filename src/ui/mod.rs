@@ -1 +1,4 @@
 pub mod cardworder_ui;
+pub mod chart;
+pub mod framebuffer;
+pub mod text_field;
\ No newline at end of file
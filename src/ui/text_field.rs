@@ -0,0 +1,683 @@
+//! Editable text drawn in a rectangle of the screen, one line scrolling sideways or
+//! wrapped lines scrolling up and down.
+//!
+//! The cursor moves over user-perceived characters: a char together with the combining marks
+//! after it, so `е́` with a stress mark is stepped over and deleted as one. The clipboard is
+//! shared by every field of the app.
+
+use std::{ops::Range, sync::Mutex};
+
+use embedded_graphics::{
+    pixelcolor::Rgb565,
+    prelude::{DrawTarget, Point, Size, WebColors},
+    primitives::Rectangle,
+};
+use u8g2_fonts::{
+    fonts,
+    types::{FontColor, VerticalPosition},
+    Error as FontError, FontRenderer,
+};
+use unicode_normalization::char::is_combining_mark;
+
+use crate::cardputer_hal::{
+    cardputer_hal::KeyboardState,
+    input::{
+        keyboard::PressedSymbol,
+        keyboard_io::{KeyEvent, Scancode},
+    },
+};
+
+/// Cell of the 6x12 font.
+pub const CHAR_WIDTH: u32 = 6;
+pub const LINE_HEIGHT: u32 = 13;
+const TEXT_COLOR: Rgb565 = Rgb565::CSS_WHITE;
+const SELECTION_COLOR: Rgb565 = Rgb565::CSS_STEEL_BLUE;
+const CURSOR_COLOR: Rgb565 = Rgb565::CSS_LIGHT_BLUE;
+
+static CLIPBOARD: Mutex<String> = Mutex::new(String::new());
+
+/// What was copied or cut last, kept until the device is turned off.
+pub fn clipboard_text() -> String {
+    CLIPBOARD
+        .lock()
+        .map(|text| text.clone())
+        .unwrap_or_default()
+}
+
+fn set_clipboard_text(text: &str) {
+    if let Ok(mut clipboard) = CLIPBOARD.lock() {
+        *clipboard = text.to_string();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    /// Newlines are not typed in, the line scrolls sideways.
+    SingleLine,
+    /// Lines wrap at spaces and scroll up and down.
+    Multiline,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextField {
+    text: String,
+    /// Byte offset, always at the start of a character.
+    cursor: usize,
+    /// The other end of the selection.
+    anchor: Option<usize>,
+    mode: TextMode,
+    area: Rectangle,
+    /// First visible character of a single line, first visible line of multiline text.
+    scroll: usize,
+}
+
+impl TextField {
+    pub fn new(text: &str, mode: TextMode, area: Rectangle) -> Self {
+        let mut field = Self {
+            text: String::new(),
+            cursor: 0,
+            anchor: None,
+            mode,
+            area,
+            scroll: 0,
+        };
+        field.set_text(text);
+        field
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text and puts the cursor at its end.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = self.accepted(text);
+        self.cursor = self.text.len();
+        self.anchor = None;
+        self.keep_cursor_visible();
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn area(&self) -> Rectangle {
+        self.area
+    }
+
+    pub fn set_area(&mut self, area: Rectangle) {
+        self.area = area;
+        self.keep_cursor_visible();
+    }
+
+    /// Byte range of the selected text, `None` if nothing is selected.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor.filter(|a| *a != self.cursor)?;
+        Some(anchor.min(self.cursor)..anchor.max(self.cursor))
+    }
+
+    pub fn selected_text(&self) -> Option<&str> {
+        self.selection().map(|range| &self.text[range])
+    }
+
+    /// Types text in place of the selection.
+    pub fn insert_str(&mut self, text: &str) {
+        let text = self.accepted(text);
+        self.delete_selection();
+        self.text.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+        self.keep_cursor_visible();
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.insert_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Deletes the selection or the character before the cursor, a whole word with `by_word`.
+    pub fn backspace(&mut self, by_word: bool) {
+        if !self.delete_selection() {
+            let start = if by_word {
+                self.word_start(self.cursor)
+            } else {
+                prev_boundary(&self.text, self.cursor)
+            };
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+        self.keep_cursor_visible();
+    }
+
+    /// Deletes the selection or the character after the cursor, a whole word with `by_word`.
+    pub fn delete(&mut self, by_word: bool) {
+        if !self.delete_selection() {
+            let end = if by_word {
+                self.word_end(self.cursor)
+            } else {
+                next_boundary(&self.text, self.cursor)
+            };
+            self.text.replace_range(self.cursor..end, "");
+        }
+        self.keep_cursor_visible();
+    }
+
+    pub fn move_left(&mut self, select: bool, by_word: bool) {
+        let to = match self.selection() {
+            Some(range) if !select => range.start,
+            _ if by_word => self.word_start(self.cursor),
+            _ => prev_boundary(&self.text, self.cursor),
+        };
+        self.move_to(to, select);
+    }
+
+    pub fn move_right(&mut self, select: bool, by_word: bool) {
+        let to = match self.selection() {
+            Some(range) if !select => range.end,
+            _ if by_word => self.word_end(self.cursor),
+            _ => next_boundary(&self.text, self.cursor),
+        };
+        self.move_to(to, select);
+    }
+
+    /// To the start of the line the cursor is on.
+    pub fn home(&mut self, select: bool) {
+        let lines = self.lines();
+        let to = lines[line_of(&lines, self.cursor)].start;
+        self.move_to(to, select);
+    }
+
+    /// To the end of the line the cursor is on.
+    pub fn end(&mut self, select: bool) {
+        let lines = self.lines();
+        let to = lines[line_of(&lines, self.cursor)].end;
+        self.move_to(to, select);
+    }
+
+    /// To the line above keeping the column, returns false on the first line.
+    pub fn move_up(&mut self, select: bool) -> bool {
+        self.move_lines(-1, select)
+    }
+
+    /// To the line below keeping the column, returns false on the last line.
+    pub fn move_down(&mut self, select: bool) -> bool {
+        self.move_lines(1, select)
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+        self.keep_cursor_visible();
+    }
+
+    pub fn copy(&self) {
+        if let Some(text) = self.selected_text() {
+            set_clipboard_text(text);
+        }
+    }
+
+    pub fn cut(&mut self) {
+        self.copy();
+        self.delete_selection();
+        self.keep_cursor_visible();
+    }
+
+    pub fn paste(&mut self) {
+        self.insert_str(&clipboard_text());
+    }
+
+    /// Byte ranges of the lines shown, without the newlines between them.
+    pub fn lines(&self) -> Vec<Range<usize>> {
+        if self.mode == TextMode::SingleLine {
+            return std::iter::once(0..self.text.len()).collect();
+        }
+        let columns = self.columns();
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut count = 0;
+        // end of the last space on the line, the place to wrap at
+        let mut wrap_at = None;
+        let mut i = 0;
+        while i < self.text.len() {
+            let next = next_boundary(&self.text, i);
+            let grapheme = &self.text[i..next];
+            if grapheme == "\n" {
+                lines.push(start..i);
+                (start, count, wrap_at) = (next, 0, None);
+                i = next;
+                continue;
+            }
+            if count == columns {
+                let end = wrap_at.unwrap_or(i);
+                lines.push(start..end);
+                (start, count, wrap_at) = (end, grapheme_count(&self.text[end..i]), None);
+            }
+            count += 1;
+            if grapheme == " " {
+                wrap_at = Some(next);
+            }
+            i = next;
+        }
+        lines.push(start..self.text.len());
+        lines
+    }
+
+    /// Line and column of the cursor in characters.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let lines = self.lines();
+        let line = line_of(&lines, self.cursor);
+        (
+            line,
+            grapheme_count(&self.text[lines[line].start..self.cursor]),
+        )
+    }
+
+    /// Characters that fit across the area.
+    pub fn columns(&self) -> usize {
+        (self.area.size.width / CHAR_WIDTH).max(1) as usize
+    }
+
+    /// Lines that fit in the area, always one for a single line.
+    pub fn rows(&self) -> usize {
+        match self.mode {
+            TextMode::SingleLine => 1,
+            TextMode::Multiline => (self.area.size.height / LINE_HEIGHT).max(1) as usize,
+        }
+    }
+
+    pub fn scroll(&self) -> usize {
+        self.scroll
+    }
+
+    /// Draws the visible text with the selection, and the cursor if the field has the focus.
+    pub fn draw<D>(&self, target: &mut D, is_focused: bool) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let font =
+            FontRenderer::new::<fonts::u8g2_font_6x12_t_cyrillic>().with_ignore_unknown_chars(true);
+        let selection = self.selection().unwrap_or(0..0);
+        let (cursor_line, cursor_column) = self.cursor_position();
+        let (first_line, first_column) = match self.mode {
+            TextMode::SingleLine => (0, self.scroll),
+            TextMode::Multiline => (self.scroll, 0),
+        };
+
+        let lines = self.lines();
+        for (row, line) in lines.iter().enumerate().skip(first_line).take(self.rows()) {
+            let y = self.area.top_left.y + ((row - first_line) as u32 * LINE_HEIGHT) as i32;
+            let mut i = line.start;
+            let mut column = 0;
+            while i < line.end && column < first_column + self.columns() {
+                let next = next_boundary(&self.text, i);
+                if column >= first_column {
+                    let x =
+                        self.area.top_left.x + ((column - first_column) as u32 * CHAR_WIDTH) as i32;
+                    if selection.contains(&i) {
+                        let cell = Rectangle::new(
+                            Point::new(x, y),
+                            Size::new(CHAR_WIDTH, LINE_HEIGHT - 1),
+                        );
+                        target.fill_solid(&cell, SELECTION_COLOR)?;
+                    }
+                    draw_grapheme(&font, &self.text[i..next], Point::new(x, y), target)?;
+                }
+                i = next;
+                column += 1;
+            }
+
+            if is_focused && row == cursor_line && cursor_column >= first_column {
+                let x = self.area.top_left.x
+                    + ((cursor_column - first_column) as u32 * CHAR_WIDTH) as i32;
+                let cursor = Rectangle::new(Point::new(x, y), Size::new(1, LINE_HEIGHT - 1));
+                target.fill_solid(&cursor, CURSOR_COLOR)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The text as it can go in: without newlines on a single line.
+    fn accepted(&self, text: &str) -> String {
+        match self.mode {
+            TextMode::SingleLine => text.replace(['\n', '\r'], " "),
+            TextMode::Multiline => text.replace('\r', ""),
+        }
+    }
+
+    fn move_to(&mut self, to: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = to;
+        self.keep_cursor_visible();
+    }
+
+    fn move_lines(&mut self, by: isize, select: bool) -> bool {
+        let lines = self.lines();
+        let (line, column) = self.cursor_position();
+        let Some(target) = line.checked_add_signed(by).filter(|l| *l < lines.len()) else {
+            return false;
+        };
+        let mut to = lines[target].start;
+        for _ in 0..column {
+            if to >= lines[target].end {
+                break;
+            }
+            to = next_boundary(&self.text, to);
+        }
+        self.move_to(to, select);
+        true
+    }
+
+    /// Removes the selected text, returns false if nothing was selected.
+    fn delete_selection(&mut self) -> bool {
+        let Some(range) = self.selection() else {
+            self.anchor = None;
+            return false;
+        };
+        self.text.replace_range(range.clone(), "");
+        self.cursor = range.start;
+        self.anchor = None;
+        true
+    }
+
+    /// Start of the word before `from`, skipping the spaces and punctuation in between.
+    fn word_start(&self, from: usize) -> usize {
+        let mut i = from;
+        let mut in_word = false;
+        while i > 0 {
+            let prev = prev_boundary(&self.text, i);
+            let is_word = is_word_grapheme(&self.text[prev..i]);
+            if in_word && !is_word {
+                break;
+            }
+            in_word |= is_word;
+            i = prev;
+        }
+        i
+    }
+
+    /// End of the word after `from`, skipping the spaces and punctuation in between.
+    fn word_end(&self, from: usize) -> usize {
+        let mut i = from;
+        let mut in_word = false;
+        while i < self.text.len() {
+            let next = next_boundary(&self.text, i);
+            let is_word = is_word_grapheme(&self.text[i..next]);
+            if in_word && !is_word {
+                break;
+            }
+            in_word |= is_word;
+            i = next;
+        }
+        i
+    }
+
+    fn keep_cursor_visible(&mut self) {
+        let (line, column) = self.cursor_position();
+        let (position, visible) = match self.mode {
+            TextMode::SingleLine => (column, self.columns()),
+            TextMode::Multiline => (line, self.rows()),
+        };
+        if position < self.scroll {
+            self.scroll = position;
+        } else if position >= self.scroll + visible {
+            self.scroll = position + 1 - visible;
+        }
+    }
+}
+
+impl TextField {
+    /// Applies an editing key: Fn arrows move, with Shift they select, with Ctrl or Alt they go
+    /// by words; Fn+[ and Fn+] go home and end; Ctrl+C/X/V/A work in either layout.
+    /// Returns false for keys the field has no use for, like Enter, Esc or Tab.
+    pub fn handle_key(&mut self, keyboard_state: &KeyboardState) -> bool {
+        let Some((KeyEvent::Pressed, symbol)) = keyboard_state.pressed else {
+            return false;
+        };
+        let input = &keyboard_state.input_state;
+        let select = input.shift_pressed;
+        let by_word = input.ctrl_pressed || input.alt_pressed;
+
+        if input.ctrl_pressed {
+            // by scancode, in the Ru layout the letters are Cyrillic
+            match keyboard_state.key {
+                Some((KeyEvent::Pressed, Scancode::C)) => self.copy(),
+                Some((KeyEvent::Pressed, Scancode::X)) => self.cut(),
+                Some((KeyEvent::Pressed, Scancode::V)) => self.paste(),
+                Some((KeyEvent::Pressed, Scancode::A)) => self.select_all(),
+                _ if matches!(symbol, PressedSymbol::Char(_)) => return false,
+                _ => return self.handle_symbol(symbol, select, by_word),
+            }
+            return true;
+        }
+        self.handle_symbol(symbol, select, by_word)
+    }
+
+    fn handle_symbol(&mut self, symbol: PressedSymbol, select: bool, by_word: bool) -> bool {
+        match symbol {
+            PressedSymbol::Char(c) => self.insert(c),
+            PressedSymbol::Backspace => self.backspace(by_word),
+            PressedSymbol::Del => self.delete(by_word),
+            PressedSymbol::ArrowLeft => self.move_left(select, by_word),
+            PressedSymbol::ArrowRight => self.move_right(select, by_word),
+            PressedSymbol::ArrowUp if self.mode == TextMode::Multiline => {
+                return self.move_up(select)
+            }
+            PressedSymbol::ArrowDown if self.mode == TextMode::Multiline => {
+                return self.move_down(select)
+            }
+            PressedSymbol::Home => self.home(select),
+            PressedSymbol::End => self.end(select),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Start of the character after the one at `i`, its combining marks included.
+pub fn next_boundary(text: &str, i: usize) -> usize {
+    let mut chars = text[i..].char_indices();
+    if chars.next().is_none() {
+        return text.len();
+    }
+    chars
+        .find(|(_, c)| !is_combining_mark(*c))
+        .map_or(text.len(), |(offset, _)| i + offset)
+}
+
+/// Start of the character before `i`, its combining marks included.
+pub fn prev_boundary(text: &str, i: usize) -> usize {
+    text[..i]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !is_combining_mark(*c))
+        .map_or(0, |(offset, _)| offset)
+}
+
+pub fn grapheme_count(text: &str) -> usize {
+    text.chars().filter(|c| !is_combining_mark(*c)).count()
+}
+
+/// The last line starting at or before `offset`, a cursor at a wrap belongs to the next line.
+fn line_of(lines: &[Range<usize>], offset: usize) -> usize {
+    lines
+        .iter()
+        .rposition(|line| line.start <= offset)
+        .unwrap_or(0)
+}
+
+fn is_word_grapheme(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Draws the base char, a combining mark over it is drawn as a stress mark since the font
+/// has no glyphs for them.
+fn draw_grapheme<D>(
+    font: &FontRenderer,
+    grapheme: &str,
+    position: Point,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let mut chars = grapheme.chars();
+    let Some(base) = chars.next() else {
+        return Ok(());
+    };
+    font.render(
+        base,
+        position,
+        VerticalPosition::Top,
+        FontColor::Transparent(TEXT_COLOR),
+        target,
+    )
+    .map(|_| ())
+    .or_else(|e| match e {
+        FontError::DisplayError(e) => Err(e),
+        _ => Ok(()),
+    })?;
+    if chars.next().is_some() {
+        let mark = Rectangle::new(position + Point::new(3, 0), Size::new(1, 2));
+        target.fill_solid(&mark, TEXT_COLOR)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ten characters across, two lines down.
+    fn field(text: &str, mode: TextMode) -> TextField {
+        TextField::new(text, mode, Rectangle::new(Point::zero(), Size::new(60, 26)))
+    }
+
+    fn line_texts(field: &TextField) -> Vec<&str> {
+        field
+            .lines()
+            .into_iter()
+            .map(|line| &field.text()[line])
+            .collect()
+    }
+
+    #[test]
+    fn combining_mark_is_deleted_with_its_char() {
+        let stressed = "се\u{301}л";
+        let mut text = field(stressed, TextMode::SingleLine);
+        assert_eq!(text.cursor_position(), (0, 3));
+        text.move_left(false, false);
+        text.backspace(false);
+        assert_eq!(text.text(), "сл");
+        assert_eq!(text.cursor_position(), (0, 1));
+
+        let mut text = field(stressed, TextMode::SingleLine);
+        text.move_left(false, false);
+        text.move_left(false, false);
+        assert_eq!(text.cursor(), "с".len());
+        text.delete(false);
+        assert_eq!(text.text(), "сл");
+
+        assert_eq!(next_boundary(stressed, 2), stressed.len() - "л".len());
+        assert_eq!(prev_boundary(stressed, stressed.len() - "л".len()), 2);
+        assert_eq!(grapheme_count(stressed), 3);
+    }
+
+    #[test]
+    fn words_skip_punctuation() {
+        let mut text = field("Hello, world! foo", TextMode::SingleLine);
+        text.move_left(false, true);
+        assert_eq!(text.cursor(), 14);
+        text.move_left(false, true);
+        assert_eq!(text.cursor(), 7);
+        text.move_left(false, true);
+        assert_eq!(text.cursor(), 0);
+        text.move_left(false, true);
+        assert_eq!(text.cursor(), 0);
+
+        text.move_right(false, true);
+        assert_eq!(text.cursor(), 5);
+        text.move_right(false, true);
+        assert_eq!(text.cursor(), 12);
+
+        // the word goes together with the punctuation after it
+        text.move_right(false, false);
+        text.backspace(true);
+        assert_eq!(text.text(), "Hello,  foo");
+        text.delete(true);
+        assert_eq!(text.text(), "Hello, ");
+    }
+
+    #[test]
+    fn shift_selects_for_cut_and_paste() {
+        let mut text = field("cat dog", TextMode::SingleLine);
+        text.home(false);
+        text.move_right(true, true);
+        assert_eq!(text.selected_text(), Some("cat"));
+        text.move_right(true, false);
+        text.move_left(true, false);
+        assert_eq!(text.selection(), Some(0..3));
+
+        text.cut();
+        assert_eq!(text.text(), " dog");
+        assert_eq!(clipboard_text(), "cat");
+        assert_eq!(text.selection(), None);
+
+        text.end(false);
+        text.insert(' ');
+        text.paste();
+        assert_eq!(text.text(), " dog cat");
+
+        // typing replaces the selection, an arrow without Shift drops it
+        text.move_left(true, true);
+        text.insert_str("fox");
+        assert_eq!(text.text(), " dog fox");
+        text.select_all();
+        text.move_left(false, false);
+        assert_eq!((text.cursor(), text.selection()), (0, None));
+    }
+
+    #[test]
+    fn lines_wrap_at_spaces() {
+        let text = field("the quick brown fox", TextMode::Multiline);
+        assert_eq!(line_texts(&text), ["the quick ", "brown fox"]);
+
+        // a word longer than a line is cut
+        let text = field("abcdefghijklmnop qr", TextMode::Multiline);
+        assert_eq!(line_texts(&text), ["abcdefghij", "klmnop qr"]);
+
+        let text = field("ab\n\ncd", TextMode::Multiline);
+        assert_eq!(line_texts(&text), ["ab", "", "cd"]);
+        assert_eq!(text.cursor_position(), (2, 2));
+
+        let text = field("ab\ncd", TextMode::SingleLine);
+        assert_eq!(line_texts(&text), ["ab cd"]);
+    }
+
+    #[test]
+    fn scrolls_to_the_cursor() {
+        let mut text = field("0123456789abcde", TextMode::SingleLine);
+        assert_eq!(text.scroll(), 6);
+        text.home(false);
+        assert_eq!(text.scroll(), 0);
+        text.end(false);
+        assert_eq!(text.scroll(), 6);
+
+        let mut text = field("one\ntwo\nthree\nfour", TextMode::Multiline);
+        assert_eq!(text.rows(), 2);
+        assert_eq!(text.scroll(), 2);
+        assert!(text.move_up(false));
+        assert_eq!(text.scroll(), 2);
+        assert!(text.move_up(false));
+        assert_eq!(text.scroll(), 1);
+        assert!(text.move_up(false));
+        assert!(!text.move_up(false));
+        assert_eq!((text.scroll(), text.cursor_position()), (0, (0, 3)));
+        assert!(text.move_down(false));
+        assert_eq!(text.scroll(), 0);
+    }
+}